@@ -0,0 +1,220 @@
+//! 呼び出し規約 (System V AMD64 と Windows x64) の記述と、関数のプロローグ・エピローグの生成
+
+use crate::{
+    asm::Assembler,
+    instruction::{Alu, AluOp, Pop, Push, Ret},
+    Mem64,
+    Reg64::{self, *},
+};
+
+/// Register usage and stack layout of a calling convention.
+///
+/// Only integer and pointer arguments are described.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CallConv {
+    /// Registers carrying the first integer arguments, in order.
+    pub args: &'static [Reg64],
+    pub ret: Reg64,
+    /// Registers a call may clobber.
+    pub caller_saved: &'static [Reg64],
+    /// Registers a function must restore before returning (besides RSP).
+    pub callee_saved: &'static [Reg64],
+    /// Alignment of RSP at a `call` instruction.
+    pub stack_align: u32,
+    /// Space the caller reserves above the return address for the callee
+    /// to spill the register arguments.
+    pub shadow_space: u32,
+}
+
+/// The System V AMD64 ABI used on Linux, macOS and the BSDs (`extern "sysv64"`).
+pub const SYSV64: CallConv = CallConv {
+    args: &[RDI, RSI, RDX, RCX, R8, R9],
+    ret: RAX,
+    caller_saved: &[RAX, RCX, RDX, RSI, RDI, R8, R9, R10, R11],
+    callee_saved: &[RBX, RBP, R12, R13, R14, R15],
+    stack_align: 16,
+    shadow_space: 0,
+};
+
+/// The Windows x64 ABI (`extern "win64"`).
+pub const WIN64: CallConv = CallConv {
+    args: &[RCX, RDX, R8, R9],
+    ret: RAX,
+    caller_saved: &[RAX, RCX, RDX, R8, R9, R10, R11],
+    callee_saved: &[RBX, RBP, RDI, RSI, R12, R13, R14, R15],
+    stack_align: 16,
+    shadow_space: 32,
+};
+
+impl CallConv {
+    /// Register holding the `n`-th (0-based) integer argument, if it is
+    /// passed in a register.
+    pub fn arg(&self, n: usize) -> Option<Reg64> {
+        self.args.get(n).copied()
+    }
+
+    pub fn is_callee_saved(&self, reg: Reg64) -> bool {
+        self.callee_saved.contains(&reg)
+    }
+
+    /// Lays out the frame of a function that writes the registers in `used`
+    /// and needs `locals` bytes of stack.
+    pub fn frame(&self, used: &[Reg64], locals: u32) -> Frame {
+        // 規約の順に並べて、同じレジスタを2回積まないようにする
+        let saved: Vec<Reg64> = self
+            .callee_saved
+            .iter()
+            .copied()
+            .filter(|reg| used.contains(reg))
+            .collect();
+
+        // 入口では戻りアドレスの分だけずれている
+        let align = self.stack_align;
+        let pushed = 8 + 8 * saved.len() as u32;
+        let mut stack_size = (locals + self.shadow_space).div_ceil(align) * align;
+        stack_size += (align - pushed % align) % align;
+
+        Frame {
+            saved,
+            stack_size,
+            shadow_space: self.shadow_space,
+        }
+    }
+}
+
+/// The stack frame of one function, as laid out by [`CallConv::frame`].
+///
+/// After the prologue RSP is aligned for calls, the outgoing shadow space
+/// (if any) is at `[rsp]`, and the locals follow it.
+///
+/// ```
+/// use at_64::{abi::SYSV64, asm::Assembler, instruction::Mov, Reg64::*};
+///
+/// let frame = SYSV64.frame(&[RAX, RBX], 8);
+/// let mut asm = Assembler::new();
+/// frame.prologue(&mut asm);
+/// asm.emit(Mov(RBX, RDI));
+/// asm.emit(Mov(frame.local(0), RBX));
+/// asm.emit(Mov(RAX, frame.local(0)));
+/// frame.epilogue(&mut asm);
+///
+/// // push rbx; sub rsp, 16; ...; add rsp, 16; pop rbx; ret
+/// let code = asm.finish().unwrap();
+/// assert_eq!(&code[..5], [0x53, 0x48, 0x83, 0xEC, 0x10]);
+/// assert_eq!(&code[code.len() - 6..], [0x48, 0x83, 0xC4, 0x10, 0x5B, 0xC3]);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Frame {
+    saved: Vec<Reg64>,
+    stack_size: u32,
+    shadow_space: u32,
+}
+
+impl Frame {
+    /// Callee-saved registers the prologue pushes, in push order.
+    pub fn saved(&self) -> &[Reg64] {
+        &self.saved
+    }
+
+    /// Bytes the prologue subtracts from RSP after the pushes.
+    pub fn stack_size(&self) -> u32 {
+        self.stack_size
+    }
+
+    /// The local at `offset` bytes into the locals area.
+    pub fn local(&self, offset: i32) -> Mem64 {
+        Mem64::reg_offset(RSP, self.shadow_space as i32 + offset)
+    }
+
+    pub fn prologue(&self, asm: &mut Assembler) {
+        for reg in self.saved.iter() {
+            asm.emit(Push(*reg));
+        }
+        if self.stack_size > 0 {
+            asm.emit(Alu(AluOp::Sub, RSP, self.stack_size as i32));
+        }
+    }
+
+    /// Undoes the prologue and returns.
+    pub fn epilogue(&self, asm: &mut Assembler) {
+        if self.stack_size > 0 {
+            asm.emit(Alu(AluOp::Add, RSP, self.stack_size as i32));
+        }
+        for reg in self.saved.iter().rev() {
+            asm.emit(Pop(*reg));
+        }
+        asm.emit(Ret());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_frame() {
+        // (conv, used, locals, saved, stack_size)
+        type Case = (CallConv, &'static [Reg64], u32, &'static [Reg64], u32);
+        let cases: [Case; 8] = [
+            (SYSV64, &[], 0, &[], 8),
+            (SYSV64, &[RAX, RDI], 0, &[], 8),
+            (SYSV64, &[RBX], 0, &[RBX], 0),
+            (SYSV64, &[R12, RBX], 0, &[RBX, R12], 8),
+            (SYSV64, &[RBX], 20, &[RBX], 32),
+            (SYSV64, &[RBX, RBX, R15], 16, &[RBX, R15], 24),
+            (WIN64, &[], 0, &[], 40),
+            (WIN64, &[RDI, RSI, RBX], 8, &[RBX, RDI, RSI], 48),
+        ];
+        for (conv, used, locals, saved, stack_size) in cases.iter() {
+            let frame = conv.frame(used, *locals);
+            assert_eq!(frame.saved(), *saved);
+            assert_eq!(frame.stack_size(), *stack_size);
+
+            // 入口の戻りアドレスを含めて 16 の倍数になる
+            let total = 8 + 8 * saved.len() as u32 + stack_size;
+            assert_eq!(total % 16, 0);
+            assert!(stack_size - conv.shadow_space >= *locals);
+        }
+    }
+
+    #[test]
+    fn test_code() {
+        let frame = WIN64.frame(&[R12, RSI], 0);
+        let mut asm = Assembler::new();
+        frame.prologue(&mut asm);
+        frame.epilogue(&mut asm);
+
+        // push rsi; push r12; sub rsp, 40; add rsp, 40; pop r12; pop rsi; ret
+        let expected = [
+            0x56, 0x41, 0x54, 0x48, 0x83, 0xEC, 0x28, 0x48, 0x83, 0xC4, 0x28, 0x41, 0x5C, 0x5E,
+            0xC3,
+        ];
+        assert_eq!(asm.finish().unwrap(), expected);
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[test]
+    fn test_run() {
+        use crate::{instruction::Mov, jit::JitMemory};
+
+        // 呼び出し先保存レジスタを壊しても呼び出し元に影響しない
+        let used = [RAX, RBX, RBP, R12, R13, R14, R15];
+        for n in 0..=used.len() {
+            let frame = SYSV64.frame(&used[..n], 8 * n as u32);
+            let mut asm = Assembler::new();
+            frame.prologue(&mut asm);
+            for (i, reg) in used[..n].iter().enumerate() {
+                asm.emit(Mov(*reg, RDI));
+                asm.emit(Mov(frame.local(8 * i as i32), *reg));
+            }
+            // 戻り値は rsp & 15
+            asm.emit(Mov(RAX, RSP));
+            asm.emit(Alu(AluOp::And, RAX, 15));
+            frame.epilogue(&mut asm);
+
+            let mem = JitMemory::new(&asm.finish().unwrap()).unwrap();
+            let f: extern "sysv64" fn(u64) -> u64 = unsafe { mem.get() };
+            assert_eq!(f(!0), 0);
+        }
+    }
+}
@@ -0,0 +1,647 @@
+use crate::{
+    object::{Reloc, RelocKind},
+    ByteCode, EncodeError, Instruction,
+};
+use std::{
+    convert::TryFrom,
+    fmt::{Display, Error as FmtError, Formatter},
+};
+
+/// A position in the code emitted by an [`Assembler`].
+///
+/// Labels can be referenced before they are bound; the references are
+/// patched when the assembler is finished.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Label(usize);
+
+/// A symbol resolved by the linker rather than by the [`Assembler`].
+///
+/// Emitting an instruction that refers to it records a [`Reloc`] instead of
+/// patching the code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sym<'a>(pub &'a str);
+
+/// The GOT entry of a symbol, as in `mov rax, [rip + sym@GOTPCREL]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Got<'a>(pub &'a str);
+
+/// ラベルまでの相対距離を書き込む `ByteCode` のフィールド
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RelField {
+    /// `addr_disp` (e.g. `[RIP + disp32]`)
+    Disp,
+    /// `imm` (e.g. `jmp rel8`, `call rel32`)
+    Imm,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AsmError {
+    /// The label was referenced but never bound, or was made by another
+    /// [`Assembler`].
+    UnboundLabel(Label),
+    /// The label was bound more than once.
+    LabelBoundTwice(Label),
+    /// The distance to the label does not fit in the rel8/rel32 field.
+    RelOutOfRange(Label),
+}
+
+impl Display for AsmError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            AsmError::UnboundLabel(label) => write!(f, "label {} is never bound", label.0),
+            AsmError::LabelBoundTwice(label) => write!(f, "label {} is bound twice", label.0),
+            AsmError::RelOutOfRange(label) => {
+                write!(f, "label {} is out of range of the relative field", label.0)
+            }
+        }
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+/// Anything that can be appended to an [`Assembler`].
+pub trait Emit {
+    /// Appends `self` to `asm`, or returns why an operand cannot be encoded.
+    fn try_emit(&self, asm: &mut Assembler) -> Result<(), EncodeError>;
+
+    /// Like [`try_emit`](Emit::try_emit), but panics if an operand cannot be
+    /// encoded.
+    fn emit(&self, asm: &mut Assembler) {
+        self.try_emit(asm).unwrap()
+    }
+}
+
+impl Emit for ByteCode {
+    fn try_emit(&self, asm: &mut Assembler) -> Result<(), EncodeError> {
+        asm.push(*self);
+        Ok(())
+    }
+}
+
+impl<T> Emit for T
+where
+    T: Into<Instruction> + Copy,
+{
+    fn try_emit(&self, asm: &mut Assembler) -> Result<(), EncodeError> {
+        asm.push((*self).into().try_bytecode()?);
+        Ok(())
+    }
+}
+
+impl RelField {
+    /// `code` の先頭からのフィールドの位置
+    fn offset(&self, code: &ByteCode) -> usize {
+        match self {
+            RelField::Disp => code.disp_offset(),
+            RelField::Imm => code.imm_offset(),
+        }
+    }
+
+    fn len(&self, code: &ByteCode) -> usize {
+        match self {
+            RelField::Disp => code.addr_disp.len(),
+            RelField::Imm => code.imm.len(),
+        }
+    }
+}
+
+/// Offsets of the labels in [`Assembled`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LabelOffsets(Vec<Option<usize>>);
+
+impl LabelOffsets {
+    /// The offset from the start of the code, or `None` if `label` was never
+    /// bound.
+    pub fn get(&self, label: Label) -> Option<usize> {
+        self.0.get(label.0).copied().flatten()
+    }
+}
+
+/// The result of [`Assembler::assemble`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Assembled {
+    pub code: Vec<u8>,
+    pub labels: LabelOffsets,
+    /// Fields left for the linker, with offsets from the start of `code`.
+    pub relocs: Vec<Reloc>,
+}
+
+enum Item {
+    Code(ByteCode),
+    /// リンカが埋めるフィールドを持つ命令
+    Reloc {
+        code: ByteCode,
+        field: RelField,
+        symbol: String,
+        kind: RelocKind,
+    },
+    LabelRef {
+        code: ByteCode,
+        field: RelField,
+        label: Label,
+    },
+    /// finish 時に short (rel8) / near (rel32) のどちらかを選ぶ分岐命令
+    Branch {
+        short: ByteCode,
+        near: ByteCode,
+        label: Label,
+    },
+    Bind(Label),
+}
+
+impl Item {
+    /// エンコードする `ByteCode` と、パッチするフィールド
+    fn code(&self, near: bool) -> Option<(&ByteCode, Option<(RelField, Label)>)> {
+        match self {
+            Item::Code(code) | Item::Reloc { code, .. } => Some((code, None)),
+            Item::LabelRef { code, field, label } => Some((code, Some((*field, *label)))),
+            Item::Branch { short, label, .. } if !near => {
+                Some((short, Some((RelField::Imm, *label))))
+            }
+            Item::Branch { near, label, .. } => Some((near, Some((RelField::Imm, *label)))),
+            Item::Bind(_) => None,
+        }
+    }
+}
+
+/// Growable code buffer with labels.
+///
+/// ```
+/// use at_64::{asm::Assembler, instruction::{Lea, Mov, Syscall}, Reg64::*};
+///
+/// let mut asm = Assembler::new();
+/// let msg = asm.new_label();
+/// asm.emit(Lea(RSI, msg));
+/// asm.emit(Mov(RAX, 1u64));
+/// asm.emit(Syscall());
+/// asm.bind(msg);
+/// let code = asm.finish().unwrap();
+/// assert_eq!(&code[..7], [0x48, 0x8D, 0x35, 0x07, 0x00, 0x00, 0x00]);
+/// ```
+#[derive(Default)]
+pub struct Assembler {
+    items: Vec<Item>,
+    labels: usize,
+}
+
+impl Assembler {
+    pub fn new() -> Self {
+        Assembler::default()
+    }
+
+    pub fn new_label(&mut self) -> Label {
+        self.labels += 1;
+        Label(self.labels - 1)
+    }
+
+    /// Binds `label` to the current end of the code.
+    pub fn bind(&mut self, label: Label) {
+        self.items.push(Item::Bind(label));
+    }
+
+    /// Appends `inst`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if an operand of `inst` cannot be encoded; see
+    /// [`try_emit`](Assembler::try_emit).
+    pub fn emit<I: Emit>(&mut self, inst: I) {
+        inst.emit(self);
+    }
+
+    /// Appends `inst`, or returns why an operand cannot be encoded.
+    ///
+    /// Nothing is appended on error, even if `inst` emits several
+    /// instructions.
+    pub fn try_emit<I: Emit>(&mut self, inst: I) -> Result<(), EncodeError> {
+        let len = self.items.len();
+        let result = inst.try_emit(self);
+        if result.is_err() {
+            self.items.truncate(len);
+        }
+        result
+    }
+
+    pub fn push(&mut self, code: ByteCode) {
+        self.items.push(Item::Code(code));
+    }
+
+    /// Appends `code` whose `field` is to be patched with the distance from
+    /// the end of `code` to `label`.
+    ///
+    /// The width of the field (1 or 4 bytes) decides between rel8 and rel32.
+    pub fn push_label_ref(&mut self, code: ByteCode, field: RelField, label: Label) {
+        self.items.push(Item::LabelRef { code, field, label });
+    }
+
+    /// Appends a branch to `label` that has a rel8 (`short`) and a rel32
+    /// (`near`) encoding, both carrying the distance in `imm`.
+    ///
+    /// `finish` picks `short` whenever the distance fits in rel8.
+    pub fn push_branch(&mut self, short: ByteCode, near: ByteCode, label: Label) {
+        self.items.push(Item::Branch { short, near, label });
+    }
+
+    /// Appends `code` whose `field` is to be filled by the linker with the
+    /// address of `symbol`.
+    ///
+    /// For PC-relative kinds the addend accounts for the bytes between the
+    /// field and the end of `code`.
+    pub fn push_reloc(&mut self, code: ByteCode, field: RelField, symbol: &str, kind: RelocKind) {
+        self.items.push(Item::Reloc {
+            code,
+            field,
+            symbol: symbol.to_string(),
+            kind,
+        });
+    }
+
+    /// Resolves every label reference and returns the encoded code.
+    pub fn finish(self) -> Result<Vec<u8>, AsmError> {
+        self.assemble().map(|assembled| assembled.code)
+    }
+
+    /// Like [`finish`](Assembler::finish), but also returns where each label
+    /// was bound and the relocations for the linker.
+    pub fn assemble(self) -> Result<Assembled, AsmError> {
+        let near = self.relax()?;
+        let offsets = self.label_offsets(&near)?;
+
+        let mut bytes = Vec::new();
+        let mut relocs = Vec::new();
+        for (item, near) in self.items.iter().zip(near) {
+            let (code, rel) = match item.code(near) {
+                Some(code) => code,
+                None => continue,
+            };
+
+            let start = bytes.len();
+            bytes.extend_from_slice(code.to_bytes().bytes());
+
+            if let Item::Reloc {
+                field,
+                symbol,
+                kind,
+                ..
+            } = item
+            {
+                let field_offset = start + field.offset(code);
+                let addend = if kind.is_pc_relative() {
+                    field_offset as i64 - bytes.len() as i64
+                } else {
+                    0
+                };
+                relocs.push(Reloc {
+                    offset: field_offset as u64,
+                    symbol: symbol.clone(),
+                    kind: *kind,
+                    addend,
+                });
+            }
+
+            if let Some((field, label)) = rel {
+                let target = offset_of(&offsets, label)?;
+                let rel = target as i64 - bytes.len() as i64;
+                let field_offset = start + field.offset(code);
+                let field = &mut bytes[field_offset..field_offset + field.len(code)];
+                write_rel(field, rel).ok_or(AsmError::RelOutOfRange(label))?;
+            }
+        }
+
+        Ok(Assembled {
+            code: bytes,
+            labels: LabelOffsets(offsets),
+            relocs,
+        })
+    }
+
+    /// 分岐命令ごとに near (rel32) が必要かどうかを決める
+    ///
+    /// 全て short から始め、rel8 に収まらないものを near に広げる。
+    /// 命令が縮むことは無いので、変化が無くなるまで繰り返せば収束する。
+    fn relax(&self) -> Result<Vec<bool>, AsmError> {
+        let mut near = vec![false; self.items.len()];
+
+        loop {
+            let offsets = self.label_offsets(&near)?;
+            let mut changed = false;
+            let mut offset = 0;
+
+            for (i, item) in self.items.iter().enumerate() {
+                if let Some((code, _)) = item.code(near[i]) {
+                    offset += code.len();
+                }
+
+                if let Item::Branch { label, .. } = item {
+                    let target = offset_of(&offsets, *label)?;
+                    let rel = target as i64 - offset as i64;
+                    if !near[i] && i8::try_from(rel).is_err() {
+                        near[i] = true;
+                        changed = true;
+                    }
+                }
+            }
+
+            if !changed {
+                return Ok(near);
+            }
+        }
+    }
+
+    fn label_offsets(&self, near: &[bool]) -> Result<Vec<Option<usize>>, AsmError> {
+        let mut offsets = vec![None; self.labels];
+        let mut offset = 0;
+
+        for (item, near) in self.items.iter().zip(near) {
+            match item.code(*near) {
+                Some((code, _)) => offset += code.len(),
+                None => {
+                    if let Item::Bind(label) = item {
+                        let slot = offsets
+                            .get_mut(label.0)
+                            .ok_or(AsmError::UnboundLabel(*label))?;
+                        if slot.replace(offset).is_some() {
+                            return Err(AsmError::LabelBoundTwice(*label));
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(offsets)
+    }
+}
+
+/// 他の Assembler のラベルは範囲外になるので、未定義と同じ扱いにする
+fn offset_of(offsets: &[Option<usize>], label: Label) -> Result<usize, AsmError> {
+    offsets
+        .get(label.0)
+        .copied()
+        .flatten()
+        .ok_or(AsmError::UnboundLabel(label))
+}
+
+fn write_rel(field: &mut [u8], rel: i64) -> Option<()> {
+    match field.len() {
+        1 => field.copy_from_slice(&i8::try_from(rel).ok()?.to_le_bytes()),
+        4 => field.copy_from_slice(&i32::try_from(rel).ok()?.to_le_bytes()),
+        _ => return None,
+    }
+    Some(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        instruction::{Call, Cond, Jcc, Jmp, Lea, Mov, Ret, Syscall},
+        BytesAtMost, Mem64,
+        Reg64::*,
+        Reg8,
+    };
+
+    /// jmp rel8
+    fn jmp_rel8() -> ByteCode {
+        let mut code = ByteCode::new();
+        code.opcode = BytesAtMost::from([0xEB]);
+        code.imm = BytesAtMost::from(0u8);
+        code
+    }
+
+    #[test]
+    fn test_forward_ref() {
+        let mut asm = Assembler::new();
+        let label = asm.new_label();
+        asm.emit(Lea(RDI, label));
+        asm.emit(Syscall());
+        asm.bind(label);
+        asm.emit(Mov(Mem64::reg(RDI), RAX));
+
+        assert_eq!(
+            asm.finish(),
+            Ok(vec![
+                0x48, 0x8D, 0x3D, 0x02, 0x00, 0x00, 0x00, // lea rdi, [rip + 2]
+                0x0F, 0x05, // syscall
+                0x48, 0x89, 0x07, // mov [rdi], rax
+            ])
+        );
+    }
+
+    #[test]
+    fn test_backward_ref() {
+        let mut asm = Assembler::new();
+        let label = asm.new_label();
+        asm.bind(label);
+        asm.emit(Syscall());
+        asm.push_label_ref(jmp_rel8(), RelField::Imm, label);
+        asm.emit(Lea(RAX, label));
+
+        assert_eq!(
+            asm.finish(),
+            Ok(vec![
+                0x0F, 0x05, // syscall
+                0xEB, 0xFC, // jmp -4
+                0x48, 0x8D, 0x05, 0xF5, 0xFF, 0xFF, 0xFF, // lea rax, [rip - 11]
+            ])
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        let mut asm = Assembler::new();
+        let label = asm.new_label();
+        asm.emit(Lea(RAX, label));
+        assert_eq!(asm.finish(), Err(AsmError::UnboundLabel(label)));
+
+        let mut asm = Assembler::new();
+        let label = asm.new_label();
+        asm.bind(label);
+        asm.bind(label);
+        assert_eq!(asm.finish(), Err(AsmError::LabelBoundTwice(label)));
+
+        let mut asm = Assembler::new();
+        let label = asm.new_label();
+        asm.push_label_ref(jmp_rel8(), RelField::Imm, label);
+        for _ in 0..64 {
+            asm.emit(Syscall());
+        }
+        asm.bind(label);
+        assert_eq!(asm.finish(), Err(AsmError::RelOutOfRange(label)));
+
+        // 他の Assembler のラベルを bind したり参照したりする
+        let mut other = Assembler::new();
+        other.new_label();
+        let foreign = other.new_label();
+        let builds: [fn(&mut Assembler, Label); 3] = [
+            |asm, label| asm.bind(label),
+            |asm, label| asm.emit(Jmp(label)),
+            |asm, label| asm.emit(Call(label)),
+        ];
+        for build in builds.iter() {
+            let mut asm = Assembler::new();
+            build(&mut asm, foreign);
+            assert_eq!(asm.finish(), Err(AsmError::UnboundLabel(foreign)));
+        }
+    }
+
+    #[test]
+    fn test_branch_relaxation() {
+        let mut asm = Assembler::new();
+        let top = asm.new_label();
+        let end = asm.new_label();
+        asm.bind(top);
+        asm.emit(Jcc(Cond::E, end));
+        asm.emit(Jmp(top));
+        asm.bind(end);
+
+        assert_eq!(
+            asm.finish(),
+            Ok(vec![
+                0x74, 0x02, // je end
+                0xEB, 0xFC, // jmp top
+            ])
+        );
+
+        // 127 bytes ちょうどなら short のまま
+        let mut asm = Assembler::new();
+        let end = asm.new_label();
+        asm.emit(Jmp(end));
+        for _ in 0..127 {
+            asm.push(nop());
+        }
+        asm.bind(end);
+        let bytes = asm.finish().unwrap();
+        assert_eq!(bytes[..2], [0xEB, 0x7F]);
+        assert_eq!(bytes.len(), 129);
+
+        // 128 bytes なら near
+        let mut asm = Assembler::new();
+        let end = asm.new_label();
+        asm.emit(Jcc(Cond::NE, end));
+        for _ in 0..128 {
+            asm.push(nop());
+        }
+        asm.bind(end);
+        let bytes = asm.finish().unwrap();
+        assert_eq!(bytes[..6], [0x0F, 0x85, 0x80, 0x00, 0x00, 0x00]);
+        assert_eq!(bytes.len(), 134);
+
+        // 後方は -128 まで short
+        let mut asm = Assembler::new();
+        let top = asm.new_label();
+        asm.bind(top);
+        for _ in 0..126 {
+            asm.push(nop());
+        }
+        asm.emit(Jmp(top));
+        asm.emit(Jmp(top));
+        let bytes = asm.finish().unwrap();
+        assert_eq!(bytes[126..128], [0xEB, 0x80]);
+        assert_eq!(bytes[128..], [0xE9, 0x7B, 0xFF, 0xFF, 0xFF]);
+    }
+
+    #[test]
+    fn test_branch_relaxation_cascade() {
+        // 内側の分岐が near に広がると、外側の分岐も rel8 に収まらなくなる
+        let mut asm = Assembler::new();
+        let outer = asm.new_label();
+        let inner = asm.new_label();
+        asm.emit(Jmp(outer));
+        asm.emit(Jmp(inner));
+        for _ in 0..124 {
+            asm.push(nop());
+        }
+        asm.bind(outer);
+        for _ in 0..4 {
+            asm.push(nop());
+        }
+        asm.bind(inner);
+        let bytes = asm.finish().unwrap();
+        assert_eq!(bytes[..5], [0xE9, 0x81, 0x00, 0x00, 0x00]);
+        assert_eq!(bytes[5..10], [0xE9, 0x80, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_call() {
+        let mut asm = Assembler::new();
+        let func = asm.new_label();
+        asm.emit(Call(func));
+        asm.emit(Ret());
+        asm.bind(func);
+        asm.emit(Mov(RAX, RDI));
+        asm.emit(Ret());
+        asm.emit(Call(func));
+
+        assert_eq!(
+            asm.finish(),
+            Ok(vec![
+                0xE8, 0x01, 0x00, 0x00, 0x00, // call func
+                0xC3, // ret
+                0x48, 0x89, 0xF8, // func: mov rax, rdi
+                0xC3, // ret
+                0xE8, 0xF7, 0xFF, 0xFF, 0xFF, // call func
+            ])
+        );
+    }
+
+    #[test]
+    fn test_label_offsets() {
+        let mut asm = Assembler::new();
+        let start = asm.new_label();
+        let end = asm.new_label();
+        let unused = asm.new_label();
+        asm.bind(start);
+        asm.emit(Syscall());
+        asm.emit(Jmp(start));
+        asm.bind(end);
+
+        let Assembled { code, labels, .. } = asm.assemble().unwrap();
+        assert_eq!(code.len(), 4);
+        assert_eq!(labels.get(start), Some(0));
+        assert_eq!(labels.get(end), Some(4));
+        assert_eq!(labels.get(unused), None);
+    }
+
+    #[test]
+    fn test_try_emit() {
+        use crate::linux::{Arg, LinuxSyscall};
+
+        // index の RSP はエンコードできない
+        let bad = Mem64::Sib {
+            base: Some(RAX),
+            disp: 0,
+            index: RSP,
+            scale: 1,
+        };
+
+        let mut asm = Assembler::new();
+        assert_eq!(asm.try_emit(Mov(RAX, bad)), Err(EncodeError::RspAsIndex));
+        assert_eq!(
+            asm.try_emit(Mov(Reg8::AH, Reg8::SIL)),
+            Err(EncodeError::HighByteWithRex)
+        );
+
+        // 途中まで出力した命令も取り消す
+        let args = [Arg::Imm(1), Arg::Reg(RDI), Arg::Mem(bad)];
+        assert_eq!(
+            asm.try_emit(LinuxSyscall(1, &args)),
+            Err(EncodeError::RspAsIndex)
+        );
+
+        asm.try_emit(Syscall()).unwrap();
+        assert_eq!(asm.finish(), Ok(vec![0x0F, 0x05]));
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_emit_panics() {
+        let mut asm = Assembler::new();
+        asm.emit(Mov(Reg8::AH, Reg8::SIL));
+    }
+
+    fn nop() -> ByteCode {
+        let mut code = ByteCode::new();
+        code.opcode = BytesAtMost::from([0x90]);
+        code
+    }
+}
@@ -0,0 +1,160 @@
+//! Intel 記法のアセンブリを機械語に変換する
+//!
+//! ```text
+//! at64-as [-f bin|hex|elf|exe] [-o OUTPUT] INPUT
+//! ```
+
+use at_64::{
+    asm::Assembler,
+    elf,
+    object::{Object, Section, SectionKind, Symbol},
+    parse::{self, Stmt},
+};
+use std::{collections::HashMap, fs, io::Write as _, process, slice};
+
+const USAGE: &str = "usage: at64-as [-f bin|hex|elf|exe] [-o OUTPUT] INPUT";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    /// 機械語そのまま
+    Bin,
+    /// オフセット・バイト列・命令の一覧
+    Hex,
+    /// ELF64 relocatable object
+    Elf,
+    /// `_start` から実行する静的リンクされた ELF64 実行ファイル
+    Exe,
+}
+
+struct Args {
+    format: Format,
+    output: Option<String>,
+    input: String,
+}
+
+fn main() {
+    if let Err(msg) = run() {
+        eprintln!("at64-as: {}", msg);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args = parse_args(std::env::args().skip(1))?;
+
+    let src = fs::read_to_string(&args.input).map_err(|e| format!("{}: {}", args.input, e))?;
+    let stmts = parse::parse(&src).map_err(|e| format!("{}:{}", args.input, e))?;
+
+    // 文ごとの位置を知るため、各文の前にラベルを置く
+    let mut asm = Assembler::new();
+    let mut labels = HashMap::new();
+    let mut marks = Vec::new();
+    for stmt in stmts.iter() {
+        let mark = asm.new_label();
+        asm.bind(mark);
+        marks.push(mark);
+        parse::emit(slice::from_ref(stmt), &mut asm, &mut labels);
+    }
+    let assembled = asm
+        .assemble()
+        .map_err(|e| format!("{}: {}", args.input, e))?;
+    let (code, offsets) = (assembled.code, assembled.labels);
+
+    let out = match args.format {
+        Format::Bin => code,
+        Format::Hex => {
+            let mut out = String::new();
+            for (i, stmt) in stmts.iter().enumerate() {
+                let start = offsets.get(marks[i]).unwrap();
+                let end = marks
+                    .get(i + 1)
+                    .map(|mark| offsets.get(*mark).unwrap())
+                    .unwrap_or(code.len());
+                let bytes: Vec<String> = code[start..end]
+                    .iter()
+                    .map(|b| format!("{:02x}", b))
+                    .collect();
+                out += &format!("{:08x}  {:<30}  {}\n", start, bytes.join(" "), stmt);
+            }
+            out.into_bytes()
+        }
+        Format::Elf | Format::Exe => {
+            let globals: Vec<&String> = stmts
+                .iter()
+                .filter_map(|stmt| match stmt {
+                    Stmt::Global(name) => Some(name),
+                    _ => None,
+                })
+                .collect();
+            let symbols: Vec<Symbol> = stmts
+                .iter()
+                .filter_map(|stmt| match stmt {
+                    Stmt::Label(name) => Some(Symbol {
+                        name: name.clone(),
+                        def: Some((SectionKind::Text, offsets.get(labels[name]).unwrap() as u64)),
+                        global: globals.contains(&name),
+                    }),
+                    _ => None,
+                })
+                .collect();
+            let obj = Object {
+                sections: vec![Section::new(SectionKind::Text, code)],
+                symbols,
+            };
+            match args.format {
+                Format::Exe => elf::write_executable(&obj, "_start")
+                    .map_err(|e| format!("{}: {}", args.input, e))?,
+                _ => elf::write_object(&obj),
+            }
+        }
+    };
+
+    let path = match (&args.output, args.format) {
+        (Some(path), _) => path.as_str(),
+        (None, Format::Hex) => return std::io::stdout().write_all(&out).map_err(|e| e.to_string()),
+        (None, _) => "a.out",
+    };
+    fs::write(path, out).map_err(|e| format!("{}: {}", path, e))?;
+
+    #[cfg(unix)]
+    if args.format == Format::Exe {
+        use std::os::unix::fs::PermissionsExt as _;
+        let perm = fs::Permissions::from_mode(0o755);
+        fs::set_permissions(path, perm).map_err(|e| format!("{}: {}", path, e))?;
+    }
+
+    Ok(())
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut format = Format::Bin;
+    let mut output = None;
+    let mut input = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-f" => {
+                format = match args.next().as_deref() {
+                    Some("bin") => Format::Bin,
+                    Some("hex") => Format::Hex,
+                    Some("elf") => Format::Elf,
+                    Some("exe") => Format::Exe,
+                    _ => return Err(USAGE.to_string()),
+                }
+            }
+            "-o" => output = Some(args.next().ok_or(USAGE)?),
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    Ok(Args {
+        format,
+        output,
+        input: input.ok_or(USAGE)?,
+    })
+}
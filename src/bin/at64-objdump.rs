@@ -0,0 +1,148 @@
+//! 機械語を逆アセンブルして表示する
+//!
+//! ```text
+//! at64-objdump [-M intel|att] [--base ADDR] [--raw] INPUT
+//! ```
+//!
+//! ELF ファイルなら `.text` を、それ以外 (または `--raw`) ならファイル全体を逆アセンブルする。
+
+use at_64::{
+    decode::decode,
+    elf,
+    instruction::{Call, Jcc, Jmp},
+    syntax::{Att, Format as _, Intel, Syntax},
+    Instruction,
+};
+use std::{
+    fs,
+    io::{self, BufWriter, Write},
+    process,
+};
+
+const USAGE: &str = "usage: at64-objdump [-M intel|att] [--base ADDR] [--raw] INPUT";
+
+struct Args {
+    syntax: Box<dyn Syntax>,
+    base: Option<u64>,
+    raw: bool,
+    input: String,
+}
+
+fn main() {
+    if let Err(msg) = run() {
+        eprintln!("at64-objdump: {}", msg);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), String> {
+    let args = parse_args(std::env::args().skip(1))?;
+
+    let data = fs::read(&args.input).map_err(|e| format!("{}: {}", args.input, e))?;
+    let (addr, bytes) = match elf::read_text(&data) {
+        Some(text) if !args.raw => (text.addr, text.bytes),
+        _ => (0, &data[..]),
+    };
+    let base = args.base.unwrap_or(addr);
+
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    match disassemble(&mut out, bytes, base, &*args.syntax).and_then(|()| out.flush()) {
+        // `| head` などで読み手が先に閉じたら、そこで終える
+        Err(e) if e.kind() == io::ErrorKind::BrokenPipe => Ok(()),
+        result => result.map_err(|e| format!("stdout: {}", e)),
+    }
+}
+
+/// `bytes` を先頭のアドレスが `base` として逆アセンブルし、1命令1行で書き出す
+fn disassemble(
+    out: &mut impl Write,
+    bytes: &[u8],
+    base: u64,
+    syntax: &dyn Syntax,
+) -> io::Result<()> {
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let addr = base.wrapping_add(pos as u64);
+        let (text, len) = match decode(&bytes[pos..]) {
+            Ok((inst, len)) => {
+                let next = addr.wrapping_add(len as u64);
+                let text = match target(&inst, next) {
+                    Some(target) => format!("{}  # {:#x}", inst.display(syntax), target),
+                    None => inst.display(syntax).to_string(),
+                };
+                (text, len)
+            }
+            // objdump と同じく1バイト進めて続ける
+            Err(_) => ("(bad)".to_string(), 1),
+        };
+
+        let hex: Vec<String> = bytes[pos..pos + len]
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        writeln!(out, "{:8x}:  {:<30}  {}", addr, hex.join(" "), text)?;
+
+        pos += len;
+    }
+
+    Ok(())
+}
+
+/// 相対分岐の飛び先。`next` は次の命令のアドレス
+fn target(inst: &Instruction, next: u64) -> Option<u64> {
+    let rel = match inst {
+        Instruction::JmpI8(Jmp(rel)) => *rel as i64,
+        Instruction::JmpI32(Jmp(rel)) => *rel as i64,
+        Instruction::JccI8(Jcc(_, rel)) => *rel as i64,
+        Instruction::JccI32(Jcc(_, rel)) => *rel as i64,
+        Instruction::CallI32(Call(rel)) => *rel as i64,
+        _ => return None,
+    };
+    Some(next.wrapping_add(rel as u64))
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut syntax: Box<dyn Syntax> = Box::new(Intel);
+    let mut base = None;
+    let mut raw = false;
+    let mut input = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-M" => {
+                syntax = match args.next().as_deref() {
+                    Some("intel") => Box::new(Intel),
+                    Some("att") => Box::new(Att),
+                    _ => return Err(USAGE.to_string()),
+                }
+            }
+            "--base" => {
+                let addr = args.next().ok_or(USAGE)?;
+                base = Some(parse_addr(&addr).ok_or_else(|| format!("invalid address {}", addr))?);
+            }
+            "--raw" => raw = true,
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            _ if input.is_none() && !arg.starts_with('-') => input = Some(arg),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+
+    Ok(Args {
+        syntax,
+        base,
+        raw,
+        input: input.ok_or(USAGE)?,
+    })
+}
+
+/// 10進 または `0x` 付きの16進
+fn parse_addr(s: &str) -> Option<u64> {
+    match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok(),
+        None => s.parse().ok(),
+    }
+}
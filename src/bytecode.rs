@@ -1,7 +1,11 @@
-use crate::BytesAtMost;
+use crate::{BytesAtMost, EncodeError};
 use byteorder::WriteBytesExt as _;
-use std::io::{Cursor, Write as _};
+use std::{
+    fmt::{Display, Error as FmtError, Formatter},
+    io::{Cursor, Write as _},
+};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ByteCode {
     pub prefix: Option<u8>,        // 0 ~ 1 byte
     pub rex: Option<Rex>,          // 0 ~ 1 byte
@@ -9,7 +13,7 @@ pub struct ByteCode {
     pub mod_rm: Option<ModRM>,     // 0 ~ 1 byte
     pub sib: Option<Sib>,          // 0 ~ 1 byte
     pub addr_disp: BytesAtMost<4>, // 0 ~ 4 byte
-    pub imm: BytesAtMost<4>,       // 0 ~ 4 byte
+    pub imm: BytesAtMost<8>,       // 0 ~ 8 byte
 }
 
 impl ByteCode {
@@ -25,16 +29,42 @@ impl ByteCode {
         }
     }
 
-    pub fn to_bytes(&self) -> BytesAtMost<15> {
-        let len = self.prefix.is_some() as usize
+    /// エンコード後のバイト数
+    pub fn len(&self) -> usize {
+        self.disp_offset() + self.addr_disp.len() + self.imm.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 命令の先頭から addr disp フィールドまでのバイト数
+    pub fn disp_offset(&self) -> usize {
+        self.prefix.is_some() as usize
             + self.rex.is_some() as usize
             + self.opcode.len()
             + self.mod_rm.is_some() as usize
             + self.sib.is_some() as usize
-            + self.addr_disp.len()
-            + self.imm.len();
+    }
 
-        let mut bytes = BytesAtMost::new(len);
+    /// 命令の先頭から immutable val フィールドまでのバイト数
+    pub fn imm_offset(&self) -> usize {
+        self.disp_offset() + self.addr_disp.len()
+    }
+
+    /// REX の W,R,X,B が一つも立っていなければ REX prefix を省略する
+    ///
+    /// `force` の場合 (SPL/BPL/SIL/DIL を使う場合) は空の REX prefix を残す
+    pub fn set_rex(&mut self, rex: Rex, force: bool) {
+        if force || rex.byte() != Rex::new().byte() {
+            self.rex = Some(rex);
+        } else {
+            self.rex = None;
+        }
+    }
+
+    pub fn to_bytes(&self) -> BytesAtMost<15> {
+        let mut bytes = BytesAtMost::new(self.len());
 
         let mut cursor = Cursor::new(bytes.bytes_mut());
 
@@ -64,12 +94,26 @@ impl ByteCode {
     }
 }
 
+/// `48 89 6c c5 2a` のような16進のバイト列
+impl Display for ByteCode {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        for (i, byte) in self.to_bytes().bytes().iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{:02x}", byte)?;
+        }
+        Ok(())
+    }
+}
+
 impl Default for ByteCode {
     fn default() -> Self {
         ByteCode::new()
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Rex(u8);
 
 impl Rex {
@@ -78,8 +122,16 @@ impl Rex {
     }
 
     pub fn from_raw(raw: u8) -> Self {
-        assert!(raw & 0b1111_0000 == 0b0100_0000);
-        Rex(raw)
+        Rex::try_from_raw(raw).unwrap()
+    }
+
+    /// 上位4bitが 0100 でなければ REX prefix ではない
+    pub fn try_from_raw(raw: u8) -> Result<Self, EncodeError> {
+        if raw & 0b1111_0000 == 0b0100_0000 {
+            Ok(Rex(raw))
+        } else {
+            Err(EncodeError::InvalidRex(raw))
+        }
     }
 
     pub fn byte(&self) -> u8 {
@@ -135,6 +187,13 @@ impl Rex {
     }
 }
 
+impl Default for Rex {
+    fn default() -> Self {
+        Rex::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ModRM(u8);
 
 impl ModRM {
@@ -156,8 +215,15 @@ impl ModRM {
 
     /// mode is 2 bits.
     pub fn set_mode(&mut self, mode: u8) {
-        assert!(mode <= 0b11);
+        self.try_set_mode(mode).unwrap()
+    }
+
+    pub fn try_set_mode(&mut self, mode: u8) -> Result<(), EncodeError> {
+        if mode > 0b11 {
+            return Err(EncodeError::FieldOutOfRange("ModR/M mode", mode));
+        }
         self.0 = (self.0 & 0b00_111_111) + (mode << 6);
+        Ok(())
     }
 
     pub fn reg(&self) -> u8 {
@@ -166,8 +232,15 @@ impl ModRM {
 
     /// reg is 3 bits.
     pub fn set_reg(&mut self, reg: u8) {
-        assert!(reg <= 0b111);
+        self.try_set_reg(reg).unwrap()
+    }
+
+    pub fn try_set_reg(&mut self, reg: u8) -> Result<(), EncodeError> {
+        if reg > 0b111 {
+            return Err(EncodeError::FieldOutOfRange("ModR/M reg", reg));
+        }
         self.0 = (self.0 & 0b11_000_111) + (reg << 3);
+        Ok(())
     }
 
     pub fn rm(&self) -> u8 {
@@ -176,20 +249,44 @@ impl ModRM {
 
     /// rm is 3 bits.
     pub fn set_rm(&mut self, rm: u8) {
-        assert!(rm <= 0b111);
+        self.try_set_rm(rm).unwrap()
+    }
+
+    pub fn try_set_rm(&mut self, rm: u8) -> Result<(), EncodeError> {
+        if rm > 0b111 {
+            return Err(EncodeError::FieldOutOfRange("ModR/M rm", rm));
+        }
         self.0 = (self.0 & 0b11_111_000) + rm;
+        Ok(())
     }
 }
 
+impl Default for ModRM {
+    fn default() -> Self {
+        ModRM::new()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Sib(u8);
 
 impl Sib {
     pub fn new(scale: u8, index: u8, base: u8) -> Self {
-        assert!(scale <= 0b11);
-        assert!(index <= 0b111);
-        assert!(base <= 0b111);
+        Sib::try_new(scale, index, base).unwrap()
+    }
 
-        Sib(scale << 6 | index << 3 | base)
+    pub fn try_new(scale: u8, index: u8, base: u8) -> Result<Self, EncodeError> {
+        if scale > 0b11 {
+            return Err(EncodeError::InvalidScale(scale));
+        }
+        if index > 0b111 {
+            return Err(EncodeError::FieldOutOfRange("SIB index", index));
+        }
+        if base > 0b111 {
+            return Err(EncodeError::FieldOutOfRange("SIB base", base));
+        }
+
+        Ok(Sib(scale << 6 | index << 3 | base))
     }
 
     pub fn from_raw(raw: u8) -> Self {
@@ -212,3 +309,38 @@ impl Sib {
         self.0 & 0b00000111
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_try_constructors() {
+        assert_eq!(Rex::try_from_raw(0x48), Ok(Rex(0x48)));
+        assert_eq!(Rex::try_from_raw(0x66), Err(EncodeError::InvalidRex(0x66)));
+
+        assert_eq!(Sib::try_new(3, 0b000, 0b101), Ok(Sib(0b11_000_101)));
+        assert_eq!(Sib::try_new(4, 0, 0), Err(EncodeError::InvalidScale(4)));
+        assert_eq!(
+            Sib::try_new(0, 8, 0),
+            Err(EncodeError::FieldOutOfRange("SIB index", 8))
+        );
+
+        let mut mod_rm = ModRM::new();
+        assert_eq!(mod_rm.try_set_mode(0b11), Ok(()));
+        assert_eq!(
+            mod_rm.try_set_mode(0b100),
+            Err(EncodeError::FieldOutOfRange("ModR/M mode", 0b100))
+        );
+        assert_eq!(
+            mod_rm.try_set_rm(0b1000),
+            Err(EncodeError::FieldOutOfRange("ModR/M rm", 0b1000))
+        );
+        assert_eq!(mod_rm, ModRM(0b11_000_000));
+
+        assert_eq!(
+            BytesAtMost::<4>::try_new(5),
+            Err(EncodeError::TooManyBytes { len: 5, max: 4 })
+        );
+    }
+}
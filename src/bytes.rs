@@ -1,5 +1,7 @@
+use crate::EncodeError;
 use byteorder::{WriteBytesExt as _, LE};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct BytesAtMost<const MAX: usize> {
     bytes: [u8; MAX],
     len: usize,
@@ -7,12 +9,18 @@ pub struct BytesAtMost<const MAX: usize> {
 
 impl<const MAX: usize> BytesAtMost<MAX> {
     pub fn new(len: usize) -> Self {
-        assert!(len <= MAX);
+        BytesAtMost::try_new(len).unwrap()
+    }
+
+    pub fn try_new(len: usize) -> Result<Self, EncodeError> {
+        if len > MAX {
+            return Err(EncodeError::TooManyBytes { len, max: MAX });
+        }
 
-        BytesAtMost {
+        Ok(BytesAtMost {
             bytes: [0; MAX],
             len,
-        }
+        })
     }
 
     pub fn len(&self) -> usize {
@@ -50,6 +58,12 @@ impl<const MAX: usize> From<u8> for BytesAtMost<MAX> {
     }
 }
 
+impl<const MAX: usize> From<i8> for BytesAtMost<MAX> {
+    fn from(n: i8) -> Self {
+        BytesAtMost::from(n as u8)
+    }
+}
+
 impl<const MAX: usize> From<u32> for BytesAtMost<MAX> {
     fn from(n: u32) -> Self {
         assert!(MAX >= 4);
@@ -60,11 +74,17 @@ impl<const MAX: usize> From<u32> for BytesAtMost<MAX> {
     }
 }
 
+impl<const MAX: usize> From<i32> for BytesAtMost<MAX> {
+    fn from(n: i32) -> Self {
+        BytesAtMost::from(n as u32)
+    }
+}
+
 impl<const MAX: usize> From<u64> for BytesAtMost<MAX> {
     fn from(n: u64) -> Self {
-        assert!(MAX >= 4);
+        assert!(MAX >= 8);
 
-        let mut bytes = BytesAtMost::new(4);
+        let mut bytes = BytesAtMost::new(8);
         bytes.bytes_mut().write_u64::<LE>(n).unwrap();
         bytes
     }
@@ -0,0 +1,466 @@
+//! PE/COFF (AMD64) のオブジェクトファイルの入出力
+//!
+//! COFF には PLT も GOT も無い。`Plt32` は `Pc32` と同じ `IMAGE_REL_AMD64_REL32` になり、
+//! `GotPcRel` はインポートアドレステーブルのスロット `__imp_<symbol>` への
+//! `IMAGE_REL_AMD64_REL32` になる。
+
+use crate::object::{Object, Reloc, RelocKind, Section, SectionKind, Symbol};
+use byteorder::{ByteOrder as _, WriteBytesExt as _, LE};
+use std::collections::HashMap;
+
+const IMAGE_FILE_MACHINE_AMD64: u16 = 0x8664;
+
+const HEADER_SIZE: usize = 20;
+const SECTION_SIZE: usize = 40;
+const SYMBOL_SIZE: usize = 18;
+const RELOC_SIZE: usize = 10;
+
+const IMAGE_SCN_CNT_CODE: u32 = 0x20;
+const IMAGE_SCN_CNT_INITIALIZED_DATA: u32 = 0x40;
+const IMAGE_SCN_CNT_UNINITIALIZED_DATA: u32 = 0x80;
+const IMAGE_SCN_MEM_EXECUTE: u32 = 0x2000_0000;
+const IMAGE_SCN_MEM_READ: u32 = 0x4000_0000;
+const IMAGE_SCN_MEM_WRITE: u32 = 0x8000_0000;
+const IMAGE_SCN_ALIGN_SHIFT: u32 = 20;
+
+const IMAGE_SYM_CLASS_EXTERNAL: u8 = 2;
+const IMAGE_SYM_CLASS_STATIC: u8 = 3;
+
+const IMAGE_REL_AMD64_ADDR64: u16 = 0x1;
+const IMAGE_REL_AMD64_REL32: u16 = 0x4;
+
+const IMP_PREFIX: &str = "__imp_";
+
+/// Writes `obj` as an AMD64 COFF object, as consumed by `link.exe` and `lld-link`.
+///
+/// Relocation addends are stored in the relocated fields, as COFF expects.
+pub fn write_object(obj: &Object) -> Vec<u8> {
+    let nsects = obj.sections.len();
+    let section_number = |kind: SectionKind| {
+        obj.sections
+            .iter()
+            .position(|section| section.kind == kind)
+            .unwrap() as i16
+            + 1
+    };
+
+    // リロケーション先の名前。GOT 経由の参照は __imp_ を付ける
+    let target = |reloc: &Reloc| match reloc.kind {
+        RelocKind::GotPcRel => format!("{}{}", IMP_PREFIX, reloc.symbol),
+        _ => reloc.symbol.clone(),
+    };
+
+    // セクションシンボルは補助レコードと合わせて2つずつ
+    let mut symbols = obj.symbols.clone();
+    for reloc in obj.sections.iter().flat_map(|section| &section.relocs) {
+        let name = target(reloc);
+        if !symbols.iter().any(|sym| sym.name == name) {
+            symbols.push(Symbol::undefined(&name));
+        }
+    }
+    let mut symbol_index = HashMap::new();
+    for (i, sym) in symbols.iter().enumerate() {
+        symbol_index
+            .entry(sym.name.clone())
+            .or_insert((nsects * 2 + i) as u32);
+    }
+
+    let mut buf = vec![0; HEADER_SIZE + SECTION_SIZE * nsects];
+    let mut strtab = StrTab::new();
+
+    for (i, section) in obj.sections.iter().enumerate() {
+        let mut data_offset = 0;
+        if section.kind != SectionKind::Bss {
+            data_offset = buf.len() as u32;
+            let start = buf.len();
+            buf.extend_from_slice(&section.data);
+            for reloc in section.relocs.iter() {
+                let field = start + reloc.offset as usize;
+                match reloc.kind {
+                    RelocKind::Abs64 => LE::write_i64(&mut buf[field..field + 8], reloc.addend),
+                    // フィールドの末尾からの相対になる
+                    _ => LE::write_i32(&mut buf[field..field + 4], reloc.addend as i32 + 4),
+                }
+            }
+        }
+
+        let reloc_offset = match section.relocs.len() {
+            0 => 0,
+            _ => buf.len() as u32,
+        };
+        for reloc in section.relocs.iter() {
+            let ty = match reloc.kind {
+                RelocKind::Abs64 => IMAGE_REL_AMD64_ADDR64,
+                _ => IMAGE_REL_AMD64_REL32,
+            };
+            buf.write_u32::<LE>(reloc.offset as u32).unwrap();
+            buf.write_u32::<LE>(symbol_index[&target(reloc)]).unwrap();
+            buf.write_u16::<LE>(ty).unwrap();
+        }
+
+        let mut shdr = Vec::with_capacity(SECTION_SIZE);
+        shdr.extend_from_slice(&section_name(section.kind));
+        shdr.write_u32::<LE>(0).unwrap(); // VirtualSize
+        shdr.write_u32::<LE>(0).unwrap(); // VirtualAddress
+        shdr.write_u32::<LE>(section.data.len() as u32).unwrap();
+        shdr.write_u32::<LE>(data_offset).unwrap();
+        shdr.write_u32::<LE>(reloc_offset).unwrap();
+        shdr.write_u32::<LE>(0).unwrap(); // PointerToLinenumbers
+        shdr.write_u16::<LE>(section.relocs.len() as u16).unwrap();
+        shdr.write_u16::<LE>(0).unwrap(); // NumberOfLinenumbers
+        let align = section.align.clamp(1, 8192).trailing_zeros() + 1;
+        shdr.write_u32::<LE>(characteristics(section.kind) | align << IMAGE_SCN_ALIGN_SHIFT)
+            .unwrap();
+        let start = HEADER_SIZE + SECTION_SIZE * i;
+        buf[start..start + SECTION_SIZE].copy_from_slice(&shdr);
+    }
+
+    let symtab_offset = buf.len() as u32;
+    for (i, section) in obj.sections.iter().enumerate() {
+        buf.extend_from_slice(&section_name(section.kind));
+        buf.write_u32::<LE>(0).unwrap(); // Value
+        buf.write_i16::<LE>(i as i16 + 1).unwrap();
+        buf.write_u16::<LE>(0).unwrap(); // Type
+        buf.write_u8(IMAGE_SYM_CLASS_STATIC).unwrap();
+        buf.write_u8(1).unwrap(); // NumberOfAuxSymbols
+
+        // 補助レコード (セクション定義)
+        buf.write_u32::<LE>(section.data.len() as u32).unwrap();
+        buf.write_u16::<LE>(section.relocs.len() as u16).unwrap();
+        buf.write_u16::<LE>(0).unwrap(); // NumberOfLinenumbers
+        buf.extend_from_slice(&[0; 10]); // CheckSum, Number, Selection
+    }
+    for sym in symbols.iter() {
+        buf.extend_from_slice(&strtab.name(&sym.name));
+        let (value, number) = match sym.def {
+            Some((kind, offset)) => (offset as u32, section_number(kind)),
+            None => (0, 0),
+        };
+        buf.write_u32::<LE>(value).unwrap();
+        buf.write_i16::<LE>(number).unwrap();
+        buf.write_u16::<LE>(0).unwrap(); // Type
+        let class = match sym.global {
+            true => IMAGE_SYM_CLASS_EXTERNAL,
+            false => IMAGE_SYM_CLASS_STATIC,
+        };
+        buf.write_u8(class).unwrap();
+        buf.write_u8(0).unwrap(); // NumberOfAuxSymbols
+    }
+    buf.extend_from_slice(&strtab.finish());
+
+    // ファイルヘッダ
+    let mut header = Vec::with_capacity(HEADER_SIZE);
+    header.write_u16::<LE>(IMAGE_FILE_MACHINE_AMD64).unwrap();
+    header.write_u16::<LE>(nsects as u16).unwrap();
+    header.write_u32::<LE>(0).unwrap(); // TimeDateStamp
+    header.write_u32::<LE>(symtab_offset).unwrap();
+    header
+        .write_u32::<LE>((nsects * 2 + symbols.len()) as u32)
+        .unwrap();
+    header.write_u16::<LE>(0).unwrap(); // SizeOfOptionalHeader
+    header.write_u16::<LE>(0).unwrap(); // Characteristics
+    buf[..HEADER_SIZE].copy_from_slice(&header);
+
+    buf
+}
+
+/// Reads back an object written by [`write_object`].
+///
+/// The implicit addends are moved into [`Reloc::addend`] and the fields are
+/// zeroed. `IMAGE_REL_AMD64_REL32` reads back as [`RelocKind::Pc32`], or as
+/// [`RelocKind::GotPcRel`] when it targets an `__imp_` symbol. Returns `None`
+/// if `data` is malformed or uses sections or relocation types that
+/// [`Object`] cannot express.
+pub fn read_object(data: &[u8]) -> Option<Object> {
+    let header = data.get(..HEADER_SIZE)?;
+    if LE::read_u16(header) != IMAGE_FILE_MACHINE_AMD64 {
+        return None;
+    }
+    let nsects = LE::read_u16(&header[2..]) as usize;
+    let symtab_offset = LE::read_u32(&header[8..]) as usize;
+    let nsyms = LE::read_u32(&header[12..]) as usize;
+    let strtab = data.get(symtab_offset.checked_add(nsyms.checked_mul(SYMBOL_SIZE)?)?..)?;
+
+    let mut obj = Object::new();
+    let mut reloc_ranges = Vec::new();
+    for i in 0..nsects {
+        let start = HEADER_SIZE + SECTION_SIZE * i;
+        let shdr = data.get(start..start + SECTION_SIZE)?;
+        let kind = section_kind(&shdr[..8])?;
+        let size = LE::read_u32(&shdr[16..]) as usize;
+        let offset = LE::read_u32(&shdr[20..]) as usize;
+        let reloc_offset = LE::read_u32(&shdr[24..]) as usize;
+        let nrelocs = LE::read_u16(&shdr[32..]) as usize;
+        let align = LE::read_u32(&shdr[36..]) >> IMAGE_SCN_ALIGN_SHIFT & 0xF;
+
+        let bytes = match kind {
+            SectionKind::Bss => vec![0; size],
+            _ => data.get(offset..offset.checked_add(size)?)?.to_vec(),
+        };
+        let mut section = Section::new(kind, bytes);
+        section.align = 1 << align.saturating_sub(1);
+        obj.sections.push(section);
+        reloc_ranges.push((reloc_offset, nrelocs));
+    }
+
+    // 補助レコードの分も含めたシンボル表の添字から名前を引けるようにする
+    let mut names = vec![None; nsyms];
+    let mut i = 0;
+    while i < nsyms {
+        let entry = data
+            .get(symtab_offset + i * SYMBOL_SIZE..)?
+            .get(..SYMBOL_SIZE)?;
+        let name = match LE::read_u32(entry) {
+            0 => {
+                let name = strtab.get(LE::read_u32(&entry[4..]) as usize..)?;
+                &name[..name.iter().position(|b| *b == 0)?]
+            }
+            _ => &entry[..entry[..8].iter().position(|b| *b == 0).unwrap_or(8)],
+        };
+        let name = String::from_utf8(name.to_vec()).ok()?;
+        let value = LE::read_u32(&entry[8..]) as u64;
+        let number = LE::read_i16(&entry[12..]);
+        let class = entry[16];
+        let naux = entry[17] as usize;
+
+        // セクションシンボルは Object には含めない
+        if naux == 0 {
+            let def = match number {
+                0 => None,
+                n if n > 0 => Some((obj.sections.get(n as usize - 1)?.kind, value)),
+                _ => return None,
+            };
+            let global = match class {
+                IMAGE_SYM_CLASS_EXTERNAL => true,
+                IMAGE_SYM_CLASS_STATIC => false,
+                _ => return None,
+            };
+            if def.is_some() || !name.starts_with(IMP_PREFIX) {
+                obj.symbols.push(Symbol {
+                    name: name.clone(),
+                    def,
+                    global,
+                });
+            }
+        }
+        names[i] = Some(name);
+        i += 1 + naux;
+    }
+
+    for (section, (offset, nrelocs)) in obj.sections.iter_mut().zip(reloc_ranges) {
+        let relocs = data.get(offset..offset + nrelocs * RELOC_SIZE)?;
+        for reloc in relocs.chunks_exact(RELOC_SIZE) {
+            let offset = LE::read_u32(reloc) as u64;
+            let name = names.get(LE::read_u32(&reloc[4..]) as usize)?.clone()?;
+            let (kind, symbol) = match LE::read_u16(&reloc[8..]) {
+                IMAGE_REL_AMD64_ADDR64 => (RelocKind::Abs64, name),
+                IMAGE_REL_AMD64_REL32 => match name.strip_prefix(IMP_PREFIX) {
+                    Some(name) => (RelocKind::GotPcRel, name.to_string()),
+                    None => (RelocKind::Pc32, name),
+                },
+                _ => return None,
+            };
+
+            let field = section
+                .data
+                .get_mut(offset as usize..offset as usize + kind.size())?;
+            let addend = match kind {
+                RelocKind::Abs64 => LE::read_i64(field),
+                _ => LE::read_i32(field) as i64 - 4,
+            };
+            field.iter_mut().for_each(|b| *b = 0);
+
+            section.relocs.push(Reloc {
+                offset,
+                symbol,
+                kind,
+                addend,
+            });
+        }
+    }
+
+    Some(obj)
+}
+
+fn section_name(kind: SectionKind) -> [u8; 8] {
+    let name: &[u8] = match kind {
+        SectionKind::Text => b".text",
+        SectionKind::Data => b".data",
+        SectionKind::Rodata => b".rdata",
+        SectionKind::Bss => b".bss",
+    };
+    let mut buf = [0; 8];
+    buf[..name.len()].copy_from_slice(name);
+    buf
+}
+
+fn section_kind(name: &[u8]) -> Option<SectionKind> {
+    [
+        SectionKind::Text,
+        SectionKind::Data,
+        SectionKind::Rodata,
+        SectionKind::Bss,
+    ]
+    .iter()
+    .copied()
+    .find(|kind| section_name(*kind) == name)
+}
+
+/// アラインメント以外の `Characteristics`
+fn characteristics(kind: SectionKind) -> u32 {
+    match kind {
+        SectionKind::Text => IMAGE_SCN_CNT_CODE | IMAGE_SCN_MEM_EXECUTE | IMAGE_SCN_MEM_READ,
+        SectionKind::Data => {
+            IMAGE_SCN_CNT_INITIALIZED_DATA | IMAGE_SCN_MEM_READ | IMAGE_SCN_MEM_WRITE
+        }
+        SectionKind::Rodata => IMAGE_SCN_CNT_INITIALIZED_DATA | IMAGE_SCN_MEM_READ,
+        SectionKind::Bss => {
+            IMAGE_SCN_CNT_UNINITIALIZED_DATA | IMAGE_SCN_MEM_READ | IMAGE_SCN_MEM_WRITE
+        }
+    }
+}
+
+/// 文字列表。先頭 4 byte は表全体の大きさ
+struct StrTab(Vec<u8>);
+
+impl StrTab {
+    fn new() -> Self {
+        StrTab(vec![0; 4])
+    }
+
+    /// シンボル表の名前フィールド。8 byte を超える名前は文字列表に置く
+    fn name(&mut self, name: &str) -> [u8; 8] {
+        let mut buf = [0; 8];
+        if name.len() <= 8 {
+            buf[..name.len()].copy_from_slice(name.as_bytes());
+        } else {
+            LE::write_u32(&mut buf[4..], self.0.len() as u32);
+            self.0.extend_from_slice(name.as_bytes());
+            self.0.push(0);
+        }
+        buf
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        let len = self.0.len() as u32;
+        LE::write_u32(&mut self.0, len);
+        self.0
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        asm::{Assembler, Got, Sym},
+        instruction::{Call, Jmp, Lea, Mov, Ret},
+        Reg64::*,
+    };
+
+    /// `testdata/coff.obj` と同じ内容
+    fn sample() -> Object {
+        let mut asm = Assembler::new();
+        asm.emit(Lea(RCX, Sym("msg")));
+        asm.emit(Call(Sym("puts")));
+        asm.emit(Mov(RAX, Got("counter")));
+        asm.emit(Mov(RCX, Sym("table")));
+        asm.emit(Ret());
+        asm.emit(Jmp(Sym("exit")));
+        let code = asm.assemble().unwrap();
+
+        let mut text = Section::new(SectionKind::Text, code.code);
+        text.relocs = code.relocs;
+        let mut data = Section::new(SectionKind::Data, vec![0; 8]);
+        data.relocs.push(Reloc {
+            offset: 0,
+            symbol: "msg".to_string(),
+            kind: RelocKind::Abs64,
+            addend: 2,
+        });
+
+        Object {
+            sections: vec![
+                text,
+                Section::new(SectionKind::Rodata, b"hello\0".to_vec()),
+                data,
+                Section::new(SectionKind::Bss, vec![0; 16]),
+            ],
+            symbols: vec![
+                Symbol::global("main", SectionKind::Text, 0),
+                Symbol::local("msg", SectionKind::Rodata, 0),
+                Symbol::global("table", SectionKind::Data, 0),
+                Symbol::global("counter", SectionKind::Bss, 8),
+                Symbol::global("a_long_symbol_name", SectionKind::Bss, 0),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_golden() {
+        let golden = include_bytes!("testdata/coff.obj");
+        assert_eq!(write_object(&sample()), &golden[..]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let obj = sample();
+        let read = read_object(&write_object(&obj)).unwrap();
+
+        // REL32 からは Pc32 と Plt32 を区別できない
+        let mut expected = obj.clone();
+        for reloc in expected.sections[0].relocs.iter_mut() {
+            if reloc.kind == RelocKind::Plt32 {
+                reloc.kind = RelocKind::Pc32;
+            }
+        }
+        expected.symbols.push(Symbol::undefined("puts"));
+        expected.symbols.push(Symbol::undefined("exit"));
+        assert_eq!(read, expected);
+    }
+
+    #[test]
+    fn test_relocs() {
+        let bytes = write_object(&sample());
+        let text = LE::read_u32(&bytes[HEADER_SIZE + 20..]) as usize;
+        let relocs = LE::read_u32(&bytes[HEADER_SIZE + 24..]) as usize;
+
+        // (offset, type, symbol, field)
+        let cases: [(u32, u16, &str, i64); 5] = [
+            (3, IMAGE_REL_AMD64_REL32, "msg", 0),
+            (8, IMAGE_REL_AMD64_REL32, "puts", 0),
+            (15, IMAGE_REL_AMD64_REL32, "__imp_counter", 0),
+            (21, IMAGE_REL_AMD64_ADDR64, "table", 0),
+            (31, IMAGE_REL_AMD64_REL32, "exit", 0),
+        ];
+        let symtab = LE::read_u32(&bytes[8..]) as usize;
+        let nsyms = LE::read_u32(&bytes[12..]) as usize;
+        let strtab = &bytes[symtab + nsyms * SYMBOL_SIZE..];
+        for (i, case) in cases.iter().enumerate() {
+            let reloc = &bytes[relocs + i * RELOC_SIZE..];
+            let sym = &bytes[symtab + LE::read_u32(&reloc[4..]) as usize * SYMBOL_SIZE..];
+            let name = match LE::read_u32(sym) {
+                0 => &strtab[LE::read_u32(&sym[4..]) as usize..],
+                _ => sym,
+            };
+            let name = &name[..name.iter().position(|b| *b == 0).unwrap()];
+            let field = text + case.0 as usize;
+            let field = match case.1 {
+                IMAGE_REL_AMD64_ADDR64 => LE::read_i64(&bytes[field..]),
+                _ => LE::read_i32(&bytes[field..]) as i64,
+            };
+
+            assert_eq!(LE::read_u32(reloc), case.0);
+            assert_eq!(LE::read_u16(&reloc[8..]), case.1);
+            assert_eq!(name, case.2.as_bytes());
+            assert_eq!(field, case.3);
+        }
+    }
+
+    #[test]
+    fn test_malformed() {
+        let bytes = write_object(&sample());
+        assert_eq!(read_object(&bytes[..100]), None);
+        assert_eq!(read_object(&bytes[2..]), None);
+        assert_eq!(read_object(b"\x7FELF"), None);
+    }
+}
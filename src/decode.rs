@@ -0,0 +1,743 @@
+use crate::{
+    instruction::{
+        Alu, AluOp, Call, Cdq, Cdqe, Cl, Cond, Cqo, Cwd, DoubleShift, DoubleShiftOp, Imul, ImulImm,
+        Instruction, Jcc, Jmp, Lahf, Lea, Mov, MulDiv, MulOp, Pop, Push, Ret, RetImm, Sahf, Shift,
+        ShiftOp, Syscall,
+    },
+    ByteCode, BytesAtMost, Mem64, ModRM, Ptr, Reg, Reg16, Reg64, Rex, Sib, Size,
+};
+use std::fmt::{Display, Error as FmtError, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The input ended in the middle of an instruction.
+    UnexpectedEnd,
+    /// More legacy prefixes than `ByteCode::prefix` can hold.
+    TooManyPrefixes,
+    /// The opcode is not in the decoder's table.
+    UnknownOpcode(BytesAtMost<3>),
+    /// The bytes are well-formed but there is no typed instruction for them.
+    Unsupported,
+}
+
+impl Display for DecodeError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of input"),
+            DecodeError::TooManyPrefixes => write!(f, "too many legacy prefixes"),
+            DecodeError::UnknownOpcode(opcode) => {
+                write!(f, "unknown opcode {:02X?}", opcode.bytes())
+            }
+            DecodeError::Unsupported => write!(f, "unsupported instruction"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Decodes a single instruction at the head of `bytes`.
+///
+/// Returns the typed instruction and the number of bytes it occupies.
+pub fn decode(bytes: &[u8]) -> Result<(Instruction, usize), DecodeError> {
+    let (code, len) = decode_bytecode(bytes)?;
+    Ok((lift(&code)?, len))
+}
+
+/// Splits a single instruction at the head of `bytes` into its `ByteCode` fields.
+///
+/// Returns the `ByteCode` and the number of bytes it occupies.
+pub fn decode_bytecode(bytes: &[u8]) -> Result<(ByteCode, usize), DecodeError> {
+    let mut reader = Reader { bytes, pos: 0 };
+    let mut code = ByteCode::new();
+
+    // legacy prefix
+    while let Some(byte) = reader.peek().filter(|byte| is_legacy_prefix(*byte)) {
+        if code.prefix.is_some() {
+            return Err(DecodeError::TooManyPrefixes);
+        }
+        code.prefix = Some(byte);
+        reader.pos += 1;
+    }
+
+    // REX prefix
+    if let Some(byte @ 0x40..=0x4F) = reader.peek() {
+        code.rex = Some(Rex::from_raw(byte));
+        reader.pos += 1;
+    }
+
+    // opcode
+    code.opcode = match reader.read_u8()? {
+        0x0F => match reader.read_u8()? {
+            escape @ (0x38 | 0x3A) => BytesAtMost::from([0x0F, escape, reader.read_u8()?]),
+            byte => BytesAtMost::from([0x0F, byte]),
+        },
+        byte => BytesAtMost::from([byte]),
+    };
+
+    let has_mod_rm = has_mod_rm(&code).ok_or(DecodeError::UnknownOpcode(code.opcode))?;
+
+    if has_mod_rm {
+        // ModR/M
+        let mod_rm = ModRM::from_raw(reader.read_u8()?);
+        code.mod_rm = Some(mod_rm);
+
+        // SIB
+        if mod_rm.mode() != 0b11 && mod_rm.rm() == 0b100 {
+            code.sib = Some(Sib::from_raw(reader.read_u8()?));
+        }
+
+        // addr disp
+        let disp_len = match (mod_rm.mode(), mod_rm.rm(), code.sib.map(|sib| sib.base())) {
+            (0b01, _, _) => 1,
+            (0b10, _, _) => 4,
+            // [RIP + disp32]
+            (0b00, 0b101, _) => 4,
+            // [disp32 + index * scale]
+            (0b00, 0b100, Some(0b101)) => 4,
+            _ => 0,
+        };
+        code.addr_disp = reader.read_bytes(disp_len)?;
+    }
+
+    // immutable val
+    code.imm = reader.read_bytes(imm_len(&code))?;
+
+    Ok((code, reader.pos))
+}
+
+/// Lifts decoded `ByteCode` fields into a typed instruction.
+pub fn lift(code: &ByteCode) -> Result<Instruction, DecodeError> {
+    let rex = code.rex.unwrap_or_default();
+
+    // operand-size prefix 以外は未対応
+    let prefix_66 = match code.prefix {
+        None => false,
+        Some(0x66) => true,
+        Some(_) => return Err(DecodeError::Unsupported),
+    };
+
+    let inst = match (code.opcode.bytes(), code.mod_rm) {
+        // MOV r/m, r
+        ([opcode @ (0x88 | 0x89)], Some(mod_rm)) => {
+            let size = operand_size(code, *opcode);
+            let src = reg(code, size, mod_rm.reg(), rex.r());
+            match rm(code, size, mod_rm) {
+                Rm::Reg(dst) => Instruction::from(Mov(dst, src)),
+                Rm::Mem(dst) => Instruction::from(Mov(dst, src)),
+            }
+        }
+        // MOV r, r/m
+        ([opcode @ (0x8A | 0x8B)], Some(mod_rm)) => {
+            let size = operand_size(code, *opcode);
+            let dst = reg(code, size, mod_rm.reg(), rex.r());
+            match rm(code, size, mod_rm) {
+                Rm::Reg(src) => Instruction::from(Mov(dst, src)),
+                Rm::Mem(src) => Instruction::from(Mov(dst, src)),
+            }
+        }
+        // LEA r, m
+        ([0x8D], Some(mod_rm)) if mod_rm.mode() != 0b11 => {
+            let dst = reg(code, operand_size(code, 0x8D), mod_rm.reg(), rex.r());
+            Instruction::from(Lea(dst, mem(code, mod_rm)))
+        }
+        // MOV r8, imm8
+        ([opcode @ 0xB0..=0xB7], None) => {
+            let dst = reg(code, Size::Byte, opcode - 0xB0, rex.b());
+            Instruction::from(Mov(dst, imm_zero_extended(code)))
+        }
+        // MOV r16, imm16 / MOV r32, imm32 / MOV r64, imm64
+        ([opcode @ 0xB8..=0xBF], None) => {
+            let dst = reg(code, operand_size(code, *opcode), opcode - 0xB8, rex.b());
+            Instruction::from(Mov(dst, imm_zero_extended(code)))
+        }
+        // MOV r/m, imm
+        ([opcode @ (0xC6 | 0xC7)], Some(mod_rm)) if mod_rm.reg() == 0 => {
+            let size = operand_size(code, *opcode);
+            let imm = imm_sign_extended(code);
+            match rm(code, size, mod_rm) {
+                Rm::Reg(dst) => Instruction::from(Mov(dst, imm as u64)),
+                Rm::Mem(dst) => Instruction::from(Mov(Ptr(size, dst), imm as i32)),
+            }
+        }
+        // SYSCALL
+        ([0x0F, 0x05], None) => Instruction::from(Syscall()),
+        // ALU r/m, r
+        ([opcode @ 0x00..=0x3F], Some(mod_rm)) if opcode & 0b110 == 0b000 => {
+            let op = AluOp::from_code(opcode >> 3);
+            let size = operand_size(code, *opcode);
+            let src = reg(code, size, mod_rm.reg(), rex.r());
+            match rm(code, size, mod_rm) {
+                Rm::Reg(dst) => Instruction::from(Alu(op, dst, src)),
+                Rm::Mem(dst) => Instruction::from(Alu(op, dst, src)),
+            }
+        }
+        // ALU r, r/m
+        ([opcode @ 0x00..=0x3F], Some(mod_rm)) if opcode & 0b110 == 0b010 => {
+            let op = AluOp::from_code(opcode >> 3);
+            let size = operand_size(code, *opcode);
+            let dst = reg(code, size, mod_rm.reg(), rex.r());
+            match rm(code, size, mod_rm) {
+                Rm::Reg(src) => Instruction::from(Alu(op, dst, src)),
+                Rm::Mem(src) => Instruction::from(Alu(op, dst, src)),
+            }
+        }
+        // ALU AL/AX/EAX/RAX, imm
+        ([opcode @ 0x00..=0x3F], None) if opcode & 0b110 == 0b100 => {
+            let op = AluOp::from_code(opcode >> 3);
+            let dst = reg(code, operand_size(code, *opcode), 0, false);
+            Instruction::from(Alu(op, dst, imm_sign_extended(code) as i32))
+        }
+        // ALU r/m, imm
+        ([opcode @ (0x80 | 0x81 | 0x83)], Some(mod_rm)) => {
+            let op = AluOp::from_code(mod_rm.reg());
+            let size = operand_size(code, *opcode);
+            let imm = imm_sign_extended(code) as i32;
+            match rm(code, size, mod_rm) {
+                Rm::Reg(dst) => Instruction::from(Alu(op, dst, imm)),
+                Rm::Mem(dst) => Instruction::from(Alu(op, Ptr(size, dst), imm)),
+            }
+        }
+        // SHIFT r/m, 1 / SHIFT r/m, CL / SHIFT r/m, imm8
+        ([opcode @ (0xC0 | 0xC1 | 0xD0..=0xD3)], Some(mod_rm)) => {
+            let op = ShiftOp::from_code(mod_rm.reg()).ok_or(DecodeError::Unsupported)?;
+            let size = operand_size(code, *opcode);
+            let count = match opcode {
+                0xD0 | 0xD1 => Some(1),
+                0xC0 | 0xC1 => Some(imm_zero_extended(code) as u8),
+                _ => None,
+            };
+            match (rm(code, size, mod_rm), count) {
+                (Rm::Reg(dst), Some(count)) => Instruction::from(Shift(op, dst, count)),
+                (Rm::Reg(dst), None) => Instruction::from(Shift(op, dst, Cl)),
+                (Rm::Mem(dst), Some(count)) => Instruction::from(Shift(op, Ptr(size, dst), count)),
+                (Rm::Mem(dst), None) => Instruction::from(Shift(op, Ptr(size, dst), Cl)),
+            }
+        }
+        // SHLD / SHRD r/m, r, imm8 / SHLD / SHRD r/m, r, CL
+        ([0x0F, opcode @ (0xA4 | 0xA5 | 0xAC | 0xAD)], Some(mod_rm)) => {
+            let op = match opcode & !1 {
+                0xA4 => DoubleShiftOp::Shld,
+                _ => DoubleShiftOp::Shrd,
+            };
+            let size = operand_size(code, *opcode);
+            let src = reg(code, size, mod_rm.reg(), rex.r());
+            let count = imm_zero_extended(code) as u8;
+            match (rm(code, size, mod_rm), opcode & 1 == 1) {
+                (Rm::Reg(dst), false) => Instruction::from(DoubleShift(op, dst, src, count)),
+                (Rm::Reg(dst), true) => Instruction::from(DoubleShift(op, dst, src, Cl)),
+                (Rm::Mem(dst), false) => Instruction::from(DoubleShift(op, dst, src, count)),
+                (Rm::Mem(dst), true) => Instruction::from(DoubleShift(op, dst, src, Cl)),
+            }
+        }
+        // MUL / IMUL / DIV / IDIV r/m
+        ([opcode @ (0xF6 | 0xF7)], Some(mod_rm)) => {
+            let op = MulOp::from_code(mod_rm.reg()).ok_or(DecodeError::Unsupported)?;
+            let size = operand_size(code, *opcode);
+            match rm(code, size, mod_rm) {
+                Rm::Reg(src) => Instruction::from(MulDiv(op, src)),
+                Rm::Mem(src) => Instruction::from(MulDiv(op, Ptr(size, src))),
+            }
+        }
+        // IMUL r, r/m
+        ([0x0F, 0xAF], Some(mod_rm)) => {
+            let size = operand_size(code, 0xAF);
+            let dst = reg(code, size, mod_rm.reg(), rex.r());
+            match rm(code, size, mod_rm) {
+                Rm::Reg(src) => Instruction::from(Imul(dst, src)),
+                Rm::Mem(src) => Instruction::from(Imul(dst, src)),
+            }
+        }
+        // IMUL r, r/m, imm
+        ([opcode @ (0x69 | 0x6B)], Some(mod_rm)) => {
+            let size = operand_size(code, *opcode);
+            let dst = reg(code, size, mod_rm.reg(), rex.r());
+            let imm = imm_sign_extended(code) as i32;
+            match rm(code, size, mod_rm) {
+                Rm::Reg(src) => Instruction::from(ImulImm(dst, src, imm)),
+                Rm::Mem(src) => Instruction::from(ImulImm(dst, src, imm)),
+            }
+        }
+        // CWD / CDQ / CQO
+        ([0x99], None) => match operand_size(code, 0x99) {
+            Size::Word => Instruction::from(Cwd()),
+            Size::Dword => Instruction::from(Cdq()),
+            _ => Instruction::from(Cqo()),
+        },
+        // CDQE (CBW / CWDE は未対応)
+        ([0x98], None) => match operand_size(code, 0x98) {
+            Size::Qword => Instruction::from(Cdqe()),
+            _ => return Err(DecodeError::Unsupported),
+        },
+        // PUSH r16/r64 / POP r16/r64
+        ([opcode @ 0x50..=0x57], None) => match stack_operand_size(code) {
+            Size::Word => Instruction::from(Push(Reg16::from_bits(opcode - 0x50, rex.b()))),
+            _ => Instruction::from(Push(Reg64::from_bits(opcode - 0x50, rex.b()))),
+        },
+        ([opcode @ 0x58..=0x5F], None) => match stack_operand_size(code) {
+            Size::Word => Instruction::from(Pop(Reg16::from_bits(opcode - 0x58, rex.b()))),
+            _ => Instruction::from(Pop(Reg64::from_bits(opcode - 0x58, rex.b()))),
+        },
+        // PUSH m16/m64 / POP m16/m64
+        ([0xFF], Some(mod_rm)) if mod_rm.reg() == 6 && mod_rm.mode() != 0b11 => {
+            Instruction::from(Push(Ptr(stack_operand_size(code), mem(code, mod_rm))))
+        }
+        ([0x8F], Some(mod_rm)) if mod_rm.reg() == 0 && mod_rm.mode() != 0b11 => {
+            Instruction::from(Pop(Ptr(stack_operand_size(code), mem(code, mod_rm))))
+        }
+        // 以下は 64bit オペランドのみ
+        _ if prefix_66 => return Err(DecodeError::Unsupported),
+        // JMP rel8 / rel32
+        ([0xEB], None) => Instruction::from(Jmp(imm_sign_extended(code) as i8)),
+        ([0xE9], None) => Instruction::from(Jmp(imm_sign_extended(code) as i32)),
+        // CALL rel32
+        ([0xE8], None) => Instruction::from(Call(imm_sign_extended(code) as i32)),
+        // CALL r/m64
+        ([0xFF], Some(mod_rm)) if mod_rm.reg() == 2 => match rm(code, Size::Qword, mod_rm) {
+            Rm::Reg(Reg::Reg64(dst)) => Instruction::from(Call(dst)),
+            Rm::Reg(_) => return Err(DecodeError::Unsupported),
+            Rm::Mem(dst) => Instruction::from(Call(dst)),
+        },
+        // RET / RET imm16
+        ([0xC3], None) => Instruction::from(Ret()),
+        ([0xC2], None) => Instruction::from(RetImm(imm_zero_extended(code) as u16)),
+        // LAHF / SAHF
+        ([0x9F], None) => Instruction::from(Lahf()),
+        ([0x9E], None) => Instruction::from(Sahf()),
+        // PUSH imm8 / imm32
+        ([0x6A], None) => Instruction::from(Push(imm_sign_extended(code) as i8)),
+        ([0x68], None) => Instruction::from(Push(imm_sign_extended(code) as i32)),
+        // JMP r/m64
+        ([0xFF], Some(mod_rm)) if mod_rm.reg() == 4 => match rm(code, Size::Qword, mod_rm) {
+            Rm::Reg(Reg::Reg64(dst)) => Instruction::from(Jmp(dst)),
+            Rm::Reg(_) => return Err(DecodeError::Unsupported),
+            Rm::Mem(dst) => Instruction::from(Jmp(dst)),
+        },
+        // Jcc rel8 / rel32
+        ([opcode @ 0x70..=0x7F], None) => {
+            Instruction::from(Jcc(Cond::from_code(*opcode), imm_sign_extended(code) as i8))
+        }
+        ([0x0F, opcode @ 0x80..=0x8F], None) => Instruction::from(Jcc(
+            Cond::from_code(*opcode),
+            imm_sign_extended(code) as i32,
+        )),
+        _ => return Err(DecodeError::Unsupported),
+    };
+
+    Ok(inst)
+}
+
+/// ModR/M の rm フィールドが指すオペランド
+enum Rm {
+    Reg(Reg),
+    Mem(Mem64),
+}
+
+fn rm(code: &ByteCode, size: Size, mod_rm: ModRM) -> Rm {
+    let rex = code.rex.unwrap_or_default();
+
+    if mod_rm.mode() == 0b11 {
+        Rm::Reg(reg(code, size, mod_rm.rm(), rex.b()))
+    } else {
+        Rm::Mem(mem(code, mod_rm))
+    }
+}
+
+fn reg(code: &ByteCode, size: Size, bits: u8, rex_bit: bool) -> Reg {
+    Reg::from_bits(size, bits, rex_bit, code.rex.is_some())
+}
+
+/// opcode, REX.W, operand-size prefix から決まるオペランドサイズ
+///
+/// 8bit 版の opcode は対応する 16/32/64bit 版より小さい
+fn operand_size(code: &ByteCode, opcode: u8) -> Size {
+    let is_byte = match opcode {
+        0x00..=0x3F => opcode & 0b001 == 0,
+        0xB0..=0xB7 => true,
+        0x80 | 0x88 | 0x8A | 0xC6 | 0xC0 | 0xD0 | 0xD2 | 0xF6 => true,
+        _ => false,
+    };
+
+    if is_byte {
+        Size::Byte
+    } else if code.rex.map(|rex| rex.w()).unwrap_or(false) {
+        Size::Qword
+    } else if code.prefix == Some(0x66) {
+        Size::Word
+    } else {
+        Size::Dword
+    }
+}
+
+/// push / pop のオペランドサイズ
+///
+/// 既定が 64bit で、operand-size prefix で 16bit になる (32bit にはできない)。
+/// REX.W は prefix より優先される
+fn stack_operand_size(code: &ByteCode) -> Size {
+    match (code.rex.map(|rex| rex.w()).unwrap_or(false), code.prefix) {
+        (false, Some(0x66)) => Size::Word,
+        _ => Size::Qword,
+    }
+}
+
+fn is_legacy_prefix(byte: u8) -> bool {
+    matches!(
+        byte,
+        0xF0 | 0xF2 | 0xF3 | 0x2E | 0x36 | 0x3E | 0x26 | 0x64 | 0x65 | 0x66 | 0x67
+    )
+}
+
+/// opcode の後に ModR/M が続くかどうか。未知の opcode なら None
+fn has_mod_rm(code: &ByteCode) -> Option<bool> {
+    match code.opcode.bytes() {
+        // ALU (op * 8 + 0 ~ 5)
+        [opcode @ 0x00..=0x3F] if opcode & 0b111 <= 3 => Some(true),
+        [opcode @ 0x00..=0x3F] if opcode & 0b111 <= 5 => Some(false),
+        [0x80 | 0x81 | 0x83] => Some(true),
+        [0xC0 | 0xC1 | 0xD0..=0xD3] | [0x0F, 0xA4 | 0xA5 | 0xAC | 0xAD] => Some(true),
+        [0xF6 | 0xF7 | 0x69 | 0x6B] | [0x0F, 0xAF] => Some(true),
+        [0x88 | 0x89 | 0x8A | 0x8B | 0x8D | 0xC6 | 0xC7 | 0xFF | 0x8F] => Some(true),
+        [0xB0..=0xBF | 0x70..=0x7F | 0xEB | 0xE9 | 0xE8 | 0xC3 | 0xC2] => Some(false),
+        [0x50..=0x5F | 0x6A | 0x68 | 0x9E | 0x9F | 0x98 | 0x99] => Some(false),
+        [0x0F, 0x05 | 0x80..=0x8F] => Some(false),
+        _ => None,
+    }
+}
+
+/// 即値のバイト数
+fn imm_len(code: &ByteCode) -> usize {
+    let rex_w = code.rex.map(|rex| rex.w()).unwrap_or(false);
+
+    match code.opcode.bytes() {
+        [opcode @ 0x00..=0x3F] if opcode & 0b111 == 4 => 1,
+        [opcode @ 0x00..=0x3F] if opcode & 0b111 == 5 => operand_size_z(code),
+        [0x80 | 0x83] => 1,
+        [0xC0 | 0xC1] | [0x0F, 0xA4 | 0xAC] => 1,
+        [0x6B] => 1,
+        [0x69] => operand_size_z(code),
+        // F6 /0, F7 /0 (TEST) と /1 (その別名) だけ即値を持つ
+        [0xF6] if code.mod_rm.map(|mod_rm| mod_rm.reg() < 2) == Some(true) => 1,
+        [0xF7] if code.mod_rm.map(|mod_rm| mod_rm.reg() < 2) == Some(true) => operand_size_z(code),
+        [0x81] => operand_size_z(code),
+        [0xB8..=0xBF] if rex_w => 8,
+        [0xB8..=0xBF] | [0xC7] => operand_size_z(code),
+        [0x70..=0x7F | 0xEB | 0x6A | 0xB0..=0xB7 | 0xC6] => 1,
+        [0xC2] => 2,
+        [0xE9 | 0xE8 | 0x68] | [0x0F, 0x80..=0x8F] => 4,
+        _ => 0,
+    }
+}
+
+/// 16bit / 32bit の即値 (Intel SDM の "iz")
+fn operand_size_z(code: &ByteCode) -> usize {
+    if code.prefix == Some(0x66) {
+        2
+    } else {
+        4
+    }
+}
+
+fn mem(code: &ByteCode, mod_rm: ModRM) -> Mem64 {
+    Mem64::from_mod_rm(mod_rm, code.sib, disp(code), code.rex)
+}
+
+fn disp(code: &ByteCode) -> i32 {
+    match code.addr_disp.bytes() {
+        [] => 0,
+        [disp] => *disp as i8 as i32,
+        disp => i32::from_le_bytes([disp[0], disp[1], disp[2], disp[3]]),
+    }
+}
+
+fn imm_zero_extended(code: &ByteCode) -> u64 {
+    code.imm
+        .bytes()
+        .iter()
+        .rev()
+        .fold(0, |acc, byte| (acc << 8) | *byte as u64)
+}
+
+fn imm_sign_extended(code: &ByteCode) -> i64 {
+    let bits = code.imm.len() as u32 * 8;
+    match bits {
+        0 => 0,
+        64 => imm_zero_extended(code) as i64,
+        _ => ((imm_zero_extended(code) << (64 - bits)) as i64) >> (64 - bits),
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn read_u8(&mut self) -> Result<u8, DecodeError> {
+        let byte = self.peek().ok_or(DecodeError::UnexpectedEnd)?;
+        self.pos += 1;
+        Ok(byte)
+    }
+
+    fn read_bytes<const MAX: usize>(
+        &mut self,
+        len: usize,
+    ) -> Result<BytesAtMost<MAX>, DecodeError> {
+        let src = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or(DecodeError::UnexpectedEnd)?;
+        self.pos += len;
+
+        let mut bytes = BytesAtMost::new(len);
+        bytes.bytes_mut().copy_from_slice(src);
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        use Reg64::*;
+
+        let cases = [
+            Instruction::from(Mov(Mem64::reg(RDI), RAX)),
+            Instruction::from(Mov(Mem64::reg_offset(RBP, -8), RAX)),
+            Instruction::from(Mov(Mem64::reg_offset(RDI, 200), R11)),
+            Instruction::from(Mov(Mem64::rip_offset(42), RAX)),
+            Instruction::from(Mov(Mem64::sib(Some(RBP), 42, RAX, 3), R13)),
+            Instruction::from(Mov(Mem64::sib(None, 0, R12, 2), RSP)),
+            Instruction::from(Mov(R12, RSP)),
+            Instruction::from(Mov(RAX, 0x1_0000_0000u64)),
+            Instruction::from(Mov(RAX, u64::MAX)),
+            Instruction::from(Mov(R10, 0x1234_5678_9ABC_DEF0u64)),
+            Instruction::from(Lea::new(RSP, Mem64::reg(RSP))),
+            Instruction::from(Lea::new(RAX, Mem64::reg(R13))),
+            Instruction::from(Lea::new(RDI, Mem64::rip_offset(-7))),
+            Instruction::from(Lea::new(RDI, Mem64::sib(Some(RAX), 0, RDI, 1))),
+            Instruction::from(Syscall()),
+            Instruction::from(Lahf()),
+            Instruction::from(Sahf()),
+            Instruction::from(Jmp(-2i8)),
+            Instruction::from(Jmp(0x1234i32)),
+            Instruction::from(Jmp(R11)),
+            Instruction::from(Jmp(Mem64::sib(None, 0x1000, RAX, 3))),
+            Instruction::from(Jmp(Mem64::rip_offset(8))),
+            Instruction::from(Jcc(Cond::E, 127i8)),
+            Instruction::from(Jcc(Cond::G, -128i8)),
+            Instruction::from(Jcc(Cond::NE, -0x1000i32)),
+            Instruction::from(Call(-5i32)),
+            Instruction::from(Call(R15)),
+            Instruction::from(Call(Mem64::reg_offset(RBP, -16))),
+            Instruction::from(Call(Mem64::rip_offset(0x100))),
+            Instruction::from(Ret()),
+            Instruction::from(RetImm(16)),
+            Instruction::from(Push(RBP)),
+            Instruction::from(Push(R12)),
+            Instruction::from(Push(Mem64::reg(R13))),
+            Instruction::from(Push(-1i8)),
+            Instruction::from(Push(0x1234_5678i32)),
+            Instruction::from(Pop(R13)),
+            Instruction::from(Pop(Mem64::reg(R12))),
+            Instruction::from(Alu::add(R8, R15)),
+            Instruction::from(Alu::cmp(Mem64::sib(Some(R12), 200, R11, 2), RSI)),
+            Instruction::from(Alu::sbb(R10, Mem64::reg(R13))),
+            Instruction::from(Alu::sub(RSP, 8)),
+            Instruction::from(Alu::or(RCX, 0x1000)),
+            Instruction::from(Alu::xor(RAX, i32::MAX)),
+            Instruction::from(Alu::and(Mem64::rip_offset(8), 0x100)),
+            Instruction::from(Alu::adc(Mem64::reg_offset(RBP, -8), -1)),
+            Instruction::from(Shift::shl(RAX, 1)),
+            Instruction::from(Shift::sar(R12, 63)),
+            Instruction::from(Shift::rcr(RDX, Cl)),
+            Instruction::from(Shift::rol(Mem64::reg_offset(RBP, -8), 4)),
+            Instruction::from(Shift::shr(Mem64::sib(Some(RAX), 0, RCX, 3), Cl)),
+            Instruction::from(DoubleShift::shld(RAX, RCX, 4)),
+            Instruction::from(DoubleShift::shrd(R9, R10, Cl)),
+            Instruction::from(DoubleShift::shrd(Mem64::reg(RDI), RSI, 63)),
+            Instruction::from(DoubleShift::shld(Mem64::rip_offset(8), R15, Cl)),
+            Instruction::from(MulDiv::mul(RCX)),
+            Instruction::from(MulDiv::idiv(R12)),
+            Instruction::from(MulDiv::div(Mem64::reg_offset(RBP, -8))),
+            Instruction::from(MulDiv::imul(Mem64::sib(Some(R13), 0, R14, 2))),
+            Instruction::from(Imul(RAX, RCX)),
+            Instruction::from(Imul(R8, Mem64::rip_offset(16))),
+            Instruction::from(ImulImm(RAX, RCX, 16)),
+            Instruction::from(ImulImm(R11, Mem64::reg(RDI), 0x1000)),
+            Instruction::from(ImulImm(RDX, RDX, -0x8000_0000)),
+            Instruction::from(Cqo()),
+            Instruction::from(Cdqe()),
+        ];
+
+        for inst in cases {
+            let bytes = inst.bytecode().to_bytes();
+            assert_eq!(decode(bytes.bytes()), Ok((inst, bytes.len())), "{}", inst);
+        }
+    }
+
+    #[test]
+    fn test_decode_operand_size() {
+        use crate::{Reg16::*, Reg32::*, Reg8::*};
+
+        let mem = Mem64::reg_offset(Reg64::RBP, -8);
+        let cases = [
+            Instruction::from(Mov(EAX, EBX)),
+            Instruction::from(Mov(R9W, CX)),
+            Instruction::from(Mov(SPL, R15B)),
+            Instruction::from(Mov(mem, R8W)),
+            Instruction::from(Mov(SIL, mem)),
+            Instruction::from(Mov(AH, AL)),
+            Instruction::from(Mov(BH, mem)),
+            Instruction::from(Mov(CH, 0x7Fu8)),
+            Instruction::from(Alu::sub(DH, BL)),
+            Instruction::from(Alu::cmp(AH, 1i8)),
+            Instruction::from(Mov(EAX, 42u32)),
+            Instruction::from(Mov(R15D, 0xFFFF_FFFFu32)),
+            Instruction::from(Mov(R9W, 0x1234u16)),
+            Instruction::from(Mov(DIL, 0xFFu8)),
+            Instruction::from(Mov(Ptr::byte(mem), -1)),
+            Instruction::from(Mov(Ptr::word(mem), 0x1234)),
+            Instruction::from(Mov(Ptr::dword(mem), -5)),
+            Instruction::from(Mov(mem, -5)),
+            Instruction::from(Lea(EAX, mem)),
+            Instruction::from(Lea(R12W, mem)),
+            Instruction::from(Alu::add(AL, CL)),
+            Instruction::from(Alu::sub(R10D, mem)),
+            Instruction::from(Alu::cmp(mem, DIL)),
+            Instruction::from(Alu::add(AL, 5i8)),
+            Instruction::from(Alu::xor(BPL, -1i8)),
+            Instruction::from(Alu::or(AX, 0x1234i16)),
+            Instruction::from(Alu::sbb(R11W, 0x1234i16)),
+            Instruction::from(Alu::and(ECX, 0x7F)),
+            Instruction::from(Alu::and(Ptr::word(mem), 0x7F)),
+            Instruction::from(Alu::cmp(Ptr::byte(mem), -128)),
+            Instruction::from(Shift::shl(AL, 1)),
+            Instruction::from(Shift::sar(AH, Cl)),
+            Instruction::from(Shift::ror(SIL, 7)),
+            Instruction::from(Shift::rcl(R9W, 1)),
+            Instruction::from(Shift::shr(EDX, Cl)),
+            Instruction::from(Shift::rol(R11D, 31)),
+            Instruction::from(Shift::shl(Ptr::byte(mem), Cl)),
+            Instruction::from(Shift::sar(Ptr::word(mem), 1)),
+            Instruction::from(Shift::shr(Ptr::dword(mem), 5)),
+            Instruction::from(DoubleShift::shld(BX, R15W, 1)),
+            Instruction::from(DoubleShift::shrd(mem, R8D, Cl)),
+            Instruction::from(MulDiv::mul(BL)),
+            Instruction::from(MulDiv::imul(AH)),
+            Instruction::from(MulDiv::div(SIL)),
+            Instruction::from(MulDiv::idiv(R9W)),
+            Instruction::from(MulDiv::mul(EBX)),
+            Instruction::from(MulDiv::div(Ptr::byte(mem))),
+            Instruction::from(MulDiv::idiv(Ptr::dword(mem))),
+            Instruction::from(Imul(DX, R15W)),
+            Instruction::from(Imul(R10D, mem)),
+            Instruction::from(ImulImm(R8W, SI, -2)),
+            Instruction::from(ImulImm(SI, mem, 0x1234)),
+            Instruction::from(ImulImm(ECX, ECX, 0x12345)),
+            Instruction::from(Cwd()),
+            Instruction::from(Cdq()),
+            Instruction::from(Push(R9W)),
+            Instruction::from(Pop(AX)),
+            Instruction::from(Push(Ptr::word(mem))),
+            Instruction::from(Pop(Ptr::word(mem))),
+        ];
+
+        for inst in cases {
+            let bytes = inst.bytecode().to_bytes();
+            assert_eq!(decode(bytes.bytes()), Ok((inst, bytes.len())), "{}", inst);
+        }
+
+        // REX prefix の有無で 0b100 ~ 0b111 の8bitレジスタが変わる
+        assert_eq!(
+            decode(&[0x88, 0xE0]),
+            Ok((Instruction::from(Mov(AL, AH)), 2))
+        );
+        assert_eq!(
+            decode(&[0x40, 0x88, 0xE0]),
+            Ok((Instruction::from(Mov(AL, SPL)), 3))
+        );
+
+        // shl rax, 1 を C1 /4 ib で書いたものは D1 /4 と同じ命令になる
+        assert_eq!(
+            decode(&[0x48, 0xC1, 0xE0, 0x01]),
+            Ok((Instruction::from(Shift::shl(Reg64::RAX, 1)), 4))
+        );
+
+        // push/pop は REX.W があれば operand-size prefix があっても 64bit
+        assert_eq!(
+            decode(&[0x66, 0x48, 0x50]),
+            Ok((Instruction::from(Push(Reg64::RAX)), 3))
+        );
+
+        // mov eax, imm32 は mov rax, imm のうちゼロ拡張される値のエンコーディング
+        assert_eq!(
+            decode(&[0xB8, 0x2A, 0x00, 0x00, 0x00]),
+            Ok((Instruction::from(Mov(EAX, 42u32)), 5))
+        );
+    }
+
+    #[test]
+    fn test_decode_bytecode() {
+        // mov qword ptr fs:[rax], rdx
+        let (code, len) = decode_bytecode(&[0x64, 0x48, 0x89, 0x10, 0x90]).unwrap();
+        assert_eq!(len, 4);
+        assert_eq!(code.prefix, Some(0x64));
+        assert_eq!(code.rex, Some(Rex::from_raw(0x48)));
+        assert_eq!(code.opcode.bytes(), [0x89]);
+        assert_eq!(code.mod_rm, Some(ModRM::from_raw(0x10)));
+        assert_eq!(code.to_bytes().bytes(), [0x64, 0x48, 0x89, 0x10]);
+
+        // [disp32 + index * scale]
+        let bytes = [0x48, 0x8D, 0x3C, 0x7D, 0x2A, 0x00, 0x00, 0x00];
+        let (code, len) = decode_bytecode(&bytes).unwrap();
+        assert_eq!(len, bytes.len());
+        assert_eq!(code.sib, Some(Sib::from_raw(0x7D)));
+        assert_eq!(code.addr_disp.bytes(), [0x2A, 0x00, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_decode_error() {
+        assert_eq!(decode(&[]), Err(DecodeError::UnexpectedEnd));
+        assert_eq!(decode(&[0x48, 0x89]), Err(DecodeError::UnexpectedEnd));
+        assert_eq!(
+            decode(&[0x48, 0xB8, 0x00, 0x00]),
+            Err(DecodeError::UnexpectedEnd)
+        );
+        assert_eq!(
+            decode(&[0x66, 0xF3, 0x90]),
+            Err(DecodeError::TooManyPrefixes)
+        );
+        assert_eq!(
+            decode(&[0x0F, 0x0B]),
+            Err(DecodeError::UnknownOpcode(BytesAtMost::from([0x0F, 0x0B])))
+        );
+        // lock prefix
+        assert_eq!(
+            decode(&[0xF0, 0x48, 0x01, 0x07]),
+            Err(DecodeError::Unsupported)
+        );
+
+        // /6 は SHL の別名で、持ち上げない
+        assert_eq!(decode(&[0x48, 0xD1, 0xF0]), Err(DecodeError::Unsupported));
+
+        // test r/m, imm と not / neg は持ち上げないが、長さは分かる
+        assert_eq!(
+            decode_bytecode(&[0x48, 0xF7, 0xC0, 0x01, 0x00, 0x00, 0x00]).map(|(_, len)| len),
+            Ok(7)
+        );
+        assert_eq!(decode(&[0xF6, 0xD8]), Err(DecodeError::Unsupported));
+        // cwde
+        assert_eq!(decode(&[0x98]), Err(DecodeError::Unsupported));
+
+        // add r64, r/m64 (mode=0b11) は Alu<Reg64, Reg64> に持ち上げる
+        assert_eq!(
+            decode(&[0x48, 0x03, 0xC1]),
+            Ok((Instruction::from(Alu::add(Reg64::RAX, Reg64::RCX)), 3))
+        );
+    }
+}
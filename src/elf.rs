@@ -0,0 +1,943 @@
+//! ELF64 (x86-64, little endian) のオブジェクトファイルの入出力
+
+use crate::object::{Object, Reloc, RelocKind, Section, SectionKind, Symbol};
+use byteorder::{ByteOrder as _, WriteBytesExt as _, LE};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt::{Display, Error as FmtError, Formatter},
+};
+
+/// Virtual address where [`write_executable`] maps the file.
+pub const BASE_ADDR: u64 = 0x40_0000;
+const PAGE_SIZE: u64 = 0x1000;
+
+const EHDR_SIZE: u64 = 64;
+const PHDR_SIZE: u16 = 56;
+const SHDR_SIZE: u16 = 64;
+const SYM_SIZE: u64 = 24;
+const RELA_SIZE: u64 = 24;
+
+const ET_REL: u16 = 1;
+const ET_EXEC: u16 = 2;
+const EM_X86_64: u16 = 62;
+
+const SHT_PROGBITS: u32 = 1;
+const SHT_SYMTAB: u32 = 2;
+const SHT_STRTAB: u32 = 3;
+const SHT_RELA: u32 = 4;
+const SHT_NOBITS: u32 = 8;
+
+const SHF_WRITE: u64 = 0x1;
+const SHF_ALLOC: u64 = 0x2;
+const SHF_EXECINSTR: u64 = 0x4;
+const SHF_INFO_LINK: u64 = 0x40;
+
+const PT_LOAD: u32 = 1;
+const PF_X: u32 = 0x1;
+const PF_W: u32 = 0x2;
+const PF_R: u32 = 0x4;
+
+const STB_LOCAL: u8 = 0;
+const STB_GLOBAL: u8 = 1;
+const STT_NOTYPE: u8 = 0;
+const STT_SECTION: u8 = 3;
+
+const R_X86_64_64: u32 = 1;
+const R_X86_64_PC32: u32 = 2;
+const R_X86_64_PLT32: u32 = 4;
+const R_X86_64_GOTPCREL: u32 = 9;
+
+/// Writes `obj` as a relocatable object (`ET_REL`).
+///
+/// Sections are laid out in the order of `obj.sections`, followed by
+/// `.note.GNU-stack` (non-executable stack), the `.rela.*` sections,
+/// `.symtab`, `.strtab` and `.shstrtab`.
+pub fn write_object(obj: &Object) -> Vec<u8> {
+    // セクションヘッダのインデックス
+    let section_index =
+        |kind: SectionKind| 1 + obj.sections.iter().position(|s| s.kind == kind).unwrap() as u32;
+    let note_index = 1 + obj.sections.len() as u32;
+    let rela_sections: Vec<&Section> = obj
+        .sections
+        .iter()
+        .filter(|s| !s.relocs.is_empty())
+        .collect();
+    let symtab_index = note_index + 1 + rela_sections.len() as u32;
+    let strtab_index = symtab_index + 1;
+    let shstrtab_index = strtab_index + 1;
+
+    // symtab はローカルシンボルを先に並べる必要がある
+    let mut symbols = obj.all_symbols();
+    symbols.sort_by_key(|sym| sym.global);
+    let first_local = 1 + obj.sections.len();
+    let first_global = first_local + symbols.iter().filter(|sym| !sym.global).count();
+
+    let mut strtab = StrTab::new();
+    let mut symtab = Vec::new();
+    write_sym(&mut symtab, 0, 0, 0, 0);
+    for section in obj.sections.iter() {
+        let shndx = section_index(section.kind) as u16;
+        write_sym(&mut symtab, 0, STB_LOCAL << 4 | STT_SECTION, shndx, 0);
+    }
+    let mut symbol_index = HashMap::new();
+    for (i, sym) in symbols.iter().enumerate() {
+        let bind = if sym.global { STB_GLOBAL } else { STB_LOCAL };
+        let (shndx, value) = match sym.def {
+            Some((kind, offset)) => (section_index(kind) as u16, offset),
+            None => (0, 0),
+        };
+        let name = strtab.add(&sym.name);
+        write_sym(&mut symtab, name, bind << 4 | STT_NOTYPE, shndx, value);
+        symbol_index
+            .entry(sym.name.as_str())
+            .or_insert(first_local + i);
+    }
+
+    let mut shstrtab = StrTab::new();
+    let mut buf = vec![0; EHDR_SIZE as usize];
+    let mut shdrs = vec![Shdr::default()];
+
+    for section in obj.sections.iter() {
+        let (kind, flags) = section_type(section.kind);
+        if kind != SHT_NOBITS {
+            pad(&mut buf, section.align);
+        }
+        shdrs.push(Shdr {
+            name: shstrtab.add(section_name(section.kind)),
+            kind,
+            flags,
+            offset: buf.len() as u64,
+            size: section.data.len() as u64,
+            align: section.align,
+            ..Shdr::default()
+        });
+        if kind != SHT_NOBITS {
+            buf.extend_from_slice(&section.data);
+        }
+    }
+
+    shdrs.push(Shdr {
+        name: shstrtab.add(".note.GNU-stack"),
+        kind: SHT_PROGBITS,
+        offset: buf.len() as u64,
+        align: 1,
+        ..Shdr::default()
+    });
+
+    for section in rela_sections {
+        pad(&mut buf, 8);
+        let offset = buf.len() as u64;
+        for reloc in section.relocs.iter() {
+            let sym = symbol_index[reloc.symbol.as_str()] as u64;
+            buf.write_u64::<LE>(reloc.offset).unwrap();
+            buf.write_u64::<LE>(sym << 32 | reloc_type(reloc.kind) as u64)
+                .unwrap();
+            buf.write_i64::<LE>(reloc.addend).unwrap();
+        }
+
+        let name = format!(".rela{}", section_name(section.kind));
+        shdrs.push(Shdr {
+            name: shstrtab.add(&name),
+            kind: SHT_RELA,
+            flags: SHF_INFO_LINK,
+            offset,
+            size: buf.len() as u64 - offset,
+            link: symtab_index,
+            info: section_index(section.kind),
+            align: 8,
+            entsize: RELA_SIZE,
+            ..Shdr::default()
+        });
+    }
+
+    pad(&mut buf, 8);
+    shdrs.push(Shdr {
+        name: shstrtab.add(".symtab"),
+        kind: SHT_SYMTAB,
+        offset: buf.len() as u64,
+        size: symtab.len() as u64,
+        link: strtab_index,
+        info: first_global as u32,
+        align: 8,
+        entsize: SYM_SIZE,
+        ..Shdr::default()
+    });
+    buf.extend_from_slice(&symtab);
+
+    shdrs.push(Shdr {
+        name: shstrtab.add(".strtab"),
+        kind: SHT_STRTAB,
+        offset: buf.len() as u64,
+        size: strtab.0.len() as u64,
+        align: 1,
+        ..Shdr::default()
+    });
+    buf.extend_from_slice(&strtab.0);
+
+    let name = shstrtab.add(".shstrtab");
+    shdrs.push(Shdr {
+        name,
+        kind: SHT_STRTAB,
+        offset: buf.len() as u64,
+        size: shstrtab.0.len() as u64,
+        align: 1,
+        ..Shdr::default()
+    });
+    buf.extend_from_slice(&shstrtab.0);
+
+    pad(&mut buf, 8);
+    let shdr_offset = buf.len() as u64;
+    for shdr in shdrs.iter() {
+        shdr.write(&mut buf);
+    }
+
+    let mut ehdr = Vec::new();
+    let shnum = shdrs.len() as u16;
+    write_ehdr(
+        &mut ehdr,
+        ET_REL,
+        0,
+        0,
+        shdr_offset,
+        shnum,
+        shstrtab_index as u16,
+    );
+    buf[..EHDR_SIZE as usize].copy_from_slice(&ehdr);
+
+    buf
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    /// The symbol is referenced (or is the entry point) but not defined.
+    UndefinedSymbol(String),
+    /// The relocated value does not fit in its field, or the field lies
+    /// outside its section.
+    RelocOutOfRange(String),
+}
+
+impl Display for LinkError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            LinkError::UndefinedSymbol(name) => write!(f, "undefined symbol `{}`", name),
+            LinkError::RelocOutOfRange(name) => {
+                write!(f, "relocation against `{}` is out of range", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// Links `obj` on its own into a static executable (`ET_EXEC`) starting at
+/// the symbol `entry`.
+///
+/// The file is mapped at [`BASE_ADDR`] as up to three `PT_LOAD` segments:
+/// the headers and `.text` (R+X), `.rodata` (R) and `.data`/`.bss` (RW).
+/// `GotPcRel` relocations get GOT entries in the read-only segment.
+/// A `.text` section header is kept so the file can be disassembled.
+///
+/// ```no_run
+/// use at_64::{
+///     asm::{Assembler, Sym},
+///     elf,
+///     instruction::{Lea, Mov, Syscall},
+///     object::{Object, Section, SectionKind, Symbol},
+///     Reg64::*,
+/// };
+///
+/// let mut asm = Assembler::new();
+/// asm.emit(Mov(RAX, 1u64)); // write
+/// asm.emit(Mov(RDI, 1u64));
+/// asm.emit(Lea(RSI, Sym("msg")));
+/// asm.emit(Mov(RDX, 6u64));
+/// asm.emit(Syscall());
+/// asm.emit(Mov(RAX, 60u64)); // exit
+/// asm.emit(Mov(RDI, 0u64));
+/// asm.emit(Syscall());
+/// let code = asm.assemble().unwrap();
+///
+/// let mut text = Section::new(SectionKind::Text, code.code);
+/// text.relocs = code.relocs;
+/// let obj = Object {
+///     sections: vec![text, Section::new(SectionKind::Rodata, b"hello\n".to_vec())],
+///     symbols: vec![
+///         Symbol::global("_start", SectionKind::Text, 0),
+///         Symbol::local("msg", SectionKind::Rodata, 0),
+///     ],
+/// };
+/// std::fs::write("hello", elf::write_executable(&obj, "_start").unwrap()).unwrap();
+/// ```
+pub fn write_executable(obj: &Object, entry: &str) -> Result<Vec<u8>, LinkError> {
+    let symbols = obj.all_symbols();
+    let relocs = || obj.sections.iter().flat_map(|s| s.relocs.iter());
+
+    let mut got: Vec<&str> = Vec::new();
+    for reloc in relocs().filter(|r| r.kind == RelocKind::GotPcRel) {
+        if !got.contains(&reloc.symbol.as_str()) {
+            got.push(&reloc.symbol);
+        }
+    }
+
+    // R+X (ヘッダと .text), R (.rodata と GOT), RW (.data と .bss)
+    let section = |kind| obj.section(kind);
+    let has_ro = section(SectionKind::Rodata).is_some() || !got.is_empty();
+    let has_rw = section(SectionKind::Data).is_some() || section(SectionKind::Bss).is_some();
+    let phnum = 1 + has_ro as u64 + has_rw as u64;
+
+    // 各セクションのファイルオフセット。仮想アドレスは BASE_ADDR + オフセット
+    let mut offsets = HashMap::new();
+    let mut segments = Vec::new();
+    let mut offset = EHDR_SIZE + phnum * PHDR_SIZE as u64;
+
+    let mut place = |kind: SectionKind, offset: &mut u64| {
+        if let Some(section) = section(kind) {
+            *offset = align(*offset, section.align);
+            offsets.insert(kind, *offset);
+            *offset += section.data.len() as u64;
+        }
+    };
+
+    place(SectionKind::Text, &mut offset);
+    segments.push((PF_R | PF_X, 0, offset, offset));
+
+    let mut got_offset = 0;
+    if has_ro {
+        offset = align(offset, PAGE_SIZE);
+        let start = offset;
+        place(SectionKind::Rodata, &mut offset);
+        offset = align(offset, 8);
+        got_offset = offset;
+        offset += 8 * got.len() as u64;
+        segments.push((PF_R, start, offset, offset));
+    }
+
+    if has_rw {
+        offset = align(offset, PAGE_SIZE);
+        let start = offset;
+        place(SectionKind::Data, &mut offset);
+        let file_end = offset;
+        // .bss はファイル上の領域を持たない
+        place(SectionKind::Bss, &mut offset);
+        segments.push((PF_R | PF_W, start, file_end, offset));
+    }
+
+    let addr_of = |name: &str| {
+        let sym = symbols
+            .iter()
+            .find(|sym| sym.name == name && sym.def.is_some());
+        match sym.and_then(|sym| sym.def) {
+            Some((kind, value)) => Ok(BASE_ADDR + offsets[&kind] + value),
+            None => Err(LinkError::UndefinedSymbol(name.to_string())),
+        }
+    };
+
+    let file_end = segments.iter().map(|seg| seg.2).max().unwrap();
+    let mut buf = vec![0; file_end as usize];
+
+    for section in obj.sections.iter().filter(|s| s.kind != SectionKind::Bss) {
+        let start = offsets[&section.kind] as usize;
+        buf[start..start + section.data.len()].copy_from_slice(&section.data);
+    }
+    for (i, name) in got.iter().enumerate() {
+        let start = got_offset as usize + 8 * i;
+        LE::write_u64(&mut buf[start..start + 8], addr_of(name)?);
+    }
+
+    for section in obj.sections.iter() {
+        let start = offsets[&section.kind];
+        for reloc in section.relocs.iter() {
+            let out_of_range = || LinkError::RelocOutOfRange(reloc.symbol.clone());
+            if section.kind == SectionKind::Bss
+                || reloc.offset + reloc.kind.size() as u64 > section.data.len() as u64
+            {
+                return Err(out_of_range());
+            }
+
+            let field = (start + reloc.offset) as usize;
+            let pc = BASE_ADDR + start + reloc.offset;
+            let target = match reloc.kind {
+                RelocKind::GotPcRel => {
+                    let i = got.iter().position(|name| *name == reloc.symbol).unwrap();
+                    BASE_ADDR + got_offset + 8 * i as u64
+                }
+                _ => addr_of(&reloc.symbol)?,
+            };
+            let value = target.wrapping_add(reloc.addend as u64);
+
+            match reloc.kind {
+                RelocKind::Abs64 => LE::write_u64(&mut buf[field..field + 8], value),
+                _ => {
+                    let rel =
+                        i32::try_from(value.wrapping_sub(pc) as i64).map_err(|_| out_of_range())?;
+                    LE::write_i32(&mut buf[field..field + 4], rel);
+                }
+            }
+        }
+    }
+
+    // 逆アセンブル用の .text と .shstrtab のセクションヘッダ
+    let mut shstrtab = StrTab::new();
+    let mut shdrs = vec![Shdr::default()];
+    if let Some(text) = section(SectionKind::Text) {
+        let offset = offsets[&SectionKind::Text];
+        shdrs.push(Shdr {
+            name: shstrtab.add(".text"),
+            kind: SHT_PROGBITS,
+            flags: SHF_ALLOC | SHF_EXECINSTR,
+            addr: BASE_ADDR + offset,
+            offset,
+            size: text.data.len() as u64,
+            align: text.align,
+            ..Shdr::default()
+        });
+    }
+    let name = shstrtab.add(".shstrtab");
+    shdrs.push(Shdr {
+        name,
+        kind: SHT_STRTAB,
+        offset: buf.len() as u64,
+        size: shstrtab.0.len() as u64,
+        align: 1,
+        ..Shdr::default()
+    });
+    buf.extend_from_slice(&shstrtab.0);
+    pad(&mut buf, 8);
+    let shdr_offset = buf.len() as u64;
+    for shdr in shdrs.iter() {
+        shdr.write(&mut buf);
+    }
+
+    let mut headers = Vec::new();
+    let (shnum, shstrndx) = (shdrs.len() as u16, shdrs.len() as u16 - 1);
+    let entry = addr_of(entry)?;
+    write_ehdr(
+        &mut headers,
+        ET_EXEC,
+        entry,
+        phnum as u16,
+        shdr_offset,
+        shnum,
+        shstrndx,
+    );
+    for (flags, start, file_end, mem_end) in segments {
+        headers.write_u32::<LE>(PT_LOAD).unwrap();
+        headers.write_u32::<LE>(flags).unwrap();
+        headers.write_u64::<LE>(start).unwrap(); // p_offset
+        headers.write_u64::<LE>(BASE_ADDR + start).unwrap(); // p_vaddr
+        headers.write_u64::<LE>(BASE_ADDR + start).unwrap(); // p_paddr
+        headers.write_u64::<LE>(file_end - start).unwrap();
+        headers.write_u64::<LE>(mem_end - start).unwrap();
+        headers.write_u64::<LE>(PAGE_SIZE).unwrap();
+    }
+    buf[..headers.len()].copy_from_slice(&headers);
+
+    Ok(buf)
+}
+
+/// `.text` section of an ELF file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Text<'a> {
+    /// `sh_addr`: 0 in relocatable objects.
+    pub addr: u64,
+    pub bytes: &'a [u8],
+}
+
+/// Finds `.text` in an ELF64 little-endian file.
+///
+/// Returns `None` if `data` is not such a file, is truncated, or has no `.text`.
+pub fn read_text(data: &[u8]) -> Option<Text<'_>> {
+    let shdrs = read_shdrs(data)?;
+    let text = shdrs.iter().find(|shdr| shdr.name == b".text")?;
+
+    Some(Text {
+        addr: text.addr,
+        bytes: text.data(data)?,
+    })
+}
+
+/// Reads back a relocatable object written by [`write_object`].
+///
+/// Only `.text`, `.data`, `.rodata` and `.bss` with their symbols and
+/// relocations are read; other sections are ignored. Returns `None` if
+/// `data` is malformed or uses relocation types [`RelocKind`] cannot express.
+pub fn read_object(data: &[u8]) -> Option<Object> {
+    let shdrs = read_shdrs(data)?;
+    let kinds: Vec<Option<SectionKind>> =
+        shdrs.iter().map(|shdr| section_kind(shdr.name)).collect();
+
+    let mut obj = Object::new();
+    for (shdr, kind) in shdrs.iter().zip(kinds.iter()) {
+        if let Some(kind) = *kind {
+            let data = match shdr.kind {
+                // 壊れたサイズで巨大な領域を確保しない
+                SHT_NOBITS if shdr.size > u32::MAX as u64 => return None,
+                SHT_NOBITS => vec![0; shdr.size as usize],
+                _ => shdr.data(data)?.to_vec(),
+            };
+            let mut section = Section::new(kind, data);
+            section.align = shdr.align;
+            obj.sections.push(section);
+        }
+    }
+
+    let symtab = shdrs.iter().find(|shdr| shdr.kind == SHT_SYMTAB)?;
+    let strtab = shdrs.get(symtab.link as usize)?.data(data)?;
+    let mut names = Vec::new();
+    for sym in symtab.data(data)?.chunks_exact(SYM_SIZE as usize) {
+        let name = cstr(strtab, LE::read_u32(sym) as usize)?;
+        names.push(name.clone());
+
+        let (info, shndx) = (sym[4], LE::read_u16(&sym[6..]) as usize);
+        if name.is_empty() || info & 0xF == STT_SECTION {
+            continue;
+        }
+        let def = match shndx {
+            0 => None,
+            _ => Some(((*kinds.get(shndx)?)?, LE::read_u64(&sym[8..]))),
+        };
+        obj.symbols.push(Symbol {
+            name,
+            def,
+            global: info >> 4 != STB_LOCAL,
+        });
+    }
+
+    for shdr in shdrs.iter().filter(|shdr| shdr.kind == SHT_RELA) {
+        let kind = (*kinds.get(shdr.info as usize)?)?;
+        let section = obj.sections.iter_mut().find(|s| s.kind == kind)?;
+        for rela in shdr.data(data)?.chunks_exact(RELA_SIZE as usize) {
+            let info = LE::read_u64(&rela[8..]);
+            section.relocs.push(Reloc {
+                offset: LE::read_u64(rela),
+                symbol: names.get((info >> 32) as usize)?.clone(),
+                kind: reloc_kind(info as u32)?,
+                addend: LE::read_i64(&rela[16..]),
+            });
+        }
+    }
+
+    Some(obj)
+}
+
+fn section_name(kind: SectionKind) -> &'static str {
+    match kind {
+        SectionKind::Text => ".text",
+        SectionKind::Data => ".data",
+        SectionKind::Rodata => ".rodata",
+        SectionKind::Bss => ".bss",
+    }
+}
+
+fn section_kind(name: &[u8]) -> Option<SectionKind> {
+    [
+        SectionKind::Text,
+        SectionKind::Data,
+        SectionKind::Rodata,
+        SectionKind::Bss,
+    ]
+    .iter()
+    .copied()
+    .find(|kind| section_name(*kind).as_bytes() == name)
+}
+
+/// `sh_type` と `sh_flags`
+fn section_type(kind: SectionKind) -> (u32, u64) {
+    match kind {
+        SectionKind::Text => (SHT_PROGBITS, SHF_ALLOC | SHF_EXECINSTR),
+        SectionKind::Data => (SHT_PROGBITS, SHF_ALLOC | SHF_WRITE),
+        SectionKind::Rodata => (SHT_PROGBITS, SHF_ALLOC),
+        SectionKind::Bss => (SHT_NOBITS, SHF_ALLOC | SHF_WRITE),
+    }
+}
+
+fn reloc_type(kind: RelocKind) -> u32 {
+    match kind {
+        RelocKind::Pc32 => R_X86_64_PC32,
+        RelocKind::Plt32 => R_X86_64_PLT32,
+        RelocKind::Abs64 => R_X86_64_64,
+        RelocKind::GotPcRel => R_X86_64_GOTPCREL,
+    }
+}
+
+fn reloc_kind(ty: u32) -> Option<RelocKind> {
+    match ty {
+        R_X86_64_PC32 => Some(RelocKind::Pc32),
+        R_X86_64_PLT32 => Some(RelocKind::Plt32),
+        R_X86_64_64 => Some(RelocKind::Abs64),
+        R_X86_64_GOTPCREL => Some(RelocKind::GotPcRel),
+        _ => None,
+    }
+}
+
+/// プログラムヘッダはヘッダの直後に置く
+fn write_ehdr(
+    buf: &mut Vec<u8>,
+    kind: u16,
+    entry: u64,
+    phnum: u16,
+    shdr_offset: u64,
+    shnum: u16,
+    shstrndx: u16,
+) {
+    // e_ident: ELFCLASS64, ELFDATA2LSB, EV_CURRENT, ELFOSABI_SYSV
+    buf.extend_from_slice(&[0x7F, b'E', b'L', b'F', 2, 1, 1, 0]);
+    buf.extend_from_slice(&[0; 8]);
+    buf.write_u16::<LE>(kind).unwrap();
+    buf.write_u16::<LE>(EM_X86_64).unwrap();
+    buf.write_u32::<LE>(1).unwrap(); // e_version
+    buf.write_u64::<LE>(entry).unwrap();
+    let phoff = if phnum == 0 { 0 } else { EHDR_SIZE };
+    buf.write_u64::<LE>(phoff).unwrap();
+    buf.write_u64::<LE>(shdr_offset).unwrap();
+    buf.write_u32::<LE>(0).unwrap(); // e_flags
+    buf.write_u16::<LE>(EHDR_SIZE as u16).unwrap();
+    let phentsize = if phnum == 0 { 0 } else { PHDR_SIZE };
+    buf.write_u16::<LE>(phentsize).unwrap();
+    buf.write_u16::<LE>(phnum).unwrap();
+    buf.write_u16::<LE>(SHDR_SIZE).unwrap();
+    buf.write_u16::<LE>(shnum).unwrap();
+    buf.write_u16::<LE>(shstrndx).unwrap();
+}
+
+fn write_sym(buf: &mut Vec<u8>, name: u32, info: u8, shndx: u16, value: u64) {
+    buf.write_u32::<LE>(name).unwrap();
+    buf.write_u8(info).unwrap();
+    buf.write_u8(0).unwrap(); // st_other
+    buf.write_u16::<LE>(shndx).unwrap();
+    buf.write_u64::<LE>(value).unwrap();
+    buf.write_u64::<LE>(0).unwrap(); // st_size
+}
+
+#[derive(Default)]
+struct Shdr {
+    name: u32,
+    kind: u32,
+    flags: u64,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+    entsize: u64,
+}
+
+impl Shdr {
+    fn write(&self, buf: &mut Vec<u8>) {
+        buf.write_u32::<LE>(self.name).unwrap();
+        buf.write_u32::<LE>(self.kind).unwrap();
+        buf.write_u64::<LE>(self.flags).unwrap();
+        buf.write_u64::<LE>(self.addr).unwrap();
+        buf.write_u64::<LE>(self.offset).unwrap();
+        buf.write_u64::<LE>(self.size).unwrap();
+        buf.write_u32::<LE>(self.link).unwrap();
+        buf.write_u32::<LE>(self.info).unwrap();
+        buf.write_u64::<LE>(self.align).unwrap();
+        buf.write_u64::<LE>(self.entsize).unwrap();
+    }
+}
+
+/// 読み込んだセクションヘッダ
+struct RawShdr<'a> {
+    name: &'a [u8],
+    kind: u32,
+    addr: u64,
+    offset: u64,
+    size: u64,
+    link: u32,
+    info: u32,
+    align: u64,
+}
+
+impl RawShdr<'_> {
+    fn data<'d>(&self, data: &'d [u8]) -> Option<&'d [u8]> {
+        let offset = self.offset as usize;
+        data.get(offset..offset.checked_add(self.size as usize)?)
+    }
+}
+
+fn read_shdrs(data: &[u8]) -> Option<Vec<RawShdr<'_>>> {
+    if data.get(..6)? != [0x7F, b'E', b'L', b'F', 2, 1] {
+        return None;
+    }
+
+    let shoff = LE::read_u64(data.get(40..48)?) as usize;
+    let shentsize = LE::read_u16(data.get(58..60)?) as usize;
+    let shnum = LE::read_u16(data.get(60..62)?) as usize;
+    let shstrndx = LE::read_u16(data.get(62..64)?) as usize;
+    if shentsize < SHDR_SIZE as usize {
+        return None;
+    }
+
+    let mut shdrs = Vec::new();
+    let mut names = Vec::new();
+    for i in 0..shnum {
+        let start = shoff.checked_add(i * shentsize)?;
+        let shdr = data.get(start..start.checked_add(shentsize)?)?;
+        names.push(LE::read_u32(shdr) as usize);
+        shdrs.push(RawShdr {
+            name: &[],
+            kind: LE::read_u32(&shdr[4..]),
+            addr: LE::read_u64(&shdr[16..]),
+            offset: LE::read_u64(&shdr[24..]),
+            size: LE::read_u64(&shdr[32..]),
+            link: LE::read_u32(&shdr[40..]),
+            info: LE::read_u32(&shdr[44..]),
+            align: LE::read_u64(&shdr[48..]),
+        });
+    }
+
+    let shstrtab = shdrs.get(shstrndx)?.data(data)?;
+    for (shdr, name) in shdrs.iter_mut().zip(names) {
+        let name = shstrtab.get(name..)?;
+        let len = name.iter().position(|b| *b == 0)?;
+        shdr.name = &name[..len];
+    }
+
+    Some(shdrs)
+}
+
+/// `offset` から始まる NUL 終端文字列
+fn cstr(table: &[u8], offset: usize) -> Option<String> {
+    let s = table.get(offset..)?;
+    let len = s.iter().position(|b| *b == 0)?;
+    String::from_utf8(s[..len].to_vec()).ok()
+}
+
+/// 先頭が空文字列の文字列テーブル
+struct StrTab(Vec<u8>);
+
+impl StrTab {
+    fn new() -> Self {
+        StrTab(vec![0])
+    }
+
+    fn add(&mut self, s: &str) -> u32 {
+        let offset = self.0.len() as u32;
+        self.0.extend_from_slice(s.as_bytes());
+        self.0.push(0);
+        offset
+    }
+}
+
+fn align(offset: u64, align: u64) -> u64 {
+    let align = align.max(1);
+    offset.div_ceil(align) * align
+}
+
+/// `buf` の長さが `align` の倍数になるまで 0 を詰める
+fn pad(buf: &mut Vec<u8>, align: u64) {
+    let len = self::align(buf.len() as u64, align);
+    buf.resize(len as usize, 0);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        asm::{Assembler, Got, Sym},
+        instruction::{Alu, AluOp, Call, Jmp, Lea, Mov, Ret, Syscall},
+        Mem64,
+        Reg64::*,
+    };
+
+    fn sample() -> Object {
+        let mut asm = Assembler::new();
+        asm.emit(Lea(RDI, Sym("msg")));
+        asm.emit(Call(Sym("puts")));
+        asm.emit(Mov(RAX, Got("counter")));
+        asm.emit(Mov(RCX, Sym("table")));
+        asm.emit(Ret());
+        asm.emit(Jmp(Sym("exit")));
+        let code = asm.assemble().unwrap();
+
+        let mut text = Section::new(SectionKind::Text, code.code);
+        text.relocs = code.relocs;
+        let mut data = Section::new(SectionKind::Data, vec![0; 8]);
+        data.relocs.push(Reloc {
+            offset: 0,
+            symbol: "msg".to_string(),
+            kind: RelocKind::Abs64,
+            addend: 2,
+        });
+
+        Object {
+            sections: vec![
+                text,
+                data,
+                Section::new(SectionKind::Rodata, b"hello\0".to_vec()),
+                Section::new(SectionKind::Bss, vec![0; 16]),
+            ],
+            symbols: vec![
+                Symbol::local("msg", SectionKind::Rodata, 0),
+                Symbol::global("main", SectionKind::Text, 0),
+                Symbol::global("table", SectionKind::Data, 0),
+                Symbol::global("counter", SectionKind::Bss, 8),
+                Symbol::undefined("puts"),
+                Symbol::undefined("exit"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_assembled_relocs() {
+        let text = &sample().sections[0];
+        let relocs: Vec<(u64, &str, RelocKind, i64)> = text
+            .relocs
+            .iter()
+            .map(|r| (r.offset, r.symbol.as_str(), r.kind, r.addend))
+            .collect();
+
+        let expected = [
+            (3, "msg", RelocKind::Pc32, -4),          // lea rdi, [rip + msg]
+            (8, "puts", RelocKind::Plt32, -4),        // call puts
+            (15, "counter", RelocKind::GotPcRel, -4), // mov rax, [rip + counter@GOTPCREL]
+            (21, "table", RelocKind::Abs64, 0),       // movabs rcx, table
+            (31, "exit", RelocKind::Plt32, -4),       // jmp exit
+        ];
+        assert_eq!(relocs, expected);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let obj = sample();
+        let bytes = write_object(&obj);
+
+        assert_eq!(bytes[..4], [0x7F, b'E', b'L', b'F']);
+        assert_eq!(LE::read_u16(&bytes[16..]), ET_REL);
+        assert_eq!(LE::read_u16(&bytes[18..]), EM_X86_64);
+        assert_eq!(read_object(&bytes), Some(obj.clone()));
+
+        let text = read_text(&bytes).unwrap();
+        assert_eq!(text.addr, 0);
+        assert_eq!(text.bytes, &obj.sections[0].data[..]);
+    }
+
+    #[test]
+    fn test_undefined_from_relocs() {
+        let mut obj = sample();
+        obj.symbols.retain(|sym| sym.def.is_some());
+
+        let read = read_object(&write_object(&obj)).unwrap();
+        assert_eq!(read.symbols, sample().symbols);
+    }
+
+    /// write で `msg` を出力し、`status` の値で exit する
+    fn hello() -> Object {
+        let mut asm = Assembler::new();
+        asm.emit(Mov(RAX, 1u64));
+        asm.emit(Mov(RDI, 1u64));
+        asm.emit(Mov(RSI, Sym("msg")));
+        asm.emit(Mov(RDX, 6u64));
+        asm.emit(Syscall());
+        asm.emit(Mov(RCX, Got("status")));
+        asm.emit(Mov(RDI, Mem64::reg(RCX)));
+        asm.emit(Lea(RCX, Sym("counter")));
+        asm.emit(Alu(AluOp::Add, RDI, Mem64::reg(RCX)));
+        asm.emit(Mov(RAX, 60u64));
+        asm.emit(Syscall());
+        let code = asm.assemble().unwrap();
+
+        let mut text = Section::new(SectionKind::Text, code.code);
+        text.relocs = code.relocs;
+
+        Object {
+            sections: vec![
+                text,
+                Section::new(SectionKind::Rodata, b"hello\n".to_vec()),
+                Section::new(SectionKind::Data, 42u64.to_le_bytes().to_vec()),
+                Section::new(SectionKind::Bss, vec![0; 8]),
+            ],
+            symbols: vec![
+                Symbol::global("_start", SectionKind::Text, 0),
+                Symbol::local("msg", SectionKind::Rodata, 0),
+                Symbol::local("status", SectionKind::Data, 0),
+                Symbol::local("counter", SectionKind::Bss, 0),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_write_executable() {
+        let exe = write_executable(&hello(), "_start").unwrap();
+
+        assert_eq!(LE::read_u16(&exe[16..]), ET_EXEC);
+        assert_eq!(LE::read_u64(&exe[24..]), BASE_ADDR + 0xF0); // e_entry: ヘッダ (64 + 3 * 56) の後
+        assert_eq!(LE::read_u16(&exe[56..]), 3); // e_phnum
+
+        // (p_flags, p_offset, p_filesz, p_memsz)
+        let phdrs: Vec<(u32, u64, u64, u64)> = exe[64..64 + 3 * 56]
+            .chunks(56)
+            .map(|phdr| {
+                let flags = LE::read_u32(&phdr[4..]);
+                let offset = LE::read_u64(&phdr[8..]);
+                let filesz = LE::read_u64(&phdr[32..]);
+                let memsz = LE::read_u64(&phdr[40..]);
+                (flags, offset, filesz, memsz)
+            })
+            .collect();
+        let text_len = hello().sections[0].data.len() as u64;
+        let expected = [
+            (PF_R | PF_X, 0, 0xF0 + text_len, 0xF0 + text_len),
+            (PF_R, 0x1000, 16, 16), // "hello\n" + GOT
+            (PF_R | PF_W, 0x2000, 8, 16),
+        ];
+        assert_eq!(phdrs, expected);
+
+        let text = read_text(&exe).unwrap();
+        assert_eq!(text.addr, BASE_ADDR + 0xF0);
+
+        let mut obj = hello();
+        obj.symbols.pop();
+        assert_eq!(
+            write_executable(&obj, "_start"),
+            Err(LinkError::UndefinedSymbol("counter".to_string()))
+        );
+        assert_eq!(
+            write_executable(&hello(), "main"),
+            Err(LinkError::UndefinedSymbol("main".to_string()))
+        );
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[test]
+    fn test_run_executable() {
+        use std::{os::unix::fs::PermissionsExt as _, process::Command};
+
+        let path = std::env::temp_dir().join(format!("at64-elf-{}", std::process::id()));
+        std::fs::write(&path, write_executable(&hello(), "_start").unwrap()).unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+        let output = Command::new(&path).output().unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(output.stdout, b"hello\n");
+        assert_eq!(output.status.code(), Some(42));
+    }
+
+    #[test]
+    fn test_malformed() {
+        let bytes = write_object(&sample());
+        assert_eq!(read_object(&bytes[..100]), None);
+        assert_eq!(read_text(&bytes[..100]), None);
+        assert_eq!(read_text(b"\x7FELF"), None);
+
+        // .bss (SHT_NOBITS) の sh_size が 32bit を超える
+        let mut bytes = bytes;
+        let shoff = LE::read_u64(&bytes[0x28..]) as usize;
+        let bss = (0..LE::read_u16(&bytes[0x3C..]) as usize)
+            .map(|i| shoff + i * SHDR_SIZE as usize)
+            .find(|shdr| LE::read_u32(&bytes[shdr + 4..]) == SHT_NOBITS)
+            .unwrap();
+        bytes[bss + 32..bss + 40].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        assert_eq!(read_object(&bytes), None);
+
+        // e_shoff が末尾近くで、セクションヘッダの位置があふれる
+        let mut header = write_object(&sample())[..64].to_vec();
+        header[0x28..0x30].copy_from_slice(&0xFFFF_FFFF_FFFF_FFF0u64.to_le_bytes());
+        assert_eq!(read_text(&header), None);
+        assert_eq!(read_object(&header), None);
+    }
+}
@@ -0,0 +1,80 @@
+use crate::{ByteCode, Register, Size};
+use std::fmt::{Display, Error as FmtError, Formatter};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncodeError {
+    /// AH/CH/DH/BH is used in an instruction that needs a REX prefix.
+    HighByteWithRex,
+    /// SIB scale is not one of 0 ~ 3 (x1, x2, x4, x8).
+    InvalidScale(u8),
+    /// RSP cannot be a SIB index; its encoding means "no index".
+    RspAsIndex,
+    /// The immediate does not fit in the operand size.
+    ImmOutOfRange(Size, i64),
+    /// The two operands have different sizes.
+    OperandSizeMismatch(Size, Size),
+    /// The instruction has no form for this operand size.
+    InvalidOperandSize(Size),
+    /// A ModR/M or SIB field does not fit in its bits.
+    FieldOutOfRange(&'static str, u8),
+    /// The byte is not a REX prefix (0x40 ~ 0x4F).
+    InvalidRex(u8),
+    /// More bytes than a `BytesAtMost` can hold.
+    TooManyBytes { len: usize, max: usize },
+}
+
+impl Display for EncodeError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            EncodeError::HighByteWithRex => {
+                write!(f, "ah/ch/dh/bh cannot be encoded with a REX prefix")
+            }
+            EncodeError::InvalidScale(scale) => write!(f, "invalid SIB scale {}", scale),
+            EncodeError::RspAsIndex => write!(f, "rsp cannot be used as an index"),
+            EncodeError::ImmOutOfRange(size, imm) => {
+                write!(f, "immediate {:#x} does not fit in {:?}", imm, size)
+            }
+            EncodeError::OperandSizeMismatch(dst, src) => {
+                write!(f, "operand size mismatch: {:?} and {:?}", dst, src)
+            }
+            EncodeError::InvalidOperandSize(size) => {
+                write!(f, "invalid operand size {:?}", size)
+            }
+            EncodeError::FieldOutOfRange(field, value) => {
+                write!(f, "{} {:#b} is out of range", field, value)
+            }
+            EncodeError::InvalidRex(byte) => write!(f, "{:#04x} is not a REX prefix", byte),
+            EncodeError::TooManyBytes { len, max } => {
+                write!(f, "{} bytes exceed the capacity {}", len, max)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {}
+
+/// REX prefix が付く命令では ModR/M の 0b100 ~ 0b111 が SPL/BPL/SIL/DIL になるため、
+/// AH/CH/DH/BH はエンコードできない
+pub(crate) fn check_high_byte<R: Register>(code: &ByteCode, regs: &[R]) -> Result<(), EncodeError> {
+    if code.rex.is_some() && regs.iter().any(|reg| reg.is_high_byte()) {
+        Err(EncodeError::HighByteWithRex)
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn check_same_size<R: Register>(dst: R, src: R) -> Result<(), EncodeError> {
+    if dst.size() == src.size() {
+        Ok(())
+    } else {
+        Err(EncodeError::OperandSizeMismatch(dst.size(), src.size()))
+    }
+}
+
+pub(crate) fn check_imm(size: Size, imm: i64) -> Result<(), EncodeError> {
+    if size.fits(imm) {
+        Ok(())
+    } else {
+        Err(EncodeError::ImmOutOfRange(size, imm))
+    }
+}
@@ -0,0 +1,661 @@
+use crate::{
+    encode::{check_high_byte, check_imm, check_same_size},
+    ByteCode, BytesAtMost, EncodeError, Mem64, ModRM, Ptr, Reg, Reg16, Reg32, Reg64, Reg8,
+    Register, Rex, Size,
+};
+use std::convert::TryFrom;
+use std::fmt::{Display, Error as FmtError, Formatter};
+
+/// 8種類の算術・論理演算
+///
+/// 値は opcode (op * 8 + 1 など) と ModR/M の reg フィールド (/n) に使われる
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AluOp {
+    Add,
+    Or,
+    Adc,
+    Sbb,
+    And,
+    Sub,
+    Xor,
+    Cmp,
+}
+
+impl AluOp {
+    pub const ALL: [AluOp; 8] = {
+        use AluOp::*;
+        [Add, Or, Adc, Sbb, And, Sub, Xor, Cmp]
+    };
+
+    pub fn code(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_code(code: u8) -> Self {
+        AluOp::ALL[(code & 0b111) as usize]
+    }
+}
+
+impl Display for AluOp {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            AluOp::Add => write!(f, "add"),
+            AluOp::Or => write!(f, "or"),
+            AluOp::Adc => write!(f, "adc"),
+            AluOp::Sbb => write!(f, "sbb"),
+            AluOp::And => write!(f, "and"),
+            AluOp::Sub => write!(f, "sub"),
+            AluOp::Xor => write!(f, "xor"),
+            AluOp::Cmp => write!(f, "cmp"),
+        }
+    }
+}
+
+/// `op dst, src`
+///
+/// 即値は64bitに符号拡張される。imm8 に収まる場合は `83 /n ib` を使う
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Alu<Dst, Src>(pub AluOp, pub Dst, pub Src);
+
+impl<Dst, Src> Alu<Dst, Src> {
+    pub fn add(dst: Dst, src: Src) -> Self {
+        Alu(AluOp::Add, dst, src)
+    }
+
+    pub fn or(dst: Dst, src: Src) -> Self {
+        Alu(AluOp::Or, dst, src)
+    }
+
+    pub fn adc(dst: Dst, src: Src) -> Self {
+        Alu(AluOp::Adc, dst, src)
+    }
+
+    pub fn sbb(dst: Dst, src: Src) -> Self {
+        Alu(AluOp::Sbb, dst, src)
+    }
+
+    pub fn and(dst: Dst, src: Src) -> Self {
+        Alu(AluOp::And, dst, src)
+    }
+
+    pub fn sub(dst: Dst, src: Src) -> Self {
+        Alu(AluOp::Sub, dst, src)
+    }
+
+    pub fn xor(dst: Dst, src: Src) -> Self {
+        Alu(AluOp::Xor, dst, src)
+    }
+
+    pub fn cmp(dst: Dst, src: Src) -> Self {
+        Alu(AluOp::Cmp, dst, src)
+    }
+}
+
+impl<R: Register> Alu<R, R> {
+    /// (op * 8 + 1) /r, 8bit は (op * 8) /r
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (op, dst, src) = (self.0, self.1, self.2);
+        check_same_size(dst, src)?;
+
+        let mut code = ByteCode::new();
+
+        // operand-size prefix
+        code.prefix = src.size().prefix();
+
+        // REX prefix
+        let mut rex = Rex::new();
+        rex.set_w(src.size().rex_w());
+        rex.set_r(src.rex_r_bit());
+        rex.set_b(dst.rex_b_bit());
+        code.set_rex(rex, src.requires_rex() || dst.requires_rex());
+        check_high_byte(&code, &[dst, src])?;
+
+        // opcode
+        match src.size() {
+            Size::Byte => code.opcode = BytesAtMost::from([op.code() * 8]),
+            _ => code.opcode = BytesAtMost::from([op.code() * 8 + 1]),
+        }
+
+        // ModR/M
+        let mut mod_rm = ModRM::new();
+        mod_rm.set_mode(dst.mode_bits());
+        mod_rm.set_reg(src.reg_bits());
+        mod_rm.set_rm(dst.rm_bits());
+        code.mod_rm = Some(mod_rm);
+
+        Ok(code)
+    }
+}
+
+impl<R: Register> Alu<Mem64, R> {
+    /// (op * 8 + 1) /r, 8bit は (op * 8) /r
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (op, dst, src) = (self.0, self.1, self.2);
+        dst.validate()?;
+
+        let mut code = ByteCode::new();
+
+        // operand-size prefix
+        code.prefix = src.size().prefix();
+
+        // REX prefix
+        let mut rex = Rex::new();
+        rex.set_w(src.size().rex_w());
+        rex.set_r(src.rex_r_bit());
+        rex.set_x(dst.rex_x_bit());
+        rex.set_b(dst.rex_b_bit());
+        code.set_rex(rex, src.requires_rex());
+        check_high_byte(&code, &[src])?;
+
+        // opcode
+        match src.size() {
+            Size::Byte => code.opcode = BytesAtMost::from([op.code() * 8]),
+            _ => code.opcode = BytesAtMost::from([op.code() * 8 + 1]),
+        }
+
+        // ModR/M
+        let mut mod_rm = ModRM::new();
+        mod_rm.set_mode(dst.mode_bits());
+        mod_rm.set_reg(src.reg_bits());
+        mod_rm.set_rm(dst.rm_bits());
+        code.mod_rm = Some(mod_rm);
+
+        // SIB
+        code.sib = dst.sib_byte();
+
+        // addr disp
+        code.addr_disp = dst.disp_bytes();
+
+        Ok(code)
+    }
+}
+
+impl<R: Register> Alu<R, Mem64> {
+    /// (op * 8 + 3) /r, 8bit は (op * 8 + 2) /r
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (op, dst, src) = (self.0, self.1, self.2);
+        src.validate()?;
+
+        let mut code = ByteCode::new();
+
+        // operand-size prefix
+        code.prefix = dst.size().prefix();
+
+        // REX prefix
+        let mut rex = Rex::new();
+        rex.set_w(dst.size().rex_w());
+        rex.set_r(dst.rex_r_bit());
+        rex.set_x(src.rex_x_bit());
+        rex.set_b(src.rex_b_bit());
+        code.set_rex(rex, dst.requires_rex());
+        check_high_byte(&code, &[dst])?;
+
+        // opcode
+        match dst.size() {
+            Size::Byte => code.opcode = BytesAtMost::from([op.code() * 8 + 2]),
+            _ => code.opcode = BytesAtMost::from([op.code() * 8 + 3]),
+        }
+
+        // ModR/M
+        let mut mod_rm = ModRM::new();
+        mod_rm.set_mode(src.mode_bits());
+        mod_rm.set_reg(dst.reg_bits());
+        mod_rm.set_rm(src.rm_bits());
+        code.mod_rm = Some(mod_rm);
+
+        // SIB
+        code.sib = src.sib_byte();
+
+        // addr disp
+        code.addr_disp = src.disp_bytes();
+
+        Ok(code)
+    }
+}
+
+impl Alu<Reg64, i32> {
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        bytecode_reg_imm(self.0, self.1, self.2 as i64)
+    }
+}
+
+impl Alu<Reg32, i32> {
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        bytecode_reg_imm(self.0, self.1, self.2 as i64)
+    }
+}
+
+impl Alu<Reg16, i16> {
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        bytecode_reg_imm(self.0, self.1, self.2 as i64)
+    }
+}
+
+impl Alu<Reg8, i8> {
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        bytecode_reg_imm(self.0, self.1, self.2 as i64)
+    }
+}
+
+/// レジスタのサイズに収まらない即値は `bytecode` では panic、`try_bytecode` では `Err` になる
+impl Alu<Reg, i32> {
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        bytecode_reg_imm(self.0, self.1, self.2 as i64)
+    }
+}
+
+impl Alu<Mem64, i32> {
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        Alu(self.0, Ptr::qword(self.1), self.2).try_bytecode()
+    }
+}
+
+/// サイズに収まらない即値は `bytecode` では panic、`try_bytecode` では `Err` になる
+impl Alu<Ptr, i32> {
+    /// - 8bit : 80 /n ib
+    /// - imm8 に収まる : 83 /n ib
+    /// - それ以外 : 81 /n iw/id
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (op, Ptr(size, dst), src) = (self.0, self.1, self.2);
+        dst.validate()?;
+        let imm = sized_imm(size, src as i64)?;
+
+        let mut code = ByteCode::new();
+
+        // operand-size prefix
+        code.prefix = size.prefix();
+
+        // REX prefix
+        let mut rex = Rex::new();
+        rex.set_w(size.rex_w());
+        rex.set_x(dst.rex_x_bit());
+        rex.set_b(dst.rex_b_bit());
+        code.set_rex(rex, false);
+
+        // ModR/M
+        let mut mod_rm = ModRM::new();
+        mod_rm.set_mode(dst.mode_bits());
+        mod_rm.set_reg(op.code());
+        mod_rm.set_rm(dst.rm_bits());
+        code.mod_rm = Some(mod_rm);
+
+        // SIB
+        code.sib = dst.sib_byte();
+
+        // addr disp
+        code.addr_disp = dst.disp_bytes();
+
+        // opcode, immutable val
+        set_group1_imm(&mut code, size, imm);
+
+        Ok(code)
+    }
+}
+
+/// - 8bit : 80 /n ib
+/// - imm8 に収まる : 83 /n ib
+/// - dst が AL/AX/EAX/RAX : (op * 8 + 4) ib / (op * 8 + 5) iw/id
+/// - それ以外 : 81 /n iw/id
+fn bytecode_reg_imm<R: Register>(op: AluOp, dst: R, src: i64) -> Result<ByteCode, EncodeError> {
+    let size = dst.size();
+    let imm = sized_imm(size, src)?;
+    let is_accumulator = dst.reg_bits() == 0 && !dst.rex_b_bit();
+
+    let mut code = ByteCode::new();
+
+    // operand-size prefix
+    code.prefix = size.prefix();
+
+    // REX prefix
+    let mut rex = Rex::new();
+    rex.set_w(size.rex_w());
+    rex.set_b(dst.rex_b_bit());
+    code.set_rex(rex, dst.requires_rex());
+    check_high_byte(&code, &[dst])?;
+
+    match (size, i8::try_from(imm)) {
+        (Size::Byte, _) | (_, Err(_)) if is_accumulator => {
+            // opcode, immutable val
+            match size {
+                Size::Byte => code.opcode = BytesAtMost::from([op.code() * 8 + 4]),
+                _ => code.opcode = BytesAtMost::from([op.code() * 8 + 5]),
+            }
+            code.imm = imm_bytes(size, imm);
+        }
+        _ => {
+            // ModR/M
+            let mut mod_rm = ModRM::new();
+            mod_rm.set_mode(dst.mode_bits());
+            mod_rm.set_reg(op.code());
+            mod_rm.set_rm(dst.rm_bits());
+            code.mod_rm = Some(mod_rm);
+
+            // opcode, immutable val
+            set_group1_imm(&mut code, size, imm);
+        }
+    }
+
+    Ok(code)
+}
+
+/// 即値をオペランドサイズで切り詰め、符号付きとして解釈し直す
+pub(super) fn sized_imm(size: Size, imm: i64) -> Result<i64, EncodeError> {
+    check_imm(size, imm)?;
+
+    let imm = match size {
+        Size::Byte => imm as i8 as i64,
+        Size::Word => imm as i16 as i64,
+        Size::Dword | Size::Qword => imm as i32 as i64,
+    };
+
+    Ok(imm)
+}
+
+/// 80 /n ib, 83 /n ib, 81 /n iw/id のいずれかの opcode と即値を設定する
+fn set_group1_imm(code: &mut ByteCode, size: Size, imm: i64) {
+    match (size, i8::try_from(imm)) {
+        (Size::Byte, _) => {
+            code.opcode = BytesAtMost::from([0x80]);
+            code.imm = imm_bytes(size, imm);
+        }
+        (_, Ok(imm8)) => {
+            code.opcode = BytesAtMost::from([0x83]);
+            code.imm = BytesAtMost::from(imm8);
+        }
+        (_, Err(_)) => {
+            code.opcode = BytesAtMost::from([0x81]);
+            code.imm = imm_bytes(size, imm);
+        }
+    }
+}
+
+/// オペランドサイズの即値 (64bit は imm32 を符号拡張)
+pub(super) fn imm_bytes(size: Size, imm: i64) -> BytesAtMost<8> {
+    match size {
+        Size::Byte => BytesAtMost::from(imm as i8),
+        Size::Word => BytesAtMost::from((imm as i16).to_le_bytes()),
+        Size::Dword | Size::Qword => BytesAtMost::from(imm as i32),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reg_reg() {
+        use Reg64::*;
+
+        let cases = [
+            (Alu::add(RAX, RCX), vec![0x48, 0x01, 0xC8]),
+            (Alu::or(RAX, RCX), vec![0x48, 0x09, 0xC8]),
+            (Alu::adc(RAX, RCX), vec![0x48, 0x11, 0xC8]),
+            (Alu::sbb(RAX, RCX), vec![0x48, 0x19, 0xC8]),
+            (Alu::and(RAX, RCX), vec![0x48, 0x21, 0xC8]),
+            (Alu::sub(RAX, RCX), vec![0x48, 0x29, 0xC8]),
+            (Alu::xor(RAX, RCX), vec![0x48, 0x31, 0xC8]),
+            (Alu::cmp(RAX, RCX), vec![0x48, 0x39, 0xC8]),
+            (Alu::add(R8, R15), vec![0x4D, 0x01, 0xF8]),
+            (Alu::sub(RSP, R9), vec![0x4C, 0x29, 0xCC]),
+        ];
+
+        for (origin, expected) in cases {
+            assert_eq!(origin.bytecode().to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_mem_reg() {
+        use Reg64::*;
+
+        let cases = [
+            (Alu::add(Mem64::reg(RDI), RAX), vec![0x48, 0x01, 0x07]),
+            (
+                Alu::xor(Mem64::reg_offset(RBP, -8), R13),
+                vec![0x4C, 0x31, 0x6D, 0xF8],
+            ),
+            (
+                Alu::cmp(Mem64::sib(Some(R12), 200, R11, 2), RSI),
+                vec![0x4B, 0x39, 0xB4, 0x9C, 0xC8, 0x00, 0x00, 0x00],
+            ),
+        ];
+
+        for (origin, expected) in cases {
+            assert_eq!(origin.bytecode().to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_reg_mem() {
+        use Reg64::*;
+
+        let cases = [
+            (Alu::add(RAX, Mem64::reg(RDI)), vec![0x48, 0x03, 0x07]),
+            (Alu::sbb(R10, Mem64::reg(R13)), vec![0x4D, 0x1B, 0x55, 0x00]),
+            (
+                Alu::and(RCX, Mem64::rip_offset(-16)),
+                vec![0x48, 0x23, 0x0D, 0xF0, 0xFF, 0xFF, 0xFF],
+            ),
+            (
+                Alu::cmp(RDX, Mem64::reg_offset(RSP, 8)),
+                vec![0x48, 0x3B, 0x54, 0x24, 0x08],
+            ),
+        ];
+
+        for (origin, expected) in cases {
+            assert_eq!(origin.bytecode().to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_reg_imm() {
+        use Reg64::*;
+
+        let cases = [
+            // imm8
+            (Alu::add(RAX, 1), vec![0x48, 0x83, 0xC0, 0x01]),
+            (Alu::sub(RSP, 8), vec![0x48, 0x83, 0xEC, 0x08]),
+            (Alu::cmp(R12, -128), vec![0x49, 0x83, 0xFC, 0x80]),
+            (Alu::and(RDI, 127), vec![0x48, 0x83, 0xE7, 0x7F]),
+            // RAX の短い形式
+            (Alu::add(RAX, 128), vec![0x48, 0x05, 0x80, 0x00, 0x00, 0x00]),
+            (
+                Alu::cmp(RAX, -129),
+                vec![0x48, 0x3D, 0x7F, 0xFF, 0xFF, 0xFF],
+            ),
+            (
+                Alu::xor(RAX, i32::MAX),
+                vec![0x48, 0x35, 0xFF, 0xFF, 0xFF, 0x7F],
+            ),
+            // imm32
+            (
+                Alu::or(RCX, 0x1000),
+                vec![0x48, 0x81, 0xC9, 0x00, 0x10, 0x00, 0x00],
+            ),
+            (
+                Alu::adc(R15, -129),
+                vec![0x49, 0x81, 0xD7, 0x7F, 0xFF, 0xFF, 0xFF],
+            ),
+        ];
+
+        for (origin, expected) in cases {
+            assert_eq!(origin.bytecode().to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_mem_imm() {
+        use Reg64::*;
+
+        let cases = [
+            (Alu::add(Mem64::reg(RAX), 1), vec![0x48, 0x83, 0x00, 0x01]),
+            (
+                Alu::sub(Mem64::reg_offset(RBP, -8), -1),
+                vec![0x48, 0x83, 0x6D, 0xF8, 0xFF],
+            ),
+            (
+                Alu::cmp(Mem64::reg(R12), 0x1234),
+                vec![0x49, 0x81, 0x3C, 0x24, 0x34, 0x12, 0x00, 0x00],
+            ),
+            (
+                Alu::and(Mem64::rip_offset(8), 0x100),
+                vec![
+                    0x48, 0x81, 0x25, 0x08, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00,
+                ],
+            ),
+            (
+                Alu::or(Mem64::sib(None, 0, R9, 3), 2),
+                vec![0x4A, 0x83, 0x0C, 0xCD, 0x00, 0x00, 0x00, 0x00, 0x02],
+            ),
+        ];
+
+        for (origin, expected) in cases {
+            assert_eq!(origin.bytecode().to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_operand_size() {
+        use crate::{Reg16::*, Reg32::*, Reg8::*};
+
+        let cases = [
+            (Alu::add(AL, CL).bytecode(), vec![0x00, 0xC8]),
+            (Alu::xor(DIL, SIL).bytecode(), vec![0x40, 0x30, 0xF7]),
+            (Alu::sub(AX, R9W).bytecode(), vec![0x66, 0x44, 0x29, 0xC8]),
+            (Alu::cmp(ECX, EDX).bytecode(), vec![0x39, 0xD1]),
+            (
+                Alu::and(R10D, Mem64::reg(Reg64::RAX)).bytecode(),
+                vec![0x44, 0x23, 0x10],
+            ),
+            (
+                Alu::or(Mem64::reg(Reg64::RDI), BL).bytecode(),
+                vec![0x08, 0x1F],
+            ),
+            // AL/AX/EAX の短い形式
+            (Alu::add(AL, 1).bytecode(), vec![0x04, 0x01]),
+            (
+                Alu::add(AX, 0x1000).bytecode(),
+                vec![0x66, 0x05, 0x00, 0x10],
+            ),
+            (
+                Alu::cmp(EAX, 0x1000).bytecode(),
+                vec![0x3D, 0x00, 0x10, 0x00, 0x00],
+            ),
+            (Alu::sub(CL, -1).bytecode(), vec![0x80, 0xE9, 0xFF]),
+            (Alu::and(SI, 0x7F).bytecode(), vec![0x66, 0x83, 0xE6, 0x7F]),
+            (
+                Alu::or(BX, 0x1234).bytecode(),
+                vec![0x66, 0x81, 0xCB, 0x34, 0x12],
+            ),
+            (Alu::adc(R8D, 1).bytecode(), vec![0x41, 0x83, 0xD0, 0x01]),
+            (
+                Alu::cmp(Ptr::byte(Mem64::reg(Reg64::RAX)), 1).bytecode(),
+                vec![0x80, 0x38, 0x01],
+            ),
+            (
+                Alu::add(Ptr::word(Mem64::reg(Reg64::RAX)), 0x1234).bytecode(),
+                vec![0x66, 0x81, 0x00, 0x34, 0x12],
+            ),
+            (
+                Alu::sub(Ptr::dword(Mem64::reg_offset(Reg64::RBP, -4)), 1).bytecode(),
+                vec![0x83, 0x6D, 0xFC, 0x01],
+            ),
+        ];
+
+        for (bytecode, expected) in cases {
+            assert_eq!(bytecode.to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_high_byte() {
+        use Reg8::*;
+
+        assert_eq!(Alu::xor(AH, AH).bytecode().to_bytes().bytes(), [0x30, 0xE4]);
+        assert_eq!(
+            Alu::cmp(CH, 1).bytecode().to_bytes().bytes(),
+            [0x80, 0xFD, 0x01]
+        );
+        assert_eq!(
+            Alu::add(AH, DIL).try_bytecode(),
+            Err(EncodeError::HighByteWithRex)
+        );
+        assert_eq!(
+            Alu::and(Mem64::reg(Reg64::R12), BH).try_bytecode(),
+            Err(EncodeError::HighByteWithRex)
+        );
+        assert_eq!(
+            Alu::or(Reg::from(DH), Reg::from(SPL)).try_bytecode(),
+            Err(EncodeError::HighByteWithRex)
+        );
+    }
+
+    #[test]
+    fn test_try_bytecode_error() {
+        use Reg64::*;
+
+        let bad_scale = Mem64::Sib {
+            base: None,
+            disp: 0,
+            index: RAX,
+            scale: 4,
+        };
+
+        assert_eq!(
+            Alu::add(RAX, bad_scale).try_bytecode(),
+            Err(EncodeError::InvalidScale(4))
+        );
+        assert_eq!(
+            Alu::cmp(Reg::from(Reg16::AX), Reg::from(Reg8::AL)).try_bytecode(),
+            Err(EncodeError::OperandSizeMismatch(Size::Word, Size::Byte))
+        );
+        assert_eq!(
+            Alu::sub(Reg::from(Reg8::CL), -129).try_bytecode(),
+            Err(EncodeError::ImmOutOfRange(Size::Byte, -129))
+        );
+        assert_eq!(
+            Alu::and(Ptr::byte(Mem64::reg(RDI)), 0x100).try_bytecode(),
+            Err(EncodeError::ImmOutOfRange(Size::Byte, 0x100))
+        );
+    }
+}
@@ -0,0 +1,151 @@
+use crate::{
+    asm::{Assembler, Emit, Label, RelField, Sym},
+    object::RelocKind,
+    ByteCode, BytesAtMost, EncodeError, Mem64, ModRM, Reg64, Rex,
+};
+
+/// `i32` は命令の末尾からの相対距離 (rel32)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Call<Dst>(pub Dst);
+
+impl Call<i32> {
+    /// E8 cd
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        // opcode
+        code.opcode = BytesAtMost::from([0xE8]);
+
+        // immutable val
+        code.imm = BytesAtMost::from(self.0);
+
+        code
+    }
+}
+
+impl Call<Reg64> {
+    /// FF /2
+    pub fn bytecode(&self) -> ByteCode {
+        let dst = self.0;
+
+        let mut code = ByteCode::new();
+
+        // REX prefix
+        if dst.rex_b_bit() {
+            let mut rex = Rex::new();
+            rex.set_b(true);
+            code.rex = Some(rex);
+        }
+
+        // opcode
+        code.opcode = BytesAtMost::from([0xFF]);
+
+        // ModR/M
+        let mut mod_rm = ModRM::new();
+        mod_rm.set_mode(dst.mode_bits());
+        mod_rm.set_reg(2);
+        mod_rm.set_rm(dst.rm_bits());
+        code.mod_rm = Some(mod_rm);
+
+        code
+    }
+}
+
+impl Call<Mem64> {
+    /// FF /2
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let dst = self.0;
+        dst.validate()?;
+
+        let mut code = ByteCode::new();
+
+        // REX prefix
+        if dst.rex_x_bit() || dst.rex_b_bit() {
+            let mut rex = Rex::new();
+            rex.set_x(dst.rex_x_bit());
+            rex.set_b(dst.rex_b_bit());
+            code.rex = Some(rex);
+        }
+
+        // opcode
+        code.opcode = BytesAtMost::from([0xFF]);
+
+        // ModR/M
+        let mut mod_rm = ModRM::new();
+        mod_rm.set_mode(dst.mode_bits());
+        mod_rm.set_reg(2);
+        mod_rm.set_rm(dst.rm_bits());
+        code.mod_rm = Some(mod_rm);
+
+        // SIB
+        code.sib = dst.sib_byte();
+
+        // addr disp
+        code.addr_disp = dst.disp_bytes();
+
+        Ok(code)
+    }
+}
+
+/// `call rel32`
+impl Emit for Call<Label> {
+    fn try_emit(&self, asm: &mut Assembler) -> Result<(), EncodeError> {
+        asm.push_label_ref(Call(0i32).bytecode(), RelField::Imm, self.0);
+        Ok(())
+    }
+}
+
+/// `call sym` (R_X86_64_PLT32)
+impl Emit for Call<Sym<'_>> {
+    fn try_emit(&self, asm: &mut Assembler) -> Result<(), EncodeError> {
+        asm.push_reloc(
+            Call(0i32).bytecode(),
+            RelField::Imm,
+            (self.0).0,
+            RelocKind::Plt32,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        use Reg64::*;
+
+        let cases = [
+            (Call(0i32).bytecode(), vec![0xE8, 0x00, 0x00, 0x00, 0x00]),
+            (Call(-5i32).bytecode(), vec![0xE8, 0xFB, 0xFF, 0xFF, 0xFF]),
+            (Call(RAX).bytecode(), vec![0xFF, 0xD0]),
+            (Call(R15).bytecode(), vec![0x41, 0xFF, 0xD7]),
+            (Call(Mem64::reg(RAX)).bytecode(), vec![0xFF, 0x10]),
+            (
+                Call(Mem64::reg_offset(RBP, -16)).bytecode(),
+                vec![0xFF, 0x55, 0xF0],
+            ),
+            (
+                Call(Mem64::reg(R12)).bytecode(),
+                vec![0x41, 0xFF, 0x14, 0x24],
+            ),
+            (
+                Call(Mem64::rip_offset(0x100)).bytecode(),
+                vec![0xFF, 0x15, 0x00, 0x01, 0x00, 0x00],
+            ),
+            (
+                Call(Mem64::sib(Some(RBX), 8, R10, 3)).bytecode(),
+                vec![0x42, 0xFF, 0x54, 0xD3, 0x08],
+            ),
+        ];
+
+        for (code, expected) in cases {
+            assert_eq!(code.to_bytes().bytes(), expected);
+        }
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    asm::{Assembler, Emit, Label},
+    ByteCode, BytesAtMost, EncodeError,
+};
+use std::fmt::{Display, Error as FmtError, Formatter};
+
+/// 条件コード (opcode の下位4bit)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cond {
+    /// Overflow
+    O,
+    /// Not overflow
+    NO,
+    /// Below (CF=1), also C / NAE
+    B,
+    /// Above or equal (CF=0), also NC / NB
+    AE,
+    /// Equal (ZF=1), also Z
+    E,
+    /// Not equal (ZF=0), also NZ
+    NE,
+    /// Below or equal (CF=1 or ZF=1), also NA
+    BE,
+    /// Above (CF=0 and ZF=0), also NBE
+    A,
+    /// Sign
+    S,
+    /// Not sign
+    NS,
+    /// Parity even, also PE
+    P,
+    /// Parity odd, also PO
+    NP,
+    /// Less (SF!=OF), also NGE
+    L,
+    /// Greater or equal (SF=OF), also NL
+    GE,
+    /// Less or equal (ZF=1 or SF!=OF), also NG
+    LE,
+    /// Greater (ZF=0 and SF=OF), also NLE
+    G,
+}
+
+impl Cond {
+    pub const ALL: [Cond; 16] = {
+        use Cond::*;
+        [O, NO, B, AE, E, NE, BE, A, S, NS, P, NP, L, GE, LE, G]
+    };
+
+    pub fn code(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_code(code: u8) -> Self {
+        Cond::ALL[(code & 0b1111) as usize]
+    }
+}
+
+/// `jcc` の `cc` 部分
+impl Display for Cond {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            Cond::O => write!(f, "o"),
+            Cond::NO => write!(f, "no"),
+            Cond::B => write!(f, "b"),
+            Cond::AE => write!(f, "ae"),
+            Cond::E => write!(f, "e"),
+            Cond::NE => write!(f, "ne"),
+            Cond::BE => write!(f, "be"),
+            Cond::A => write!(f, "a"),
+            Cond::S => write!(f, "s"),
+            Cond::NS => write!(f, "ns"),
+            Cond::P => write!(f, "p"),
+            Cond::NP => write!(f, "np"),
+            Cond::L => write!(f, "l"),
+            Cond::GE => write!(f, "ge"),
+            Cond::LE => write!(f, "le"),
+            Cond::G => write!(f, "g"),
+        }
+    }
+}
+
+/// `i8` / `i32` は命令の末尾からの相対距離 (rel8 / rel32)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Jcc<Dst>(pub Cond, pub Dst);
+
+impl Jcc<i8> {
+    /// 70+cc cb
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        // opcode
+        code.opcode = BytesAtMost::from([0x70 + self.0.code()]);
+
+        // immutable val
+        code.imm = BytesAtMost::from(self.1);
+
+        code
+    }
+}
+
+impl Jcc<i32> {
+    /// 0F 80+cc cd
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        // opcode
+        code.opcode = BytesAtMost::from([0x0F, 0x80 + self.0.code()]);
+
+        // immutable val
+        code.imm = BytesAtMost::from(self.1);
+
+        code
+    }
+}
+
+/// rel8 で届くなら `jcc rel8`、そうでなければ `jcc rel32`
+impl Emit for Jcc<Label> {
+    fn try_emit(&self, asm: &mut Assembler) -> Result<(), EncodeError> {
+        let cond = self.0;
+        asm.push_branch(
+            Jcc(cond, 0i8).bytecode(),
+            Jcc(cond, 0i32).bytecode(),
+            self.1,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        for (i, cond) in Cond::ALL.iter().enumerate() {
+            let i = i as u8;
+            assert_eq!(Cond::from_code(i), *cond);
+            assert_eq!(
+                Jcc(*cond, -2i8).bytecode().to_bytes().bytes(),
+                [0x70 + i, 0xFE]
+            );
+            assert_eq!(
+                Jcc(*cond, 0x1234i32).bytecode().to_bytes().bytes(),
+                [0x0F, 0x80 + i, 0x34, 0x12, 0x00, 0x00]
+            );
+        }
+
+        assert_eq!(
+            Jcc(Cond::E, 0i8).bytecode().to_bytes().bytes(),
+            [0x74, 0x00]
+        );
+        assert_eq!(
+            Jcc(Cond::G, 0i8).bytecode().to_bytes().bytes(),
+            [0x7F, 0x00]
+        );
+        assert_eq!(
+            Jcc(Cond::NE, -6i32).bytecode().to_bytes().bytes(),
+            [0x0F, 0x85, 0xFA, 0xFF, 0xFF, 0xFF]
+        );
+    }
+}
@@ -0,0 +1,162 @@
+use crate::{
+    asm::{Assembler, Emit, Label, RelField, Sym},
+    object::RelocKind,
+    ByteCode, BytesAtMost, EncodeError, Mem64, ModRM, Reg64, Rex,
+};
+
+/// `i8` / `i32` は命令の末尾からの相対距離 (rel8 / rel32)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Jmp<Dst>(pub Dst);
+
+impl Jmp<i8> {
+    /// EB cb
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        // opcode
+        code.opcode = BytesAtMost::from([0xEB]);
+
+        // immutable val
+        code.imm = BytesAtMost::from(self.0);
+
+        code
+    }
+}
+
+impl Jmp<i32> {
+    /// E9 cd
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        // opcode
+        code.opcode = BytesAtMost::from([0xE9]);
+
+        // immutable val
+        code.imm = BytesAtMost::from(self.0);
+
+        code
+    }
+}
+
+impl Jmp<Reg64> {
+    /// FF /4
+    pub fn bytecode(&self) -> ByteCode {
+        let dst = self.0;
+
+        let mut code = ByteCode::new();
+
+        // REX prefix
+        if dst.rex_b_bit() {
+            let mut rex = Rex::new();
+            rex.set_b(true);
+            code.rex = Some(rex);
+        }
+
+        // opcode
+        code.opcode = BytesAtMost::from([0xFF]);
+
+        // ModR/M
+        let mut mod_rm = ModRM::new();
+        mod_rm.set_mode(dst.mode_bits());
+        mod_rm.set_reg(4);
+        mod_rm.set_rm(dst.rm_bits());
+        code.mod_rm = Some(mod_rm);
+
+        code
+    }
+}
+
+impl Jmp<Mem64> {
+    /// FF /4
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let dst = self.0;
+        dst.validate()?;
+
+        let mut code = ByteCode::new();
+
+        // REX prefix
+        if dst.rex_x_bit() || dst.rex_b_bit() {
+            let mut rex = Rex::new();
+            rex.set_x(dst.rex_x_bit());
+            rex.set_b(dst.rex_b_bit());
+            code.rex = Some(rex);
+        }
+
+        // opcode
+        code.opcode = BytesAtMost::from([0xFF]);
+
+        // ModR/M
+        let mut mod_rm = ModRM::new();
+        mod_rm.set_mode(dst.mode_bits());
+        mod_rm.set_reg(4);
+        mod_rm.set_rm(dst.rm_bits());
+        code.mod_rm = Some(mod_rm);
+
+        // SIB
+        code.sib = dst.sib_byte();
+
+        // addr disp
+        code.addr_disp = dst.disp_bytes();
+
+        Ok(code)
+    }
+}
+
+/// rel8 で届くなら `jmp rel8`、そうでなければ `jmp rel32`
+impl Emit for Jmp<Label> {
+    fn try_emit(&self, asm: &mut Assembler) -> Result<(), EncodeError> {
+        asm.push_branch(Jmp(0i8).bytecode(), Jmp(0i32).bytecode(), self.0);
+        Ok(())
+    }
+}
+
+/// 飛び先はリンク時まで分からないので常に `jmp rel32` (R_X86_64_PLT32)
+impl Emit for Jmp<Sym<'_>> {
+    fn try_emit(&self, asm: &mut Assembler) -> Result<(), EncodeError> {
+        asm.push_reloc(
+            Jmp(0i32).bytecode(),
+            RelField::Imm,
+            (self.0).0,
+            RelocKind::Plt32,
+        );
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        use Reg64::*;
+
+        let cases = [
+            (Jmp(-2i8).bytecode(), vec![0xEB, 0xFE]),
+            (Jmp(0x100i32).bytecode(), vec![0xE9, 0x00, 0x01, 0x00, 0x00]),
+            (Jmp(RAX).bytecode(), vec![0xFF, 0xE0]),
+            (Jmp(R11).bytecode(), vec![0x41, 0xFF, 0xE3]),
+            (Jmp(Mem64::reg(RAX)).bytecode(), vec![0xFF, 0x20]),
+            (
+                Jmp(Mem64::sib(None, 0x1000, RAX, 3)).bytecode(),
+                vec![0xFF, 0x24, 0xC5, 0x00, 0x10, 0x00, 0x00],
+            ),
+            (
+                Jmp(Mem64::sib(Some(R12), 0, R9, 3)).bytecode(),
+                vec![0x43, 0xFF, 0x24, 0xCC],
+            ),
+            (
+                Jmp(Mem64::rip_offset(8)).bytecode(),
+                vec![0xFF, 0x25, 0x08, 0x00, 0x00, 0x00],
+            ),
+        ];
+
+        for (code, expected) in cases {
+            assert_eq!(code.to_bytes().bytes(), expected);
+        }
+    }
+}
@@ -0,0 +1,31 @@
+use crate::{ByteCode, BytesAtMost};
+
+/// `lahf` : SF/ZF/AF/PF/CF を AH に読み込む
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lahf();
+
+impl Lahf {
+    /// 9F
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        code.opcode = BytesAtMost::from([0x9F]);
+
+        code
+    }
+}
+
+/// `sahf` : AH から SF/ZF/AF/PF/CF を設定する
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sahf();
+
+impl Sahf {
+    /// 9E
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        code.opcode = BytesAtMost::from([0x9E]);
+
+        code
+    }
+}
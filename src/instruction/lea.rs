@@ -1,24 +1,43 @@
-use crate::{ByteCode, BytesAtMost, Mem64, ModRM, Reg64, Rex};
+use crate::{
+    asm::{Assembler, Emit, Label, RelField, Sym},
+    object::RelocKind,
+    ByteCode, BytesAtMost, EncodeError, Mem64, ModRM, Reg64, Register, Rex, Size,
+};
 
-pub struct Lea<Dst, Src>(Dst, Src);
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Lea<Dst, Src>(pub Dst, pub Src);
 
 impl Lea<Reg64, Mem64> {
     pub fn new(dst: Reg64, src: Mem64) -> Self {
         Lea(dst, src)
     }
+}
 
+/// 8bit レジスタは `bytecode` では panic、`try_bytecode` では `Err` になる
+impl<R: Register> Lea<R, Mem64> {
     pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
         let (dst, src) = (self.0, self.1);
+        src.validate()?;
+        if dst.size() == Size::Byte {
+            return Err(EncodeError::InvalidOperandSize(Size::Byte));
+        }
 
         let mut code = ByteCode::new();
 
+        // operand-size prefix
+        code.prefix = dst.size().prefix();
+
         // REX prefix
         let mut rex = Rex::new();
-        rex.set_w(true);
+        rex.set_w(dst.size().rex_w());
         rex.set_r(dst.rex_r_bit());
         rex.set_x(src.rex_x_bit());
         rex.set_b(src.rex_b_bit());
-        code.rex = Some(rex);
+        code.set_rex(rex, false);
 
         // opcode
         code.opcode = BytesAtMost::from([0x8D]);
@@ -36,7 +55,25 @@ impl Lea<Reg64, Mem64> {
         // addr disp
         code.addr_disp = src.disp_bytes();
 
-        code
+        Ok(code)
+    }
+}
+
+/// `lea reg, [RIP + label]`
+impl Emit for Lea<Reg64, Label> {
+    fn try_emit(&self, asm: &mut Assembler) -> Result<(), EncodeError> {
+        let code = Lea(self.0, Mem64::rip_offset(0)).try_bytecode()?;
+        asm.push_label_ref(code, RelField::Disp, self.1);
+        Ok(())
+    }
+}
+
+/// `lea reg, [RIP + sym]` (R_X86_64_PC32)
+impl Emit for Lea<Reg64, Sym<'_>> {
+    fn try_emit(&self, asm: &mut Assembler) -> Result<(), EncodeError> {
+        let code = Lea(self.0, Mem64::rip_offset(0)).try_bytecode()?;
+        asm.push_reloc(code, RelField::Disp, (self.1).0, RelocKind::Pc32);
+        Ok(())
     }
 }
 
@@ -54,6 +91,18 @@ mod test {
                 Lea::new(RAX, Mem64::reg_offset(RDI, 42)),
                 vec![0x48, 0x8D, 0x47, 0x2A],
             ),
+            (
+                Lea::new(RAX, Mem64::reg_offset(RBP, -8)),
+                vec![0x48, 0x8D, 0x45, 0xF8],
+            ),
+            (
+                Lea::new(RAX, Mem64::reg_offset(RSP, -128)),
+                vec![0x48, 0x8D, 0x44, 0x24, 0x80],
+            ),
+            (
+                Lea::new(RAX, Mem64::reg_offset(R12, 128)),
+                vec![0x49, 0x8D, 0x84, 0x24, 0x80, 0x00, 0x00, 0x00],
+            ),
             (Lea::new(RSP, Mem64::reg(RSP)), vec![0x48, 0x8D, 0x24, 0x24]),
             (Lea::new(RAX, Mem64::reg(RSP)), vec![0x48, 0x8D, 0x04, 0x24]),
             (
@@ -74,4 +123,40 @@ mod test {
             assert_eq!(origin.bytecode().to_bytes().bytes(), expected);
         }
     }
+
+    #[test]
+    fn test_operand_size() {
+        use crate::{Reg16::*, Reg32::*};
+
+        assert_eq!(
+            Lea(EAX, Mem64::reg(Reg64::RDI))
+                .bytecode()
+                .to_bytes()
+                .bytes(),
+            [0x8D, 0x07]
+        );
+        assert_eq!(
+            Lea(R8D, Mem64::reg_offset(Reg64::RSP, 8))
+                .bytecode()
+                .to_bytes()
+                .bytes(),
+            [0x44, 0x8D, 0x44, 0x24, 0x08]
+        );
+        assert_eq!(
+            Lea(CX, Mem64::rip_offset(42)).bytecode().to_bytes().bytes(),
+            [0x66, 0x8D, 0x0D, 0x2A, 0x00, 0x00, 0x00]
+        );
+    }
+
+    #[test]
+    fn test_try_bytecode_error() {
+        assert_eq!(
+            Lea(crate::Reg8::AL, Mem64::reg(Reg64::RDI)).try_bytecode(),
+            Err(EncodeError::InvalidOperandSize(Size::Byte))
+        );
+        assert_eq!(
+            Lea(Reg64::RAX, Mem64::sib(None, 0, Reg64::RSP, 0)).try_bytecode(),
+            Ok(Lea(Reg64::RAX, Mem64::sib(None, 0, Reg64::RSP, 0)).bytecode())
+        );
+    }
 }
@@ -1,3 +1,772 @@
+pub mod alu;
+pub mod call;
+pub mod jcc;
+pub mod jmp;
+pub mod lahf;
 pub mod lea;
 pub mod mov;
+pub mod mul;
+pub mod push;
+pub mod ret;
+pub mod shift;
 pub mod syscall;
+
+pub use alu::{Alu, AluOp};
+pub use call::Call;
+pub use jcc::{Cond, Jcc};
+pub use jmp::Jmp;
+pub use lahf::{Lahf, Sahf};
+pub use lea::Lea;
+pub use mov::Mov;
+pub use mul::{Cdq, Cdqe, Cqo, Cwd, Imul, ImulImm, MulDiv, MulOp};
+pub use push::{Pop, Push};
+pub use ret::{Ret, RetImm};
+pub use shift::{Cl, DoubleShift, DoubleShiftOp, Shift, ShiftOp};
+pub use syscall::Syscall;
+
+use crate::{
+    syntax::{Intel, Syntax},
+    ByteCode, EncodeError, Mem64, Ptr, Reg, Reg16, Reg32, Reg64, Reg8, Register,
+};
+use std::fmt::{Display, Error as FmtError, Formatter};
+
+/// Any instruction this crate can encode, as a typed value.
+///
+/// This is what the decoder lifts machine code into. Instructions that work
+/// across operand sizes hold a [`Reg`] (or a [`Ptr`] when only the memory
+/// operand carries the size); the statically sized forms such as
+/// `Mov<Reg32, Reg32>` convert into them with `From`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    MovRegReg(Mov<Reg, Reg>),
+    MovMemReg(Mov<Mem64, Reg>),
+    MovRegMem(Mov<Reg, Mem64>),
+    MovRegImm(Mov<Reg, u64>),
+    MovMemImm(Mov<Ptr, i32>),
+    Lea(Lea<Reg, Mem64>),
+    Syscall(Syscall),
+    JmpI8(Jmp<i8>),
+    JmpI32(Jmp<i32>),
+    JmpReg64(Jmp<Reg64>),
+    JmpMem64(Jmp<Mem64>),
+    JccI8(Jcc<i8>),
+    JccI32(Jcc<i32>),
+    CallI32(Call<i32>),
+    CallReg64(Call<Reg64>),
+    CallMem64(Call<Mem64>),
+    Ret(Ret),
+    RetImm(RetImm),
+    PushReg64(Push<Reg64>),
+    PushReg16(Push<Reg16>),
+    PushMem(Push<Ptr>),
+    PushI8(Push<i8>),
+    PushI32(Push<i32>),
+    PopReg64(Pop<Reg64>),
+    PopReg16(Pop<Reg16>),
+    PopMem(Pop<Ptr>),
+    AluRegReg(Alu<Reg, Reg>),
+    AluMemReg(Alu<Mem64, Reg>),
+    AluRegMem(Alu<Reg, Mem64>),
+    AluRegImm(Alu<Reg, i32>),
+    AluMemImm(Alu<Ptr, i32>),
+    ShiftRegImm(Shift<Reg, u8>),
+    ShiftRegCl(Shift<Reg, Cl>),
+    ShiftMemImm(Shift<Ptr, u8>),
+    ShiftMemCl(Shift<Ptr, Cl>),
+    DoubleShiftRegImm(DoubleShift<Reg, Reg, u8>),
+    DoubleShiftRegCl(DoubleShift<Reg, Reg, Cl>),
+    DoubleShiftMemImm(DoubleShift<Mem64, Reg, u8>),
+    DoubleShiftMemCl(DoubleShift<Mem64, Reg, Cl>),
+    MulDivReg(MulDiv<Reg>),
+    MulDivMem(MulDiv<Ptr>),
+    ImulRegReg(Imul<Reg, Reg>),
+    ImulRegMem(Imul<Reg, Mem64>),
+    ImulRegRegImm(ImulImm<Reg, Reg>),
+    ImulRegMemImm(ImulImm<Reg, Mem64>),
+    Cwd(Cwd),
+    Cdq(Cdq),
+    Cqo(Cqo),
+    Cdqe(Cdqe),
+    Lahf(Lahf),
+    Sahf(Sahf),
+}
+
+impl Instruction {
+    pub fn bytecode(&self) -> ByteCode {
+        match self {
+            Instruction::MovRegReg(inst) => inst.bytecode(),
+            Instruction::MovMemReg(inst) => inst.bytecode(),
+            Instruction::MovRegMem(inst) => inst.bytecode(),
+            Instruction::MovRegImm(inst) => inst.bytecode(),
+            Instruction::MovMemImm(inst) => inst.bytecode(),
+            Instruction::Lea(inst) => inst.bytecode(),
+            Instruction::Syscall(inst) => inst.bytecode(),
+            Instruction::JmpI8(inst) => inst.bytecode(),
+            Instruction::JmpI32(inst) => inst.bytecode(),
+            Instruction::JmpReg64(inst) => inst.bytecode(),
+            Instruction::JmpMem64(inst) => inst.bytecode(),
+            Instruction::JccI8(inst) => inst.bytecode(),
+            Instruction::JccI32(inst) => inst.bytecode(),
+            Instruction::CallI32(inst) => inst.bytecode(),
+            Instruction::CallReg64(inst) => inst.bytecode(),
+            Instruction::CallMem64(inst) => inst.bytecode(),
+            Instruction::Ret(inst) => inst.bytecode(),
+            Instruction::RetImm(inst) => inst.bytecode(),
+            Instruction::PushReg64(inst) => inst.bytecode(),
+            Instruction::PushReg16(inst) => inst.bytecode(),
+            Instruction::PushMem(inst) => inst.bytecode(),
+            Instruction::PushI8(inst) => inst.bytecode(),
+            Instruction::PushI32(inst) => inst.bytecode(),
+            Instruction::PopReg64(inst) => inst.bytecode(),
+            Instruction::PopReg16(inst) => inst.bytecode(),
+            Instruction::PopMem(inst) => inst.bytecode(),
+            Instruction::AluRegReg(inst) => inst.bytecode(),
+            Instruction::AluMemReg(inst) => inst.bytecode(),
+            Instruction::AluRegMem(inst) => inst.bytecode(),
+            Instruction::AluRegImm(inst) => inst.bytecode(),
+            Instruction::AluMemImm(inst) => inst.bytecode(),
+            Instruction::ShiftRegImm(inst) => inst.bytecode(),
+            Instruction::ShiftRegCl(inst) => inst.bytecode(),
+            Instruction::ShiftMemImm(inst) => inst.bytecode(),
+            Instruction::ShiftMemCl(inst) => inst.bytecode(),
+            Instruction::DoubleShiftRegImm(inst) => inst.bytecode(),
+            Instruction::DoubleShiftRegCl(inst) => inst.bytecode(),
+            Instruction::DoubleShiftMemImm(inst) => inst.bytecode(),
+            Instruction::DoubleShiftMemCl(inst) => inst.bytecode(),
+            Instruction::MulDivReg(inst) => inst.bytecode(),
+            Instruction::MulDivMem(inst) => inst.bytecode(),
+            Instruction::ImulRegReg(inst) => inst.bytecode(),
+            Instruction::ImulRegMem(inst) => inst.bytecode(),
+            Instruction::ImulRegRegImm(inst) => inst.bytecode(),
+            Instruction::ImulRegMemImm(inst) => inst.bytecode(),
+            Instruction::Cwd(inst) => inst.bytecode(),
+            Instruction::Cdq(inst) => inst.bytecode(),
+            Instruction::Cqo(inst) => inst.bytecode(),
+            Instruction::Cdqe(inst) => inst.bytecode(),
+            Instruction::Lahf(inst) => inst.bytecode(),
+            Instruction::Sahf(inst) => inst.bytecode(),
+        }
+    }
+
+    /// エンコードできないオペランド (範囲外の即値、不正な SIB など) は `Err` を返す
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        match self {
+            Instruction::MovRegReg(inst) => inst.try_bytecode(),
+            Instruction::MovMemReg(inst) => inst.try_bytecode(),
+            Instruction::MovRegMem(inst) => inst.try_bytecode(),
+            Instruction::MovRegImm(inst) => inst.try_bytecode(),
+            Instruction::MovMemImm(inst) => inst.try_bytecode(),
+            Instruction::Lea(inst) => inst.try_bytecode(),
+            Instruction::JmpMem64(inst) => inst.try_bytecode(),
+            Instruction::CallMem64(inst) => inst.try_bytecode(),
+            Instruction::PushMem(inst) => inst.try_bytecode(),
+            Instruction::PopMem(inst) => inst.try_bytecode(),
+            Instruction::AluRegReg(inst) => inst.try_bytecode(),
+            Instruction::AluMemReg(inst) => inst.try_bytecode(),
+            Instruction::AluRegMem(inst) => inst.try_bytecode(),
+            Instruction::AluRegImm(inst) => inst.try_bytecode(),
+            Instruction::AluMemImm(inst) => inst.try_bytecode(),
+            Instruction::ShiftRegImm(inst) => inst.try_bytecode(),
+            Instruction::ShiftRegCl(inst) => inst.try_bytecode(),
+            Instruction::ShiftMemImm(inst) => inst.try_bytecode(),
+            Instruction::ShiftMemCl(inst) => inst.try_bytecode(),
+            Instruction::DoubleShiftRegImm(inst) => inst.try_bytecode(),
+            Instruction::DoubleShiftRegCl(inst) => inst.try_bytecode(),
+            Instruction::DoubleShiftMemImm(inst) => inst.try_bytecode(),
+            Instruction::DoubleShiftMemCl(inst) => inst.try_bytecode(),
+            Instruction::MulDivReg(inst) => inst.try_bytecode(),
+            Instruction::MulDivMem(inst) => inst.try_bytecode(),
+            Instruction::ImulRegReg(inst) => inst.try_bytecode(),
+            Instruction::ImulRegMem(inst) => inst.try_bytecode(),
+            Instruction::ImulRegRegImm(inst) => inst.try_bytecode(),
+            Instruction::ImulRegMemImm(inst) => inst.try_bytecode(),
+            inst => Ok(inst.bytecode()),
+        }
+    }
+}
+
+/// Intel 記法 ([`Intel`] と同じ)
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        Intel.fmt_instruction(self, f)
+    }
+}
+
+/// 個々の命令型は [`Instruction`] に変換して表示する
+macro_rules! impl_display {
+    ($(impl$(<$param:ident>)? for $ty:ty;)*) => {
+        $(
+            impl$(<$param: Register + Into<Reg>>)? Display for $ty {
+                fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+                    Instruction::from(*self).fmt(f)
+                }
+            }
+        )*
+    };
+}
+
+impl_display! {
+    impl<R> for Mov<R, R>;
+    impl<R> for Mov<Mem64, R>;
+    impl<R> for Mov<R, Mem64>;
+    impl for Mov<Reg, u64>;
+    impl for Mov<Reg64, u64>;
+    impl for Mov<Reg32, u32>;
+    impl for Mov<Reg16, u16>;
+    impl for Mov<Reg8, u8>;
+    impl for Mov<Ptr, i32>;
+    impl for Mov<Mem64, i32>;
+    impl<R> for Lea<R, Mem64>;
+    impl for Syscall;
+    impl for Jmp<i8>;
+    impl for Jmp<i32>;
+    impl for Jmp<Reg64>;
+    impl for Jmp<Mem64>;
+    impl for Jcc<i8>;
+    impl for Jcc<i32>;
+    impl for Call<i32>;
+    impl for Call<Reg64>;
+    impl for Call<Mem64>;
+    impl for Ret;
+    impl for RetImm;
+    impl for Push<Reg64>;
+    impl for Push<Reg16>;
+    impl for Push<Ptr>;
+    impl for Push<Mem64>;
+    impl for Push<i8>;
+    impl for Push<i32>;
+    impl for Pop<Reg64>;
+    impl for Pop<Reg16>;
+    impl for Pop<Ptr>;
+    impl for Pop<Mem64>;
+    impl<R> for Alu<R, R>;
+    impl<R> for Alu<Mem64, R>;
+    impl<R> for Alu<R, Mem64>;
+    impl for Alu<Reg, i32>;
+    impl for Alu<Reg64, i32>;
+    impl for Alu<Reg32, i32>;
+    impl for Alu<Reg16, i16>;
+    impl for Alu<Reg8, i8>;
+    impl for Alu<Ptr, i32>;
+    impl for Alu<Mem64, i32>;
+    impl<R> for Shift<R, u8>;
+    impl<R> for Shift<R, Cl>;
+    impl for Shift<Ptr, u8>;
+    impl for Shift<Ptr, Cl>;
+    impl for Shift<Mem64, u8>;
+    impl for Shift<Mem64, Cl>;
+    impl<R> for DoubleShift<R, R, u8>;
+    impl<R> for DoubleShift<R, R, Cl>;
+    impl<R> for DoubleShift<Mem64, R, u8>;
+    impl<R> for DoubleShift<Mem64, R, Cl>;
+    impl<R> for MulDiv<R>;
+    impl for MulDiv<Ptr>;
+    impl for MulDiv<Mem64>;
+    impl<R> for Imul<R, R>;
+    impl<R> for Imul<R, Mem64>;
+    impl<R> for ImulImm<R, R>;
+    impl<R> for ImulImm<R, Mem64>;
+    impl for Cwd;
+    impl for Cdq;
+    impl for Cqo;
+    impl for Cdqe;
+    impl for Lahf;
+    impl for Sahf;
+}
+
+impl<R: Register + Into<Reg>> From<Mov<R, R>> for Instruction {
+    fn from(Mov(dst, src): Mov<R, R>) -> Self {
+        Instruction::MovRegReg(Mov(dst.into(), src.into()))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<Mov<Mem64, R>> for Instruction {
+    fn from(Mov(dst, src): Mov<Mem64, R>) -> Self {
+        Instruction::MovMemReg(Mov(dst, src.into()))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<Mov<R, Mem64>> for Instruction {
+    fn from(Mov(dst, src): Mov<R, Mem64>) -> Self {
+        Instruction::MovRegMem(Mov(dst.into(), src))
+    }
+}
+
+impl From<Mov<Reg, u64>> for Instruction {
+    fn from(inst: Mov<Reg, u64>) -> Self {
+        Instruction::MovRegImm(inst)
+    }
+}
+
+impl From<Mov<Reg64, u64>> for Instruction {
+    fn from(Mov(dst, src): Mov<Reg64, u64>) -> Self {
+        Instruction::MovRegImm(Mov(dst.into(), src))
+    }
+}
+
+impl From<Mov<Reg32, u32>> for Instruction {
+    fn from(Mov(dst, src): Mov<Reg32, u32>) -> Self {
+        Instruction::MovRegImm(Mov(dst.into(), src as u64))
+    }
+}
+
+impl From<Mov<Reg16, u16>> for Instruction {
+    fn from(Mov(dst, src): Mov<Reg16, u16>) -> Self {
+        Instruction::MovRegImm(Mov(dst.into(), src as u64))
+    }
+}
+
+impl From<Mov<Reg8, u8>> for Instruction {
+    fn from(Mov(dst, src): Mov<Reg8, u8>) -> Self {
+        Instruction::MovRegImm(Mov(dst.into(), src as u64))
+    }
+}
+
+impl From<Mov<Ptr, i32>> for Instruction {
+    fn from(inst: Mov<Ptr, i32>) -> Self {
+        Instruction::MovMemImm(inst)
+    }
+}
+
+impl From<Mov<Mem64, i32>> for Instruction {
+    fn from(Mov(dst, src): Mov<Mem64, i32>) -> Self {
+        Instruction::MovMemImm(Mov(Ptr::qword(dst), src))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<Lea<R, Mem64>> for Instruction {
+    fn from(Lea(dst, src): Lea<R, Mem64>) -> Self {
+        Instruction::Lea(Lea(dst.into(), src))
+    }
+}
+
+impl From<Syscall> for Instruction {
+    fn from(inst: Syscall) -> Self {
+        Instruction::Syscall(inst)
+    }
+}
+
+impl From<Jmp<i8>> for Instruction {
+    fn from(inst: Jmp<i8>) -> Self {
+        Instruction::JmpI8(inst)
+    }
+}
+
+impl From<Jmp<i32>> for Instruction {
+    fn from(inst: Jmp<i32>) -> Self {
+        Instruction::JmpI32(inst)
+    }
+}
+
+impl From<Jmp<Reg64>> for Instruction {
+    fn from(inst: Jmp<Reg64>) -> Self {
+        Instruction::JmpReg64(inst)
+    }
+}
+
+impl From<Jmp<Mem64>> for Instruction {
+    fn from(inst: Jmp<Mem64>) -> Self {
+        Instruction::JmpMem64(inst)
+    }
+}
+
+impl From<Jcc<i8>> for Instruction {
+    fn from(inst: Jcc<i8>) -> Self {
+        Instruction::JccI8(inst)
+    }
+}
+
+impl From<Jcc<i32>> for Instruction {
+    fn from(inst: Jcc<i32>) -> Self {
+        Instruction::JccI32(inst)
+    }
+}
+
+impl From<Call<i32>> for Instruction {
+    fn from(inst: Call<i32>) -> Self {
+        Instruction::CallI32(inst)
+    }
+}
+
+impl From<Call<Reg64>> for Instruction {
+    fn from(inst: Call<Reg64>) -> Self {
+        Instruction::CallReg64(inst)
+    }
+}
+
+impl From<Call<Mem64>> for Instruction {
+    fn from(inst: Call<Mem64>) -> Self {
+        Instruction::CallMem64(inst)
+    }
+}
+
+impl From<Ret> for Instruction {
+    fn from(inst: Ret) -> Self {
+        Instruction::Ret(inst)
+    }
+}
+
+impl From<RetImm> for Instruction {
+    fn from(inst: RetImm) -> Self {
+        Instruction::RetImm(inst)
+    }
+}
+
+impl From<Push<Reg64>> for Instruction {
+    fn from(inst: Push<Reg64>) -> Self {
+        Instruction::PushReg64(inst)
+    }
+}
+
+impl From<Push<Reg16>> for Instruction {
+    fn from(inst: Push<Reg16>) -> Self {
+        Instruction::PushReg16(inst)
+    }
+}
+
+impl From<Push<Ptr>> for Instruction {
+    fn from(inst: Push<Ptr>) -> Self {
+        Instruction::PushMem(inst)
+    }
+}
+
+impl From<Push<Mem64>> for Instruction {
+    fn from(Push(src): Push<Mem64>) -> Self {
+        Instruction::PushMem(Push(Ptr::qword(src)))
+    }
+}
+
+impl From<Push<i8>> for Instruction {
+    fn from(inst: Push<i8>) -> Self {
+        Instruction::PushI8(inst)
+    }
+}
+
+impl From<Push<i32>> for Instruction {
+    fn from(inst: Push<i32>) -> Self {
+        Instruction::PushI32(inst)
+    }
+}
+
+impl From<Pop<Reg64>> for Instruction {
+    fn from(inst: Pop<Reg64>) -> Self {
+        Instruction::PopReg64(inst)
+    }
+}
+
+impl From<Pop<Reg16>> for Instruction {
+    fn from(inst: Pop<Reg16>) -> Self {
+        Instruction::PopReg16(inst)
+    }
+}
+
+impl From<Pop<Ptr>> for Instruction {
+    fn from(inst: Pop<Ptr>) -> Self {
+        Instruction::PopMem(inst)
+    }
+}
+
+impl From<Pop<Mem64>> for Instruction {
+    fn from(Pop(dst): Pop<Mem64>) -> Self {
+        Instruction::PopMem(Pop(Ptr::qword(dst)))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<Alu<R, R>> for Instruction {
+    fn from(Alu(op, dst, src): Alu<R, R>) -> Self {
+        Instruction::AluRegReg(Alu(op, dst.into(), src.into()))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<Alu<Mem64, R>> for Instruction {
+    fn from(Alu(op, dst, src): Alu<Mem64, R>) -> Self {
+        Instruction::AluMemReg(Alu(op, dst, src.into()))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<Alu<R, Mem64>> for Instruction {
+    fn from(Alu(op, dst, src): Alu<R, Mem64>) -> Self {
+        Instruction::AluRegMem(Alu(op, dst.into(), src))
+    }
+}
+
+impl From<Alu<Reg, i32>> for Instruction {
+    fn from(inst: Alu<Reg, i32>) -> Self {
+        Instruction::AluRegImm(inst)
+    }
+}
+
+impl From<Alu<Reg64, i32>> for Instruction {
+    fn from(Alu(op, dst, src): Alu<Reg64, i32>) -> Self {
+        Instruction::AluRegImm(Alu(op, dst.into(), src))
+    }
+}
+
+impl From<Alu<Reg32, i32>> for Instruction {
+    fn from(Alu(op, dst, src): Alu<Reg32, i32>) -> Self {
+        Instruction::AluRegImm(Alu(op, dst.into(), src))
+    }
+}
+
+impl From<Alu<Reg16, i16>> for Instruction {
+    fn from(Alu(op, dst, src): Alu<Reg16, i16>) -> Self {
+        Instruction::AluRegImm(Alu(op, dst.into(), src as i32))
+    }
+}
+
+impl From<Alu<Reg8, i8>> for Instruction {
+    fn from(Alu(op, dst, src): Alu<Reg8, i8>) -> Self {
+        Instruction::AluRegImm(Alu(op, dst.into(), src as i32))
+    }
+}
+
+impl From<Alu<Ptr, i32>> for Instruction {
+    fn from(inst: Alu<Ptr, i32>) -> Self {
+        Instruction::AluMemImm(inst)
+    }
+}
+
+impl From<Alu<Mem64, i32>> for Instruction {
+    fn from(Alu(op, dst, src): Alu<Mem64, i32>) -> Self {
+        Instruction::AluMemImm(Alu(op, Ptr::qword(dst), src))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<Shift<R, u8>> for Instruction {
+    fn from(Shift(op, dst, count): Shift<R, u8>) -> Self {
+        Instruction::ShiftRegImm(Shift(op, dst.into(), count))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<Shift<R, Cl>> for Instruction {
+    fn from(Shift(op, dst, count): Shift<R, Cl>) -> Self {
+        Instruction::ShiftRegCl(Shift(op, dst.into(), count))
+    }
+}
+
+impl From<Shift<Ptr, u8>> for Instruction {
+    fn from(inst: Shift<Ptr, u8>) -> Self {
+        Instruction::ShiftMemImm(inst)
+    }
+}
+
+impl From<Shift<Ptr, Cl>> for Instruction {
+    fn from(inst: Shift<Ptr, Cl>) -> Self {
+        Instruction::ShiftMemCl(inst)
+    }
+}
+
+impl From<Shift<Mem64, u8>> for Instruction {
+    fn from(Shift(op, dst, count): Shift<Mem64, u8>) -> Self {
+        Instruction::ShiftMemImm(Shift(op, Ptr::qword(dst), count))
+    }
+}
+
+impl From<Shift<Mem64, Cl>> for Instruction {
+    fn from(Shift(op, dst, count): Shift<Mem64, Cl>) -> Self {
+        Instruction::ShiftMemCl(Shift(op, Ptr::qword(dst), count))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<DoubleShift<R, R, u8>> for Instruction {
+    fn from(DoubleShift(op, dst, src, count): DoubleShift<R, R, u8>) -> Self {
+        Instruction::DoubleShiftRegImm(DoubleShift(op, dst.into(), src.into(), count))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<DoubleShift<R, R, Cl>> for Instruction {
+    fn from(DoubleShift(op, dst, src, count): DoubleShift<R, R, Cl>) -> Self {
+        Instruction::DoubleShiftRegCl(DoubleShift(op, dst.into(), src.into(), count))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<DoubleShift<Mem64, R, u8>> for Instruction {
+    fn from(DoubleShift(op, dst, src, count): DoubleShift<Mem64, R, u8>) -> Self {
+        Instruction::DoubleShiftMemImm(DoubleShift(op, dst, src.into(), count))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<DoubleShift<Mem64, R, Cl>> for Instruction {
+    fn from(DoubleShift(op, dst, src, count): DoubleShift<Mem64, R, Cl>) -> Self {
+        Instruction::DoubleShiftMemCl(DoubleShift(op, dst, src.into(), count))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<MulDiv<R>> for Instruction {
+    fn from(MulDiv(op, src): MulDiv<R>) -> Self {
+        Instruction::MulDivReg(MulDiv(op, src.into()))
+    }
+}
+
+impl From<MulDiv<Ptr>> for Instruction {
+    fn from(inst: MulDiv<Ptr>) -> Self {
+        Instruction::MulDivMem(inst)
+    }
+}
+
+impl From<MulDiv<Mem64>> for Instruction {
+    fn from(MulDiv(op, src): MulDiv<Mem64>) -> Self {
+        Instruction::MulDivMem(MulDiv(op, Ptr::qword(src)))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<Imul<R, R>> for Instruction {
+    fn from(Imul(dst, src): Imul<R, R>) -> Self {
+        Instruction::ImulRegReg(Imul(dst.into(), src.into()))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<Imul<R, Mem64>> for Instruction {
+    fn from(Imul(dst, src): Imul<R, Mem64>) -> Self {
+        Instruction::ImulRegMem(Imul(dst.into(), src))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<ImulImm<R, R>> for Instruction {
+    fn from(ImulImm(dst, src, imm): ImulImm<R, R>) -> Self {
+        Instruction::ImulRegRegImm(ImulImm(dst.into(), src.into(), imm))
+    }
+}
+
+impl<R: Register + Into<Reg>> From<ImulImm<R, Mem64>> for Instruction {
+    fn from(ImulImm(dst, src, imm): ImulImm<R, Mem64>) -> Self {
+        Instruction::ImulRegMemImm(ImulImm(dst.into(), src, imm))
+    }
+}
+
+impl From<Cwd> for Instruction {
+    fn from(inst: Cwd) -> Self {
+        Instruction::Cwd(inst)
+    }
+}
+
+impl From<Cdq> for Instruction {
+    fn from(inst: Cdq) -> Self {
+        Instruction::Cdq(inst)
+    }
+}
+
+impl From<Cqo> for Instruction {
+    fn from(inst: Cqo) -> Self {
+        Instruction::Cqo(inst)
+    }
+}
+
+impl From<Cdqe> for Instruction {
+    fn from(inst: Cdqe) -> Self {
+        Instruction::Cdqe(inst)
+    }
+}
+
+impl From<Lahf> for Instruction {
+    fn from(inst: Lahf) -> Self {
+        Instruction::Lahf(inst)
+    }
+}
+
+impl From<Sahf> for Instruction {
+    fn from(inst: Sahf) -> Self {
+        Instruction::Sahf(inst)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_display() {
+        use Reg64::*;
+
+        let cases = [
+            (
+                Instruction::from(Mov(Mem64::sib(Some(RBP), 42, RAX, 3), R13)),
+                "mov qword ptr [rbp + rax*8 + 0x2a], r13",
+            ),
+            (
+                Instruction::from(Mov(Reg32::EAX, Mem64::reg_offset(RBP, -4))),
+                "mov eax, dword ptr [rbp - 0x4]",
+            ),
+            (Instruction::from(Mov(Reg8::AH, Reg8::AL)), "mov ah, al"),
+            (
+                Instruction::from(Mov(RAX, u64::MAX)),
+                "mov rax, 0xffffffffffffffff",
+            ),
+            (
+                Instruction::from(Mov(Ptr::word(Mem64::reg(RDI)), -1)),
+                "mov word ptr [rdi], -0x1",
+            ),
+            (
+                Instruction::from(Lea::new(RDI, Mem64::rip_offset(42))),
+                "lea rdi, [rip + 0x2a]",
+            ),
+            (
+                Instruction::from(Mov(Reg32::ECX, Mem64::sib(None, -8, RSP, 0))),
+                "mov ecx, dword ptr [-0x8]",
+            ),
+            (Instruction::from(Syscall()), "syscall"),
+            (Instruction::from(Jmp(-2i8)), "jmp -0x2"),
+            (Instruction::from(Jcc(Cond::NE, 0x100)), "jne 0x100"),
+            (
+                Instruction::from(Call(Mem64::reg_offset(RAX, 8))),
+                "call qword ptr [rax + 0x8]",
+            ),
+            (Instruction::from(RetImm(16)), "ret 0x10"),
+            (Instruction::from(Push(R12)), "push r12"),
+            (
+                Instruction::from(Pop(Mem64::reg(RSP))),
+                "pop qword ptr [rsp]",
+            ),
+            (Instruction::from(Push(Reg16::R9W)), "push r9w"),
+            (
+                Instruction::from(Pop(Ptr::word(Mem64::reg(RBP)))),
+                "pop word ptr [rbp]",
+            ),
+            (Instruction::from(Alu::sub(RSP, 8)), "sub rsp, 0x8"),
+            (
+                Instruction::from(Alu::xor(Mem64::reg(RDI), Reg16::CX)),
+                "xor word ptr [rdi], cx",
+            ),
+            (
+                Instruction::from(Alu::cmp(Ptr::byte(Mem64::reg(RAX)), -128)),
+                "cmp byte ptr [rax], -0x80",
+            ),
+            (Instruction::from(Shift::shl(RAX, 1)), "shl rax, 0x1"),
+            (
+                Instruction::from(Shift::sar(Ptr::dword(Mem64::reg(RDI)), Cl)),
+                "sar dword ptr [rdi], cl",
+            ),
+            (
+                Instruction::from(DoubleShift::shrd(Mem64::reg(RAX), Reg16::DX, 4)),
+                "shrd word ptr [rax], dx, 0x4",
+            ),
+            (Instruction::from(MulDiv::idiv(RCX)), "idiv rcx"),
+            (
+                Instruction::from(MulDiv::mul(Ptr::byte(Mem64::reg(RSI)))),
+                "mul byte ptr [rsi]",
+            ),
+            (
+                Instruction::from(ImulImm(Reg32::EAX, Mem64::reg(RDI), -3)),
+                "imul eax, dword ptr [rdi], -0x3",
+            ),
+            (Instruction::from(Cqo()), "cqo"),
+            (Instruction::from(Lahf()), "lahf"),
+        ];
+
+        for (inst, expected) in cases {
+            assert_eq!(inst.to_string(), expected);
+        }
+
+        // 個々の命令型も同じ表示
+        assert_eq!(
+            Mov(Mem64::sib(Some(RBP), 42, RAX, 3), R13).to_string(),
+            "mov qword ptr [rbp + rax*8 + 0x2a], r13"
+        );
+        assert_eq!(
+            Mov(Mem64::sib(Some(RBP), 42, RAX, 3), R13)
+                .bytecode()
+                .to_string(),
+            "4c 89 6c c5 2a"
+        );
+    }
+}
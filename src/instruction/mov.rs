@@ -1,23 +1,43 @@
-use crate::{ByteCode, BytesAtMost, Mem64, ModRM, Reg64, Rex};
+use crate::{
+    asm::{Assembler, Emit, Got, RelField, Sym},
+    encode::{check_high_byte, check_imm, check_same_size},
+    object::RelocKind,
+    ByteCode, BytesAtMost, EncodeError, Mem64, ModRM, Ptr, Reg, Reg16, Reg32, Reg64, Reg8,
+    Register, Rex, Size,
+};
+use std::convert::TryFrom;
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Mov<Dst, Src>(pub Dst, pub Src);
 
-impl Mov<Mem64, Reg64> {
+impl<R: Register> Mov<Mem64, R> {
     pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
         let (dst, src) = (self.0, self.1);
+        dst.validate()?;
 
         let mut code = ByteCode::new();
 
+        // operand-size prefix
+        code.prefix = src.size().prefix();
+
         // REX prefix
         let mut rex = Rex::new();
-        rex.set_w(true);
+        rex.set_w(src.size().rex_w());
         rex.set_r(src.rex_r_bit());
         rex.set_x(dst.rex_x_bit());
         rex.set_b(dst.rex_b_bit());
-        code.rex = Some(rex);
+        code.set_rex(rex, src.requires_rex());
+        check_high_byte(&code, &[src])?;
 
         // opcode
-        code.opcode = BytesAtMost::from([0x89]);
+        match src.size() {
+            Size::Byte => code.opcode = BytesAtMost::from([0x88]),
+            _ => code.opcode = BytesAtMost::from([0x89]),
+        }
 
         // ModR/M
         let mut mod_rm = ModRM::new();
@@ -32,26 +52,38 @@ impl Mov<Mem64, Reg64> {
         // addr disp
         code.addr_disp = dst.disp_bytes();
 
-        code
+        Ok(code)
     }
 }
 
-impl Mov<Reg64, Reg64> {
+impl<R: Register> Mov<R, R> {
     pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
         let (dst, src) = (self.0, self.1);
+        check_same_size(dst, src)?;
 
         let mut code = ByteCode::new();
 
+        // set operand-size prefix
+        code.prefix = src.size().prefix();
+
         // set REX prefix
         let mut rex = Rex::new();
-        rex.set_w(true);
+        rex.set_w(src.size().rex_w());
         rex.set_r(src.rex_r_bit());
         rex.set_x(false);
         rex.set_b(dst.rex_b_bit());
-        code.rex = Some(rex);
+        code.set_rex(rex, src.requires_rex() || dst.requires_rex());
+        check_high_byte(&code, &[dst, src])?;
 
         // set opcode
-        code.opcode = BytesAtMost::from([0x89]);
+        match src.size() {
+            Size::Byte => code.opcode = BytesAtMost::from([0x88]),
+            _ => code.opcode = BytesAtMost::from([0x89]),
+        }
 
         // set ModR/M
         let mut mod_rm = ModRM::new();
@@ -60,14 +92,122 @@ impl Mov<Reg64, Reg64> {
         mod_rm.set_rm(dst.rm_bits());
         code.mod_rm = Some(mod_rm);
 
-        code
+        Ok(code)
+    }
+}
+
+impl<R: Register> Mov<R, Mem64> {
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (dst, src) = (self.0, self.1);
+        src.validate()?;
+
+        let mut code = ByteCode::new();
+
+        // operand-size prefix
+        code.prefix = dst.size().prefix();
+
+        // REX prefix
+        let mut rex = Rex::new();
+        rex.set_w(dst.size().rex_w());
+        rex.set_r(dst.rex_r_bit());
+        rex.set_x(src.rex_x_bit());
+        rex.set_b(src.rex_b_bit());
+        code.set_rex(rex, dst.requires_rex());
+        check_high_byte(&code, &[dst])?;
+
+        // opcode
+        match dst.size() {
+            Size::Byte => code.opcode = BytesAtMost::from([0x8A]),
+            _ => code.opcode = BytesAtMost::from([0x8B]),
+        }
+
+        // ModR/M
+        let mut mod_rm = ModRM::new();
+        mod_rm.set_mode(src.mode_bits());
+        mod_rm.set_reg(dst.reg_bits());
+        mod_rm.set_rm(src.rm_bits());
+        code.mod_rm = Some(mod_rm);
+
+        // SIB
+        code.sib = src.sib_byte();
+
+        // addr disp
+        code.addr_disp = src.disp_bytes();
+
+        Ok(code)
     }
 }
 
 impl Mov<Reg64, u64> {
+    /// 値に応じて最短のエンコーディングを選択する
+    ///
+    /// - 0 ~ u32::MAX : `mov r32, imm32` (上位32bitはゼロ拡張される)
+    /// - i32に収まる負数 : `mov r/m64, imm32` (符号拡張される)
+    /// - それ以外 : `movabs r64, imm64`
     pub fn bytecode(&self) -> ByteCode {
         let (dst, src) = (self.0, self.1);
 
+        if let Ok(imm) = u32::try_from(src) {
+            self.bytecode_zero_extended(dst, imm)
+        } else if let Ok(imm) = i32::try_from(src as i64) {
+            self.bytecode_sign_extended(dst, imm)
+        } else {
+            self.bytecode_imm64(dst, src)
+        }
+    }
+
+    /// B8+rd id
+    fn bytecode_zero_extended(&self, dst: Reg64, imm: u32) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        // REX prefix
+        if dst.rex_b_bit() {
+            let mut rex = Rex::new();
+            rex.set_b(true);
+            code.rex = Some(rex);
+        }
+
+        // opcode
+        code.opcode = BytesAtMost::from([0xB8 + dst.reg_bits()]);
+
+        // immutable val
+        code.imm = BytesAtMost::from(imm);
+
+        code
+    }
+
+    /// REX.W + C7 /0 id
+    fn bytecode_sign_extended(&self, dst: Reg64, imm: i32) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        // REX prefix
+        let mut rex = Rex::new();
+        rex.set_w(true);
+        rex.set_b(dst.rex_b_bit());
+        code.rex = Some(rex);
+
+        // opcode
+        code.opcode = BytesAtMost::from([0xC7]);
+
+        // ModR/M
+        let mut mod_rm = ModRM::new();
+        mod_rm.set_mode(dst.mode_bits());
+        mod_rm.set_reg(0);
+        mod_rm.set_rm(dst.rm_bits());
+        code.mod_rm = Some(mod_rm);
+
+        // immutable val
+        code.imm = BytesAtMost::from(imm);
+
+        code
+    }
+
+    /// REX.W + B8+rd io
+    fn bytecode_imm64(&self, dst: Reg64, imm: u64) -> ByteCode {
         let mut code = ByteCode::new();
 
         // REX prefix
@@ -79,6 +219,46 @@ impl Mov<Reg64, u64> {
         // opcode
         code.opcode = BytesAtMost::from([0xB8 + dst.reg_bits()]);
 
+        // immutable val
+        code.imm = BytesAtMost::from(imm);
+
+        code
+    }
+}
+
+/// `movabs reg, sym` (R_X86_64_64)
+impl Emit for Mov<Reg64, Sym<'_>> {
+    fn try_emit(&self, asm: &mut Assembler) -> Result<(), EncodeError> {
+        let code = Mov(self.0, 0u64).bytecode_imm64(self.0, 0);
+        asm.push_reloc(code, RelField::Imm, (self.1).0, RelocKind::Abs64);
+        Ok(())
+    }
+}
+
+/// `mov reg, [RIP + sym@GOTPCREL]`
+impl Emit for Mov<Reg64, Got<'_>> {
+    fn try_emit(&self, asm: &mut Assembler) -> Result<(), EncodeError> {
+        let code = Mov(self.0, Mem64::rip_offset(0)).try_bytecode()?;
+        asm.push_reloc(code, RelField::Disp, (self.1).0, RelocKind::GotPcRel);
+        Ok(())
+    }
+}
+
+impl Mov<Reg32, u32> {
+    /// B8+rd id
+    pub fn bytecode(&self) -> ByteCode {
+        let (dst, src) = (self.0, self.1);
+
+        let mut code = ByteCode::new();
+
+        // REX prefix
+        let mut rex = Rex::new();
+        rex.set_b(dst.rex_b_bit());
+        code.set_rex(rex, false);
+
+        // opcode
+        code.opcode = BytesAtMost::from([0xB8 + dst.reg_bits()]);
+
         // immutable val
         code.imm = BytesAtMost::from(src);
 
@@ -86,6 +266,148 @@ impl Mov<Reg64, u64> {
     }
 }
 
+impl Mov<Reg16, u16> {
+    /// 66 B8+rw iw
+    pub fn bytecode(&self) -> ByteCode {
+        let (dst, src) = (self.0, self.1);
+
+        let mut code = ByteCode::new();
+
+        // operand-size prefix
+        code.prefix = Size::Word.prefix();
+
+        // REX prefix
+        let mut rex = Rex::new();
+        rex.set_b(dst.rex_b_bit());
+        code.set_rex(rex, false);
+
+        // opcode
+        code.opcode = BytesAtMost::from([0xB8 + dst.reg_bits()]);
+
+        // immutable val
+        code.imm = BytesAtMost::from(src.to_le_bytes());
+
+        code
+    }
+}
+
+impl Mov<Reg8, u8> {
+    /// B0+rb ib
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (dst, src) = (self.0, self.1);
+
+        let mut code = ByteCode::new();
+
+        // REX prefix
+        let mut rex = Rex::new();
+        rex.set_b(dst.rex_b_bit());
+        code.set_rex(rex, dst.requires_rex());
+        check_high_byte(&code, &[dst])?;
+
+        // opcode
+        code.opcode = BytesAtMost::from([0xB0 + dst.reg_bits()]);
+
+        // immutable val
+        code.imm = BytesAtMost::from(src);
+
+        Ok(code)
+    }
+}
+
+/// レジスタのサイズに収まらない即値は `bytecode` では panic、`try_bytecode` では `Err` になる
+impl Mov<Reg, u64> {
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (dst, src) = (self.0, self.1);
+        check_imm(dst.size(), src as i64)?;
+
+        match dst {
+            Reg::Reg64(dst) => Ok(Mov(dst, src).bytecode()),
+            Reg::Reg32(dst) => Ok(Mov(dst, src as u32).bytecode()),
+            Reg::Reg16(dst) => Ok(Mov(dst, src as u16).bytecode()),
+            Reg::Reg8(dst) => Mov(dst, src as u8).try_bytecode(),
+        }
+    }
+}
+
+impl Mov<Mem64, i32> {
+    /// REX.W + C7 /0 id (64bit に符号拡張される)
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        Mov(Ptr::qword(self.0), self.1).try_bytecode()
+    }
+}
+
+/// サイズに収まらない即値は `bytecode` では panic、`try_bytecode` では `Err` になる
+impl Mov<Ptr, i32> {
+    /// - byte : C6 /0 ib
+    /// - word : 66 C7 /0 iw
+    /// - dword : C7 /0 id
+    /// - qword : REX.W + C7 /0 id (64bit に符号拡張される)
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (Ptr(size, dst), src) = (self.0, self.1);
+        dst.validate()?;
+        check_imm(size, src as i64)?;
+
+        let mut code = ByteCode::new();
+
+        // operand-size prefix
+        code.prefix = size.prefix();
+
+        // REX prefix
+        let mut rex = Rex::new();
+        rex.set_w(size.rex_w());
+        rex.set_x(dst.rex_x_bit());
+        rex.set_b(dst.rex_b_bit());
+        code.set_rex(rex, false);
+
+        // opcode, immutable val
+        match size {
+            Size::Byte => {
+                code.opcode = BytesAtMost::from([0xC6]);
+                code.imm = BytesAtMost::from(src as u8);
+            }
+            Size::Word => {
+                code.opcode = BytesAtMost::from([0xC7]);
+                code.imm = BytesAtMost::from((src as u16).to_le_bytes());
+            }
+            Size::Dword | Size::Qword => {
+                code.opcode = BytesAtMost::from([0xC7]);
+                code.imm = BytesAtMost::from(src);
+            }
+        }
+
+        // ModR/M
+        let mut mod_rm = ModRM::new();
+        mod_rm.set_mode(dst.mode_bits());
+        mod_rm.set_reg(0);
+        mod_rm.set_rm(dst.rm_bits());
+        code.mod_rm = Some(mod_rm);
+
+        // SIB
+        code.sib = dst.sib_byte();
+
+        // addr disp
+        code.addr_disp = dst.disp_bytes();
+
+        Ok(code)
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -108,10 +430,176 @@ mod test {
                 Mov(Mem64::sib(Some(RBP), 42, RAX, 3), R13),
                 vec![0x4C, 0x89, 0x6C, 0xC5, 0x2A],
             ),
+            (
+                Mov(Mem64::reg_offset(RBP, -8), RAX),
+                vec![0x48, 0x89, 0x45, 0xF8],
+            ),
+            (
+                Mov(Mem64::reg_offset(RDI, 200), RAX),
+                vec![0x48, 0x89, 0x87, 0xC8, 0x00, 0x00, 0x00],
+            ),
+            (
+                Mov(Mem64::rip_offset(-7), RAX),
+                vec![0x48, 0x89, 0x05, 0xF9, 0xFF, 0xFF, 0xFF],
+            ),
         ];
 
         for (origin, expected) in cases {
             assert_eq!(origin.bytecode().to_bytes().bytes(), expected);
         }
     }
+
+    #[test]
+    fn test_mov_reg64_u64() {
+        use Reg64::*;
+
+        let cases = [
+            // mov r32, imm32
+            (Mov(RAX, 0), vec![0xB8, 0x00, 0x00, 0x00, 0x00]),
+            (Mov(RAX, 42), vec![0xB8, 0x2A, 0x00, 0x00, 0x00]),
+            (Mov(RDI, 0xFFFF_FFFF), vec![0xBF, 0xFF, 0xFF, 0xFF, 0xFF]),
+            (Mov(R15, 1), vec![0x41, 0xBF, 0x01, 0x00, 0x00, 0x00]),
+            // mov r/m64, imm32 (sign extended)
+            (
+                Mov(RAX, u64::MAX),
+                vec![0x48, 0xC7, 0xC0, 0xFF, 0xFF, 0xFF, 0xFF],
+            ),
+            (
+                Mov(R9, -0x8000_0000i64 as u64),
+                vec![0x49, 0xC7, 0xC1, 0x00, 0x00, 0x00, 0x80],
+            ),
+            // movabs r64, imm64
+            (
+                Mov(RAX, 0x1_0000_0000),
+                vec![0x48, 0xB8, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00],
+            ),
+            (
+                Mov(R10, -0x8000_0001i64 as u64),
+                vec![0x49, 0xBA, 0xFF, 0xFF, 0xFF, 0x7F, 0xFF, 0xFF, 0xFF, 0xFF],
+            ),
+            (
+                Mov(RCX, 0x1234_5678_9ABC_DEF0),
+                vec![0x48, 0xB9, 0xF0, 0xDE, 0xBC, 0x9A, 0x78, 0x56, 0x34, 0x12],
+            ),
+        ];
+
+        for (origin, expected) in cases {
+            assert_eq!(origin.bytecode().to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_mov_operand_size() {
+        use crate::{Reg16::*, Reg32::*, Reg8::*};
+
+        let cases = [
+            (Mov(SIL, DIL).bytecode(), vec![0x40, 0x88, 0xFE]),
+            (Mov(AL, R8B).bytecode(), vec![0x44, 0x88, 0xC0]),
+            (Mov(AX, BX).bytecode(), vec![0x66, 0x89, 0xD8]),
+            (Mov(R9D, EAX).bytecode(), vec![0x41, 0x89, 0xC1]),
+            (
+                Mov(EAX, Mem64::reg(Reg64::RDI)).bytecode(),
+                vec![0x8B, 0x07],
+            ),
+            (
+                Mov(R8B, Mem64::reg(Reg64::RAX)).bytecode(),
+                vec![0x44, 0x8A, 0x00],
+            ),
+            (
+                Mov(Mem64::reg_offset(Reg64::RBP, -2), CX).bytecode(),
+                vec![0x66, 0x89, 0x4D, 0xFE],
+            ),
+            (Mov(CL, 0xFF).bytecode(), vec![0xB1, 0xFF]),
+            (Mov(SPL, 1).bytecode(), vec![0x40, 0xB4, 0x01]),
+            (
+                Mov(R10W, 0x1234).bytecode(),
+                vec![0x66, 0x41, 0xBA, 0x34, 0x12],
+            ),
+            (
+                Mov(EDX, 0xFFFF_FFFF).bytecode(),
+                vec![0xBA, 0xFF, 0xFF, 0xFF, 0xFF],
+            ),
+            (
+                Mov(Ptr::byte(Mem64::reg(Reg64::RAX)), -1).bytecode(),
+                vec![0xC6, 0x00, 0xFF],
+            ),
+            (
+                Mov(Ptr::word(Mem64::reg(Reg64::RAX)), 0x1234).bytecode(),
+                vec![0x66, 0xC7, 0x00, 0x34, 0x12],
+            ),
+            (
+                Mov(Ptr::dword(Mem64::reg_offset(Reg64::RBP, -4)), 1).bytecode(),
+                vec![0xC7, 0x45, 0xFC, 0x01, 0x00, 0x00, 0x00],
+            ),
+        ];
+
+        for (bytecode, expected) in cases {
+            assert_eq!(bytecode.to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_mov_high_byte() {
+        use Reg8::*;
+
+        let cases = [
+            (Mov(AH, AL).try_bytecode(), vec![0x88, 0xC4]),
+            (Mov(BL, CH).try_bytecode(), vec![0x88, 0xEB]),
+            (
+                Mov(DH, Mem64::reg(Reg64::RDI)).try_bytecode(),
+                vec![0x8A, 0x37],
+            ),
+            (
+                Mov(Mem64::reg_offset(Reg64::RSP, 1), BH).try_bytecode(),
+                vec![0x88, 0x7C, 0x24, 0x01],
+            ),
+            (Mov(AH, 0xFF).try_bytecode(), vec![0xB4, 0xFF]),
+        ];
+
+        for (bytecode, expected) in cases {
+            assert_eq!(bytecode.unwrap().to_bytes().bytes(), expected);
+        }
+
+        // REX prefix が必要な命令では AH/CH/DH/BH を使えない
+        let errors = [
+            Mov(AH, SIL).try_bytecode(),
+            Mov(R8B, CH).try_bytecode(),
+            Mov(DH, Mem64::reg(Reg64::R8)).try_bytecode(),
+            Mov(Mem64::sib(None, 0, Reg64::R9, 0), BH).try_bytecode(),
+            Mov(Reg::from(AH), Reg::from(R9B)).try_bytecode(),
+        ];
+
+        for result in errors {
+            assert_eq!(result, Err(EncodeError::HighByteWithRex));
+        }
+    }
+
+    #[test]
+    fn test_try_bytecode_error() {
+        use Reg64::*;
+
+        let bad_sib = Mem64::Sib {
+            base: Some(RAX),
+            disp: 0,
+            index: RSP,
+            scale: 1,
+        };
+
+        assert_eq!(
+            Mov(bad_sib, RAX).try_bytecode(),
+            Err(EncodeError::RspAsIndex)
+        );
+        assert_eq!(
+            Mov(Reg::from(RAX), Reg::from(Reg32::EAX)).try_bytecode(),
+            Err(EncodeError::OperandSizeMismatch(Size::Qword, Size::Dword))
+        );
+        assert_eq!(
+            Mov(Reg::from(Reg8::AL), 0x100).try_bytecode(),
+            Err(EncodeError::ImmOutOfRange(Size::Byte, 0x100))
+        );
+        assert_eq!(
+            Mov(Ptr::word(Mem64::reg(RAX)), 0x10000).try_bytecode(),
+            Err(EncodeError::ImmOutOfRange(Size::Word, 0x10000))
+        );
+    }
 }
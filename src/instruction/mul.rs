@@ -0,0 +1,541 @@
+use super::alu::{imm_bytes, sized_imm};
+use crate::{
+    encode::{check_high_byte, check_same_size},
+    ByteCode, BytesAtMost, EncodeError, Mem64, ModRM, Ptr, Register, Rex, Size,
+};
+use std::convert::TryFrom;
+use std::fmt::{Display, Error as FmtError, Formatter};
+
+/// F7 /4 ~ /7 の乗除算
+///
+/// 値は ModR/M の reg フィールド (/n)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MulOp {
+    Mul = 4,
+    Imul = 5,
+    Div = 6,
+    Idiv = 7,
+}
+
+impl MulOp {
+    pub const ALL: [MulOp; 4] = {
+        use MulOp::*;
+        [Mul, Imul, Div, Idiv]
+    };
+
+    pub fn code(&self) -> u8 {
+        *self as u8
+    }
+
+    /// /0 ~ /3 (TEST, NOT, NEG) は None
+    pub fn from_code(code: u8) -> Option<Self> {
+        MulOp::ALL.iter().copied().find(|op| op.code() == code)
+    }
+}
+
+impl Display for MulOp {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            MulOp::Mul => write!(f, "mul"),
+            MulOp::Imul => write!(f, "imul"),
+            MulOp::Div => write!(f, "div"),
+            MulOp::Idiv => write!(f, "idiv"),
+        }
+    }
+}
+
+/// `op src`
+///
+/// 乗算は AL/AX/EAX/RAX と src の積を AX, DX:AX, EDX:EAX, RDX:RAX に、
+/// 除算は AX, DX:AX, ... を src で割った商と余りを AL:AH, AX:DX, ... に置く
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MulDiv<Src>(pub MulOp, pub Src);
+
+impl<Src> MulDiv<Src> {
+    pub fn mul(src: Src) -> Self {
+        MulDiv(MulOp::Mul, src)
+    }
+
+    pub fn imul(src: Src) -> Self {
+        MulDiv(MulOp::Imul, src)
+    }
+
+    pub fn div(src: Src) -> Self {
+        MulDiv(MulOp::Div, src)
+    }
+
+    pub fn idiv(src: Src) -> Self {
+        MulDiv(MulOp::Idiv, src)
+    }
+}
+
+impl<R: Register> MulDiv<R> {
+    /// F7 /n, 8bit は F6 /n
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (op, src) = (self.0, self.1);
+
+        let mut code = ByteCode::new();
+
+        // operand-size prefix
+        code.prefix = src.size().prefix();
+
+        // REX prefix
+        let mut rex = Rex::new();
+        rex.set_w(src.size().rex_w());
+        rex.set_b(src.rex_b_bit());
+        code.set_rex(rex, src.requires_rex());
+        check_high_byte(&code, &[src])?;
+
+        // opcode
+        code.opcode = group3_opcode(src.size());
+
+        // ModR/M
+        let mut mod_rm = ModRM::new();
+        mod_rm.set_mode(src.mode_bits());
+        mod_rm.set_reg(op.code());
+        mod_rm.set_rm(src.rm_bits());
+        code.mod_rm = Some(mod_rm);
+
+        Ok(code)
+    }
+}
+
+impl MulDiv<Ptr> {
+    /// F7 /n, 8bit は F6 /n
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (op, Ptr(size, src)) = (self.0, self.1);
+        src.validate()?;
+
+        let mut code = ByteCode::new();
+
+        // operand-size prefix
+        code.prefix = size.prefix();
+
+        // REX prefix
+        let mut rex = Rex::new();
+        rex.set_w(size.rex_w());
+        rex.set_x(src.rex_x_bit());
+        rex.set_b(src.rex_b_bit());
+        code.set_rex(rex, false);
+
+        // opcode
+        code.opcode = group3_opcode(size);
+
+        // ModR/M
+        let mut mod_rm = ModRM::new();
+        mod_rm.set_mode(src.mode_bits());
+        mod_rm.set_reg(op.code());
+        mod_rm.set_rm(src.rm_bits());
+        code.mod_rm = Some(mod_rm);
+
+        // SIB
+        code.sib = src.sib_byte();
+
+        // addr disp
+        code.addr_disp = src.disp_bytes();
+
+        Ok(code)
+    }
+}
+
+impl MulDiv<Mem64> {
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        MulDiv(self.0, Ptr::qword(self.1)).try_bytecode()
+    }
+}
+
+fn group3_opcode(size: Size) -> BytesAtMost<3> {
+    match size {
+        Size::Byte => BytesAtMost::from([0xF6]),
+        _ => BytesAtMost::from([0xF7]),
+    }
+}
+
+/// `imul dst, src` : dst に dst * src の下位を置く。16/32/64bit のみ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Imul<Dst, Src>(pub Dst, pub Src);
+
+/// 8bit レジスタは `bytecode` では panic、`try_bytecode` では `Err` になる
+impl<R: Register> Imul<R, R> {
+    /// 0F AF /r
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (dst, src) = (self.0, self.1);
+        check_same_size(dst, src)?;
+        let mut code = bytecode_reg_reg(dst, src)?;
+        code.opcode = BytesAtMost::from([0x0F, 0xAF]);
+        Ok(code)
+    }
+}
+
+impl<R: Register> Imul<R, Mem64> {
+    /// 0F AF /r
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let mut code = bytecode_reg_mem(self.0, self.1)?;
+        code.opcode = BytesAtMost::from([0x0F, 0xAF]);
+        Ok(code)
+    }
+}
+
+/// `imul dst, src, imm` : dst に src * imm の下位を置く。16/32/64bit のみ
+///
+/// 即値は64bitに符号拡張される。imm8 に収まる場合は `6B /r ib` を使う
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImulImm<Dst, Src>(pub Dst, pub Src, pub i32);
+
+/// 8bit レジスタやサイズに収まらない即値は `bytecode` では panic、`try_bytecode` では `Err` になる
+impl<R: Register> ImulImm<R, R> {
+    /// 6B /r ib, 69 /r iw/id
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (dst, src) = (self.0, self.1);
+        check_same_size(dst, src)?;
+        let mut code = bytecode_reg_reg(dst, src)?;
+        set_imul_imm(&mut code, dst.size(), self.2)?;
+        Ok(code)
+    }
+}
+
+impl<R: Register> ImulImm<R, Mem64> {
+    /// 6B /r ib, 69 /r iw/id
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let mut code = bytecode_reg_mem(self.0, self.1)?;
+        set_imul_imm(&mut code, self.0.size(), self.2)?;
+        Ok(code)
+    }
+}
+
+/// opcode 以外 (ModR/M の reg が dst、rm が src)
+fn bytecode_reg_reg<R: Register>(dst: R, src: R) -> Result<ByteCode, EncodeError> {
+    if dst.size() == Size::Byte {
+        return Err(EncodeError::InvalidOperandSize(Size::Byte));
+    }
+
+    let mut code = ByteCode::new();
+
+    // operand-size prefix
+    code.prefix = dst.size().prefix();
+
+    // REX prefix
+    let mut rex = Rex::new();
+    rex.set_w(dst.size().rex_w());
+    rex.set_r(dst.rex_r_bit());
+    rex.set_b(src.rex_b_bit());
+    code.set_rex(rex, false);
+
+    // ModR/M
+    let mut mod_rm = ModRM::new();
+    mod_rm.set_mode(src.mode_bits());
+    mod_rm.set_reg(dst.reg_bits());
+    mod_rm.set_rm(src.rm_bits());
+    code.mod_rm = Some(mod_rm);
+
+    Ok(code)
+}
+
+/// opcode 以外
+fn bytecode_reg_mem<R: Register>(dst: R, src: Mem64) -> Result<ByteCode, EncodeError> {
+    src.validate()?;
+    if dst.size() == Size::Byte {
+        return Err(EncodeError::InvalidOperandSize(Size::Byte));
+    }
+
+    let mut code = ByteCode::new();
+
+    // operand-size prefix
+    code.prefix = dst.size().prefix();
+
+    // REX prefix
+    let mut rex = Rex::new();
+    rex.set_w(dst.size().rex_w());
+    rex.set_r(dst.rex_r_bit());
+    rex.set_x(src.rex_x_bit());
+    rex.set_b(src.rex_b_bit());
+    code.set_rex(rex, false);
+
+    // ModR/M
+    let mut mod_rm = ModRM::new();
+    mod_rm.set_mode(src.mode_bits());
+    mod_rm.set_reg(dst.reg_bits());
+    mod_rm.set_rm(src.rm_bits());
+    code.mod_rm = Some(mod_rm);
+
+    // SIB
+    code.sib = src.sib_byte();
+
+    // addr disp
+    code.addr_disp = src.disp_bytes();
+
+    Ok(code)
+}
+
+/// 6B /r ib か 69 /r iw/id の opcode と即値を設定する
+fn set_imul_imm(code: &mut ByteCode, size: Size, imm: i32) -> Result<(), EncodeError> {
+    let imm = sized_imm(size, imm as i64)?;
+
+    match i8::try_from(imm) {
+        Ok(imm8) => {
+            code.opcode = BytesAtMost::from([0x6B]);
+            code.imm = BytesAtMost::from(imm8);
+        }
+        Err(_) => {
+            code.opcode = BytesAtMost::from([0x69]);
+            code.imm = imm_bytes(size, imm);
+        }
+    }
+
+    Ok(())
+}
+
+/// `cwd` : AX を符号拡張して DX:AX にする
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cwd();
+
+impl Cwd {
+    /// 66 99
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        code.prefix = Size::Word.prefix();
+        code.opcode = BytesAtMost::from([0x99]);
+
+        code
+    }
+}
+
+/// `cdq` : EAX を符号拡張して EDX:EAX にする
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cdq();
+
+impl Cdq {
+    /// 99
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        code.opcode = BytesAtMost::from([0x99]);
+
+        code
+    }
+}
+
+/// `cqo` : RAX を符号拡張して RDX:RAX にする
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cqo();
+
+impl Cqo {
+    /// REX.W 99
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        let mut rex = Rex::new();
+        rex.set_w(true);
+        code.set_rex(rex, false);
+        code.opcode = BytesAtMost::from([0x99]);
+
+        code
+    }
+}
+
+/// `cdqe` : EAX を RAX に符号拡張する
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cdqe();
+
+impl Cdqe {
+    /// REX.W 98
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        let mut rex = Rex::new();
+        rex.set_w(true);
+        code.set_rex(rex, false);
+        code.opcode = BytesAtMost::from([0x98]);
+
+        code
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Reg, Reg16, Reg32, Reg64, Reg8};
+
+    #[test]
+    fn test_mul_div() {
+        use Reg64::*;
+
+        let cases = [
+            (MulDiv::mul(RCX).bytecode(), vec![0x48, 0xF7, 0xE1]),
+            (MulDiv::imul(R12).bytecode(), vec![0x49, 0xF7, 0xEC]),
+            (MulDiv::div(Reg32::EBX).bytecode(), vec![0xF7, 0xF3]),
+            (
+                MulDiv::idiv(Reg16::R9W).bytecode(),
+                vec![0x66, 0x41, 0xF7, 0xF9],
+            ),
+            (MulDiv::mul(Reg8::BL).bytecode(), vec![0xF6, 0xE3]),
+            (MulDiv::imul(Reg8::AH).bytecode(), vec![0xF6, 0xEC]),
+            (MulDiv::div(Reg8::SIL).bytecode(), vec![0x40, 0xF6, 0xF6]),
+            (
+                MulDiv::mul(Mem64::reg(RDI)).bytecode(),
+                vec![0x48, 0xF7, 0x27],
+            ),
+            (
+                MulDiv::idiv(Ptr::dword(Mem64::reg_offset(RBP, -4))).bytecode(),
+                vec![0xF7, 0x7D, 0xFC],
+            ),
+            (
+                MulDiv::div(Ptr::byte(Mem64::sib(Some(RAX), 0, RCX, 1))).bytecode(),
+                vec![0xF6, 0x34, 0x48],
+            ),
+            (
+                MulDiv::imul(Ptr::word(Mem64::rip_offset(8))).bytecode(),
+                vec![0x66, 0xF7, 0x2D, 0x08, 0x00, 0x00, 0x00],
+            ),
+        ];
+
+        for (bytecode, expected) in cases {
+            assert_eq!(bytecode.to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_imul() {
+        use Reg64::*;
+
+        let cases = [
+            (Imul(RAX, RCX).bytecode(), vec![0x48, 0x0F, 0xAF, 0xC1]),
+            (
+                Imul(Reg32::R10D, Mem64::reg_offset(RSP, 8)).bytecode(),
+                vec![0x44, 0x0F, 0xAF, 0x54, 0x24, 0x08],
+            ),
+            (
+                Imul(Reg16::DX, Reg16::R15W).bytecode(),
+                vec![0x66, 0x41, 0x0F, 0xAF, 0xD7],
+            ),
+            (
+                ImulImm(RAX, RCX, 16).bytecode(),
+                vec![0x48, 0x6B, 0xC1, 0x10],
+            ),
+            (
+                ImulImm(RAX, Mem64::reg(RDI), 0x1000).bytecode(),
+                vec![0x48, 0x69, 0x07, 0x00, 0x10, 0x00, 0x00],
+            ),
+            (
+                ImulImm(Reg16::R8W, Reg16::SI, -2).bytecode(),
+                vec![0x66, 0x44, 0x6B, 0xC6, 0xFE],
+            ),
+            (
+                ImulImm(Reg32::ECX, Reg32::ECX, 0x12345).bytecode(),
+                vec![0x69, 0xC9, 0x45, 0x23, 0x01, 0x00],
+            ),
+            (
+                ImulImm(Reg16::SI, Mem64::reg(RAX), 0x1234).bytecode(),
+                vec![0x66, 0x69, 0x30, 0x34, 0x12],
+            ),
+            // 16bit の 0xFFFF は -1 として imm8 になる
+            (
+                ImulImm(Reg16::AX, Reg16::AX, 0xFFFF).bytecode(),
+                vec![0x66, 0x6B, 0xC0, 0xFF],
+            ),
+        ];
+
+        for (bytecode, expected) in cases {
+            assert_eq!(bytecode.to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_sign_extend() {
+        assert_eq!(Cwd().bytecode().to_bytes().bytes(), [0x66, 0x99]);
+        assert_eq!(Cdq().bytecode().to_bytes().bytes(), [0x99]);
+        assert_eq!(Cqo().bytecode().to_bytes().bytes(), [0x48, 0x99]);
+        assert_eq!(Cdqe().bytecode().to_bytes().bytes(), [0x48, 0x98]);
+    }
+
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[test]
+    fn test_run() {
+        use crate::{
+            asm::Assembler,
+            instruction::{Alu, Mov, Ret},
+            jit::JitMemory,
+            Reg64::*,
+        };
+
+        // (a * 3 + b) / 7 を符号付きで計算し、余りは rdx に残る
+        let mut asm = Assembler::new();
+        asm.emit(ImulImm(RAX, RDI, 3));
+        asm.emit(Alu::add(RAX, RSI));
+        asm.emit(Mov(RCX, 7u64));
+        asm.emit(Cqo());
+        asm.emit(MulDiv::idiv(RCX));
+        asm.emit(Imul(RAX, RAX));
+        asm.emit(Ret());
+
+        let mem = JitMemory::new(&asm.finish().unwrap()).unwrap();
+        let f: extern "sysv64" fn(i64, i64) -> i64 = unsafe { mem.get() };
+
+        let cases = [(5, 6, 9), (-5, -6, 9), (100, 0, 1764), (-3, 2, 1)];
+        for (a, b, expected) in cases.iter() {
+            assert_eq!(f(*a, *b), *expected);
+        }
+    }
+
+    #[test]
+    fn test_try_bytecode_error() {
+        use Reg8::*;
+
+        let bad_scale = Mem64::Sib {
+            base: None,
+            disp: 0,
+            index: Reg64::RAX,
+            scale: 4,
+        };
+
+        assert_eq!(
+            MulDiv::div(bad_scale).try_bytecode(),
+            Err(EncodeError::InvalidScale(4))
+        );
+        assert_eq!(
+            Imul(AL, CL).try_bytecode(),
+            Err(EncodeError::InvalidOperandSize(Size::Byte))
+        );
+        assert_eq!(
+            ImulImm(BL, Mem64::reg(Reg64::RAX), 1).try_bytecode(),
+            Err(EncodeError::InvalidOperandSize(Size::Byte))
+        );
+        assert_eq!(
+            Imul(Reg::from(Reg64::RAX), Reg::from(Reg32::EAX)).try_bytecode(),
+            Err(EncodeError::OperandSizeMismatch(Size::Qword, Size::Dword))
+        );
+        assert_eq!(
+            ImulImm(Reg16::AX, Reg16::CX, 0x10000).try_bytecode(),
+            Err(EncodeError::ImmOutOfRange(Size::Word, 0x10000))
+        );
+    }
+}
@@ -0,0 +1,336 @@
+use crate::{
+    ByteCode, BytesAtMost, EncodeError, Mem64, ModRM, Ptr, Reg16, Reg64, Register, Rex, Size,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Push<Src>(pub Src);
+
+impl Push<Reg64> {
+    /// 50+rd
+    pub fn bytecode(&self) -> ByteCode {
+        let src = self.0;
+
+        let mut code = ByteCode::new();
+
+        // REX prefix
+        if src.rex_b_bit() {
+            let mut rex = Rex::new();
+            rex.set_b(true);
+            code.rex = Some(rex);
+        }
+
+        // opcode
+        code.opcode = BytesAtMost::from([0x50 + src.reg_bits()]);
+
+        code
+    }
+}
+
+impl Push<Reg16> {
+    /// 66 50+rw
+    pub fn bytecode(&self) -> ByteCode {
+        let src = self.0;
+
+        let mut code = ByteCode::new();
+
+        // operand-size prefix
+        code.prefix = Size::Word.prefix();
+
+        // REX prefix
+        if src.rex_b_bit() {
+            let mut rex = Rex::new();
+            rex.set_b(true);
+            code.rex = Some(rex);
+        }
+
+        // opcode
+        code.opcode = BytesAtMost::from([0x50 + src.reg_bits()]);
+
+        code
+    }
+}
+
+impl Push<Mem64> {
+    /// FF /6
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        Push(Ptr::qword(self.0)).try_bytecode()
+    }
+}
+
+impl Push<Ptr> {
+    /// FF /6, 16bit は 66 FF /6
+    ///
+    /// 64bit モードでは 32bit の push はエンコードできない
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let Ptr(size, src) = self.0;
+        stack_operand(&src, size, 0xFF, 6)
+    }
+}
+
+impl Push<i8> {
+    /// 6A ib (64bit に符号拡張される)
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        // opcode
+        code.opcode = BytesAtMost::from([0x6A]);
+
+        // immutable val
+        code.imm = BytesAtMost::from(self.0);
+
+        code
+    }
+}
+
+impl Push<i32> {
+    /// 68 id (64bit に符号拡張される)
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        // opcode
+        code.opcode = BytesAtMost::from([0x68]);
+
+        // immutable val
+        code.imm = BytesAtMost::from(self.0);
+
+        code
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Pop<Dst>(pub Dst);
+
+impl Pop<Reg64> {
+    /// 58+rd
+    pub fn bytecode(&self) -> ByteCode {
+        let dst = self.0;
+
+        let mut code = ByteCode::new();
+
+        // REX prefix
+        if dst.rex_b_bit() {
+            let mut rex = Rex::new();
+            rex.set_b(true);
+            code.rex = Some(rex);
+        }
+
+        // opcode
+        code.opcode = BytesAtMost::from([0x58 + dst.reg_bits()]);
+
+        code
+    }
+}
+
+impl Pop<Reg16> {
+    /// 66 58+rw
+    pub fn bytecode(&self) -> ByteCode {
+        let dst = self.0;
+
+        let mut code = ByteCode::new();
+
+        // operand-size prefix
+        code.prefix = Size::Word.prefix();
+
+        // REX prefix
+        if dst.rex_b_bit() {
+            let mut rex = Rex::new();
+            rex.set_b(true);
+            code.rex = Some(rex);
+        }
+
+        // opcode
+        code.opcode = BytesAtMost::from([0x58 + dst.reg_bits()]);
+
+        code
+    }
+}
+
+impl Pop<Mem64> {
+    /// 8F /0
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        Pop(Ptr::qword(self.0)).try_bytecode()
+    }
+}
+
+impl Pop<Ptr> {
+    /// 8F /0, 16bit は 66 8F /0
+    ///
+    /// 64bit モードでは 32bit の pop はエンコードできない
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let Ptr(size, dst) = self.0;
+        stack_operand(&dst, size, 0x8F, 0)
+    }
+}
+
+/// push (FF /6) と pop (8F /0) のメモリオペランド版
+///
+/// 既定のオペランドサイズが 64bit なので REX.W は付けない
+fn stack_operand(mem: &Mem64, size: Size, opcode: u8, reg: u8) -> Result<ByteCode, EncodeError> {
+    if size != Size::Word && size != Size::Qword {
+        return Err(EncodeError::InvalidOperandSize(size));
+    }
+    mem.validate()?;
+
+    let mut code = ByteCode::new();
+
+    // operand-size prefix
+    code.prefix = size.prefix();
+
+    // REX prefix
+    if mem.rex_x_bit() || mem.rex_b_bit() {
+        let mut rex = Rex::new();
+        rex.set_x(mem.rex_x_bit());
+        rex.set_b(mem.rex_b_bit());
+        code.rex = Some(rex);
+    }
+
+    // opcode
+    code.opcode = BytesAtMost::from([opcode]);
+
+    // ModR/M
+    let mut mod_rm = ModRM::new();
+    mod_rm.set_mode(mem.mode_bits());
+    mod_rm.set_reg(reg);
+    mod_rm.set_rm(mem.rm_bits());
+    code.mod_rm = Some(mod_rm);
+
+    // SIB
+    code.sib = mem.sib_byte();
+
+    // addr disp
+    code.addr_disp = mem.disp_bytes();
+
+    Ok(code)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_push() {
+        use Reg64::*;
+
+        let cases = [
+            (Push(RAX).bytecode(), vec![0x50]),
+            (Push(RBP).bytecode(), vec![0x55]),
+            (Push(RDI).bytecode(), vec![0x57]),
+            (Push(R8).bytecode(), vec![0x41, 0x50]),
+            (Push(R12).bytecode(), vec![0x41, 0x54]),
+            (Push(R13).bytecode(), vec![0x41, 0x55]),
+            (Push(R15).bytecode(), vec![0x41, 0x57]),
+            (Push(Mem64::reg(RAX)).bytecode(), vec![0xFF, 0x30]),
+            (
+                Push(Mem64::reg(R12)).bytecode(),
+                vec![0x41, 0xFF, 0x34, 0x24],
+            ),
+            (
+                Push(Mem64::reg(R13)).bytecode(),
+                vec![0x41, 0xFF, 0x75, 0x00],
+            ),
+            (
+                Push(Mem64::reg_offset(R12, -8)).bytecode(),
+                vec![0x41, 0xFF, 0x74, 0x24, 0xF8],
+            ),
+            (
+                Push(Mem64::sib(Some(R13), 0, R12, 3)).bytecode(),
+                vec![0x43, 0xFF, 0x74, 0xE5, 0x00],
+            ),
+            (
+                Push(Mem64::rip_offset(16)).bytecode(),
+                vec![0xFF, 0x35, 0x10, 0x00, 0x00, 0x00],
+            ),
+            (Push(-1i8).bytecode(), vec![0x6A, 0xFF]),
+            (Push(0x7Fi8).bytecode(), vec![0x6A, 0x7F]),
+            (
+                Push(0x1234_5678i32).bytecode(),
+                vec![0x68, 0x78, 0x56, 0x34, 0x12],
+            ),
+            (Push(Reg16::AX).bytecode(), vec![0x66, 0x50]),
+            (Push(Reg16::R9W).bytecode(), vec![0x66, 0x41, 0x51]),
+            (
+                Push(Ptr::word(Mem64::reg(RAX))).bytecode(),
+                vec![0x66, 0xFF, 0x30],
+            ),
+            (
+                Push(Ptr::word(Mem64::reg(R12))).bytecode(),
+                vec![0x66, 0x41, 0xFF, 0x34, 0x24],
+            ),
+            (
+                Push(Ptr::qword(Mem64::reg(RAX))).bytecode(),
+                vec![0xFF, 0x30],
+            ),
+        ];
+
+        for (code, expected) in cases {
+            assert_eq!(code.to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_pop() {
+        use Reg64::*;
+
+        let cases = [
+            (Pop(RAX).bytecode(), vec![0x58]),
+            (Pop(RBP).bytecode(), vec![0x5D]),
+            (Pop(R12).bytecode(), vec![0x41, 0x5C]),
+            (Pop(R13).bytecode(), vec![0x41, 0x5D]),
+            (Pop(R15).bytecode(), vec![0x41, 0x5F]),
+            (Pop(Mem64::reg(RAX)).bytecode(), vec![0x8F, 0x00]),
+            (
+                Pop(Mem64::reg(R12)).bytecode(),
+                vec![0x41, 0x8F, 0x04, 0x24],
+            ),
+            (
+                Pop(Mem64::reg(R13)).bytecode(),
+                vec![0x41, 0x8F, 0x45, 0x00],
+            ),
+            (
+                Pop(Mem64::reg_offset(R13, 200)).bytecode(),
+                vec![0x41, 0x8F, 0x85, 0xC8, 0x00, 0x00, 0x00],
+            ),
+            (
+                Pop(Mem64::sib(Some(RSP), 8, R12, 2)).bytecode(),
+                vec![0x42, 0x8F, 0x44, 0xA4, 0x08],
+            ),
+            (Pop(Reg16::R15W).bytecode(), vec![0x66, 0x41, 0x5F]),
+            (
+                Pop(Ptr::word(Mem64::reg_offset(RBP, -2))).bytecode(),
+                vec![0x66, 0x8F, 0x45, 0xFE],
+            ),
+        ];
+
+        for (code, expected) in cases {
+            assert_eq!(code.to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_invalid_size() {
+        let mem = Mem64::reg(Reg64::RAX);
+
+        // 64bit モードの push/pop は 16bit か 64bit
+        for size in [Size::Byte, Size::Dword] {
+            let err = Err(EncodeError::InvalidOperandSize(size));
+            assert_eq!(Push(Ptr(size, mem)).try_bytecode(), err);
+            assert_eq!(Pop(Ptr(size, mem)).try_bytecode(), err);
+        }
+    }
+}
@@ -0,0 +1,49 @@
+use crate::{ByteCode, BytesAtMost};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ret();
+
+impl Ret {
+    /// C3
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        code.opcode = BytesAtMost::from([0xC3]);
+
+        code
+    }
+}
+
+/// `ret imm16` : 戻った後にスタックから imm16 バイトを取り除く
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetImm(pub u16);
+
+impl RetImm {
+    /// C2 iw
+    pub fn bytecode(&self) -> ByteCode {
+        let mut code = ByteCode::new();
+
+        // opcode
+        code.opcode = BytesAtMost::from([0xC2]);
+
+        // immutable val
+        code.imm = BytesAtMost::from(self.0.to_le_bytes());
+
+        code
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test() {
+        assert_eq!(Ret().bytecode().to_bytes().bytes(), [0xC3]);
+        assert_eq!(RetImm(16).bytecode().to_bytes().bytes(), [0xC2, 0x10, 0x00]);
+        assert_eq!(
+            RetImm(0x1234).bytecode().to_bytes().bytes(),
+            [0xC2, 0x34, 0x12]
+        );
+    }
+}
@@ -0,0 +1,568 @@
+use crate::{
+    encode::{check_high_byte, check_same_size},
+    ByteCode, BytesAtMost, EncodeError, Mem64, ModRM, Ptr, Register, Rex, Size,
+};
+use std::fmt::{Display, Error as FmtError, Formatter};
+
+/// 7種類のシフト・ローテート
+///
+/// 値は ModR/M の reg フィールド (/n)。/6 は SHL の別名で、使わない
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ShiftOp {
+    Rol = 0,
+    Ror = 1,
+    Rcl = 2,
+    Rcr = 3,
+    Shl = 4,
+    Shr = 5,
+    Sar = 7,
+}
+
+impl ShiftOp {
+    pub const ALL: [ShiftOp; 7] = {
+        use ShiftOp::*;
+        [Rol, Ror, Rcl, Rcr, Shl, Shr, Sar]
+    };
+
+    pub fn code(&self) -> u8 {
+        *self as u8
+    }
+
+    pub fn from_code(code: u8) -> Option<Self> {
+        ShiftOp::ALL.iter().copied().find(|op| op.code() == code)
+    }
+}
+
+impl Display for ShiftOp {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            ShiftOp::Rol => write!(f, "rol"),
+            ShiftOp::Ror => write!(f, "ror"),
+            ShiftOp::Rcl => write!(f, "rcl"),
+            ShiftOp::Rcr => write!(f, "rcr"),
+            ShiftOp::Shl => write!(f, "shl"),
+            ShiftOp::Shr => write!(f, "shr"),
+            ShiftOp::Sar => write!(f, "sar"),
+        }
+    }
+}
+
+/// The `cl` register as a shift count.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cl;
+
+impl Display for Cl {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        write!(f, "cl")
+    }
+}
+
+/// `op dst, count`
+///
+/// count は `u8` の即値か [`Cl`]。即値の 1 は `D1 /n` の短い形式になる
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shift<Dst, Count>(pub ShiftOp, pub Dst, pub Count);
+
+impl<Dst, Count> Shift<Dst, Count> {
+    pub fn rol(dst: Dst, count: Count) -> Self {
+        Shift(ShiftOp::Rol, dst, count)
+    }
+
+    pub fn ror(dst: Dst, count: Count) -> Self {
+        Shift(ShiftOp::Ror, dst, count)
+    }
+
+    pub fn rcl(dst: Dst, count: Count) -> Self {
+        Shift(ShiftOp::Rcl, dst, count)
+    }
+
+    pub fn rcr(dst: Dst, count: Count) -> Self {
+        Shift(ShiftOp::Rcr, dst, count)
+    }
+
+    pub fn shl(dst: Dst, count: Count) -> Self {
+        Shift(ShiftOp::Shl, dst, count)
+    }
+
+    pub fn shr(dst: Dst, count: Count) -> Self {
+        Shift(ShiftOp::Shr, dst, count)
+    }
+
+    pub fn sar(dst: Dst, count: Count) -> Self {
+        Shift(ShiftOp::Sar, dst, count)
+    }
+}
+
+impl<R: Register> Shift<R, u8> {
+    /// D1 /n, C1 /n ib, 8bit は D0 /n, C0 /n ib
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        bytecode_reg(self.0, self.1, Some(self.2))
+    }
+}
+
+impl<R: Register> Shift<R, Cl> {
+    /// D3 /n, 8bit は D2 /n
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        bytecode_reg(self.0, self.1, None)
+    }
+}
+
+impl Shift<Ptr, u8> {
+    /// D1 /n, C1 /n ib, 8bit は D0 /n, C0 /n ib
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        bytecode_mem(self.0, self.1, Some(self.2))
+    }
+}
+
+impl Shift<Ptr, Cl> {
+    /// D3 /n, 8bit は D2 /n
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        bytecode_mem(self.0, self.1, None)
+    }
+}
+
+impl Shift<Mem64, u8> {
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        Shift(self.0, Ptr::qword(self.1), self.2).try_bytecode()
+    }
+}
+
+impl Shift<Mem64, Cl> {
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        Shift(self.0, Ptr::qword(self.1), Cl).try_bytecode()
+    }
+}
+
+/// count が None なら CL
+fn bytecode_reg<R: Register>(
+    op: ShiftOp,
+    dst: R,
+    count: Option<u8>,
+) -> Result<ByteCode, EncodeError> {
+    let mut code = ByteCode::new();
+
+    // operand-size prefix
+    code.prefix = dst.size().prefix();
+
+    // REX prefix
+    let mut rex = Rex::new();
+    rex.set_w(dst.size().rex_w());
+    rex.set_b(dst.rex_b_bit());
+    code.set_rex(rex, dst.requires_rex());
+    check_high_byte(&code, &[dst])?;
+
+    // ModR/M
+    let mut mod_rm = ModRM::new();
+    mod_rm.set_mode(dst.mode_bits());
+    mod_rm.set_reg(op.code());
+    mod_rm.set_rm(dst.rm_bits());
+    code.mod_rm = Some(mod_rm);
+
+    // opcode, immutable val
+    set_count(&mut code, dst.size(), count);
+
+    Ok(code)
+}
+
+/// count が None なら CL
+fn bytecode_mem(op: ShiftOp, dst: Ptr, count: Option<u8>) -> Result<ByteCode, EncodeError> {
+    let Ptr(size, dst) = dst;
+    dst.validate()?;
+
+    let mut code = ByteCode::new();
+
+    // operand-size prefix
+    code.prefix = size.prefix();
+
+    // REX prefix
+    let mut rex = Rex::new();
+    rex.set_w(size.rex_w());
+    rex.set_x(dst.rex_x_bit());
+    rex.set_b(dst.rex_b_bit());
+    code.set_rex(rex, false);
+
+    // ModR/M
+    let mut mod_rm = ModRM::new();
+    mod_rm.set_mode(dst.mode_bits());
+    mod_rm.set_reg(op.code());
+    mod_rm.set_rm(dst.rm_bits());
+    code.mod_rm = Some(mod_rm);
+
+    // SIB
+    code.sib = dst.sib_byte();
+
+    // addr disp
+    code.addr_disp = dst.disp_bytes();
+
+    // opcode, immutable val
+    set_count(&mut code, size, count);
+
+    Ok(code)
+}
+
+/// - 1 : D0 /n, D1 /n
+/// - CL : D2 /n, D3 /n
+/// - それ以外 : C0 /n ib, C1 /n ib
+fn set_count(code: &mut ByteCode, size: Size, count: Option<u8>) {
+    let opcode = match count {
+        Some(1) => 0xD0,
+        None => 0xD2,
+        Some(imm) => {
+            code.imm = BytesAtMost::from(imm);
+            0xC0
+        }
+    };
+
+    match size {
+        Size::Byte => code.opcode = BytesAtMost::from([opcode]),
+        _ => code.opcode = BytesAtMost::from([opcode + 1]),
+    }
+}
+
+/// SHLD / SHRD
+///
+/// 値は count が imm8 の形式の opcode の2バイト目 (CL は +1)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DoubleShiftOp {
+    Shld = 0xA4,
+    Shrd = 0xAC,
+}
+
+impl DoubleShiftOp {
+    pub fn code(&self) -> u8 {
+        *self as u8
+    }
+}
+
+impl Display for DoubleShiftOp {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            DoubleShiftOp::Shld => write!(f, "shld"),
+            DoubleShiftOp::Shrd => write!(f, "shrd"),
+        }
+    }
+}
+
+/// `op dst, src, count`
+///
+/// dst を count ビットシフトし、空いたビットを src から埋める。16/32/64bit のみ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DoubleShift<Dst, Src, Count>(pub DoubleShiftOp, pub Dst, pub Src, pub Count);
+
+impl<Dst, Src, Count> DoubleShift<Dst, Src, Count> {
+    pub fn shld(dst: Dst, src: Src, count: Count) -> Self {
+        DoubleShift(DoubleShiftOp::Shld, dst, src, count)
+    }
+
+    pub fn shrd(dst: Dst, src: Src, count: Count) -> Self {
+        DoubleShift(DoubleShiftOp::Shrd, dst, src, count)
+    }
+}
+
+/// 8bit レジスタは `bytecode` では panic、`try_bytecode` では `Err` になる
+impl<R: Register> DoubleShift<R, R, u8> {
+    /// 0F A4 /r ib, 0F AC /r ib
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (op, dst, src, count) = (self.0, self.1, self.2, self.3);
+        check_same_size(dst, src)?;
+        let mut code = bytecode_double_reg(op, dst, src, false)?;
+        code.imm = BytesAtMost::from(count);
+        Ok(code)
+    }
+}
+
+impl<R: Register> DoubleShift<R, R, Cl> {
+    /// 0F A5 /r, 0F AD /r
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (op, dst, src) = (self.0, self.1, self.2);
+        check_same_size(dst, src)?;
+        bytecode_double_reg(op, dst, src, true)
+    }
+}
+
+impl<R: Register> DoubleShift<Mem64, R, u8> {
+    /// 0F A4 /r ib, 0F AC /r ib
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        let (op, dst, src, count) = (self.0, self.1, self.2, self.3);
+        let mut code = bytecode_double_mem(op, dst, src, false)?;
+        code.imm = BytesAtMost::from(count);
+        Ok(code)
+    }
+}
+
+impl<R: Register> DoubleShift<Mem64, R, Cl> {
+    /// 0F A5 /r, 0F AD /r
+    pub fn bytecode(&self) -> ByteCode {
+        self.try_bytecode().unwrap()
+    }
+
+    pub fn try_bytecode(&self) -> Result<ByteCode, EncodeError> {
+        bytecode_double_mem(self.0, self.1, self.2, true)
+    }
+}
+
+fn bytecode_double_reg<R: Register>(
+    op: DoubleShiftOp,
+    dst: R,
+    src: R,
+    cl: bool,
+) -> Result<ByteCode, EncodeError> {
+    if src.size() == Size::Byte {
+        return Err(EncodeError::InvalidOperandSize(Size::Byte));
+    }
+
+    let mut code = ByteCode::new();
+
+    // operand-size prefix
+    code.prefix = src.size().prefix();
+
+    // REX prefix
+    let mut rex = Rex::new();
+    rex.set_w(src.size().rex_w());
+    rex.set_r(src.rex_r_bit());
+    rex.set_b(dst.rex_b_bit());
+    code.set_rex(rex, false);
+
+    // opcode
+    code.opcode = BytesAtMost::from([0x0F, op.code() + cl as u8]);
+
+    // ModR/M
+    let mut mod_rm = ModRM::new();
+    mod_rm.set_mode(dst.mode_bits());
+    mod_rm.set_reg(src.reg_bits());
+    mod_rm.set_rm(dst.rm_bits());
+    code.mod_rm = Some(mod_rm);
+
+    Ok(code)
+}
+
+fn bytecode_double_mem<R: Register>(
+    op: DoubleShiftOp,
+    dst: Mem64,
+    src: R,
+    cl: bool,
+) -> Result<ByteCode, EncodeError> {
+    dst.validate()?;
+    if src.size() == Size::Byte {
+        return Err(EncodeError::InvalidOperandSize(Size::Byte));
+    }
+
+    let mut code = ByteCode::new();
+
+    // operand-size prefix
+    code.prefix = src.size().prefix();
+
+    // REX prefix
+    let mut rex = Rex::new();
+    rex.set_w(src.size().rex_w());
+    rex.set_r(src.rex_r_bit());
+    rex.set_x(dst.rex_x_bit());
+    rex.set_b(dst.rex_b_bit());
+    code.set_rex(rex, false);
+
+    // opcode
+    code.opcode = BytesAtMost::from([0x0F, op.code() + cl as u8]);
+
+    // ModR/M
+    let mut mod_rm = ModRM::new();
+    mod_rm.set_mode(dst.mode_bits());
+    mod_rm.set_reg(src.reg_bits());
+    mod_rm.set_rm(dst.rm_bits());
+    code.mod_rm = Some(mod_rm);
+
+    // SIB
+    code.sib = dst.sib_byte();
+
+    // addr disp
+    code.addr_disp = dst.disp_bytes();
+
+    Ok(code)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{Reg, Reg16, Reg32, Reg64, Reg8};
+
+    #[test]
+    fn test_reg() {
+        use Reg64::*;
+
+        let cases = [
+            (Shift::shl(RAX, 1).bytecode(), vec![0x48, 0xD1, 0xE0]),
+            (Shift::shr(RAX, Cl).bytecode(), vec![0x48, 0xD3, 0xE8]),
+            (Shift::sar(RAX, 4).bytecode(), vec![0x48, 0xC1, 0xF8, 0x04]),
+            (Shift::rol(R12, 1).bytecode(), vec![0x49, 0xD1, 0xC4]),
+            (
+                Shift::ror(Reg32::R8D, Cl).bytecode(),
+                vec![0x41, 0xD3, 0xC8],
+            ),
+            (Shift::rcl(Reg32::ECX, 3).bytecode(), vec![0xC1, 0xD1, 0x03]),
+            (Shift::rcr(Reg16::DX, 1).bytecode(), vec![0x66, 0xD1, 0xDA]),
+            (
+                Shift::shl(Reg16::R9W, Cl).bytecode(),
+                vec![0x66, 0x41, 0xD3, 0xE1],
+            ),
+            (Shift::sar(Reg8::AL, 1).bytecode(), vec![0xD0, 0xF8]),
+            (Shift::shr(Reg8::AH, Cl).bytecode(), vec![0xD2, 0xEC]),
+            (
+                Shift::rol(Reg8::SIL, 7).bytecode(),
+                vec![0x40, 0xC0, 0xC6, 0x07],
+            ),
+        ];
+
+        for (bytecode, expected) in cases {
+            assert_eq!(bytecode.to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_mem() {
+        use Reg64::*;
+
+        let cases = [
+            (
+                Shift::shl(Mem64::reg(RDI), 1).bytecode(),
+                vec![0x48, 0xD1, 0x27],
+            ),
+            (
+                Shift::sar(Mem64::reg_offset(RBP, -8), Cl).bytecode(),
+                vec![0x48, 0xD3, 0x7D, 0xF8],
+            ),
+            (
+                Shift::shr(Ptr::dword(Mem64::reg_offset(R12, 4)), 31).bytecode(),
+                vec![0x41, 0xC1, 0x6C, 0x24, 0x04, 0x1F],
+            ),
+            (
+                Shift::rol(Ptr::byte(Mem64::reg(RAX)), 2).bytecode(),
+                vec![0xC0, 0x00, 0x02],
+            ),
+            (
+                Shift::ror(Ptr::word(Mem64::rip_offset(16)), Cl).bytecode(),
+                vec![0x66, 0xD3, 0x0D, 0x10, 0x00, 0x00, 0x00],
+            ),
+            (
+                Shift::rcr(Mem64::sib(Some(RAX), 0, RCX, 3), 1).bytecode(),
+                vec![0x48, 0xD1, 0x1C, 0xC8],
+            ),
+        ];
+
+        for (bytecode, expected) in cases {
+            assert_eq!(bytecode.to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_double_shift() {
+        use Reg64::*;
+
+        let cases = [
+            (
+                DoubleShift::shld(RAX, RCX, 4).bytecode(),
+                vec![0x48, 0x0F, 0xA4, 0xC8, 0x04],
+            ),
+            (
+                DoubleShift::shld(Reg32::R10D, Reg32::EDX, Cl).bytecode(),
+                vec![0x41, 0x0F, 0xA5, 0xD2],
+            ),
+            // 1 でも短い形式は無い
+            (
+                DoubleShift::shrd(Reg16::BX, Reg16::R15W, 1).bytecode(),
+                vec![0x66, 0x44, 0x0F, 0xAC, 0xFB, 0x01],
+            ),
+            (
+                DoubleShift::shrd(Mem64::reg(RDI), RSI, Cl).bytecode(),
+                vec![0x48, 0x0F, 0xAD, 0x37],
+            ),
+            (
+                DoubleShift::shld(Mem64::reg_offset(RSP, 8), Reg32::R9D, 12).bytecode(),
+                vec![0x44, 0x0F, 0xA4, 0x4C, 0x24, 0x08, 0x0C],
+            ),
+            (
+                DoubleShift::shrd(RDX, RAX, 63).bytecode(),
+                vec![0x48, 0x0F, 0xAC, 0xC2, 0x3F],
+            ),
+        ];
+
+        for (bytecode, expected) in cases {
+            assert_eq!(bytecode.to_bytes().bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_from_code() {
+        for op in ShiftOp::ALL {
+            assert_eq!(ShiftOp::from_code(op.code()), Some(op));
+        }
+        assert_eq!(ShiftOp::from_code(6), None);
+    }
+
+    #[test]
+    fn test_try_bytecode_error() {
+        use Reg8::*;
+
+        let bad_scale = Mem64::Sib {
+            base: None,
+            disp: 0,
+            index: Reg64::RAX,
+            scale: 4,
+        };
+
+        assert_eq!(
+            Shift::shl(Ptr::byte(bad_scale), 1).try_bytecode(),
+            Err(EncodeError::InvalidScale(4))
+        );
+        assert_eq!(
+            DoubleShift::shld(bad_scale, Reg64::RCX, Cl).try_bytecode(),
+            Err(EncodeError::InvalidScale(4))
+        );
+        assert_eq!(
+            DoubleShift::shld(AL, CL, 1).try_bytecode(),
+            Err(EncodeError::InvalidOperandSize(Size::Byte))
+        );
+        assert_eq!(
+            DoubleShift::shrd(Mem64::reg(Reg64::RAX), BL, Cl).try_bytecode(),
+            Err(EncodeError::InvalidOperandSize(Size::Byte))
+        );
+        assert_eq!(
+            DoubleShift::shld(Reg::from(Reg64::RAX), Reg::from(Reg32::EAX), Cl).try_bytecode(),
+            Err(EncodeError::OperandSizeMismatch(Size::Qword, Size::Dword))
+        );
+    }
+}
@@ -1,5 +1,6 @@
 use crate::{ByteCode, BytesAtMost};
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Syscall();
 
 impl Syscall {
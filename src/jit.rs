@@ -0,0 +1,213 @@
+//! 機械語を実行可能なメモリに置いて呼び出す (Linux x86_64 のみ)
+//!
+//! 書き込み可能かつ実行可能なページは作らない (W^X)。RW で `mmap` してコピーした後、
+//! `mprotect` で RX に切り替える。
+
+use std::{
+    io, mem,
+    os::raw::{c_int, c_long, c_void},
+    ptr, slice,
+};
+
+const PROT_READ: c_int = 0x1;
+const PROT_WRITE: c_int = 0x2;
+const PROT_EXEC: c_int = 0x4;
+const MAP_PRIVATE: c_int = 0x02;
+const MAP_ANONYMOUS: c_int = 0x20;
+const MAP_FAILED: *mut c_void = !0 as *mut c_void;
+
+extern "C" {
+    fn mmap(
+        addr: *mut c_void,
+        len: usize,
+        prot: c_int,
+        flags: c_int,
+        fd: c_int,
+        offset: c_long,
+    ) -> *mut c_void;
+    fn mprotect(addr: *mut c_void, len: usize, prot: c_int) -> c_int;
+    fn munmap(addr: *mut c_void, len: usize) -> c_int;
+}
+
+/// Read-only, executable memory holding a copy of some machine code.
+///
+/// The mapping is released on drop, so function pointers taken with
+/// [`JitMemory::get`] must not outlive it.
+///
+/// ```
+/// use at_64::{asm::Assembler, instruction::{Lea, Ret}, jit::JitMemory, Mem64, Reg64::*};
+///
+/// // rdi + rsi * 4 + 3
+/// let mut asm = Assembler::new();
+/// asm.emit(Lea(RAX, Mem64::sib(Some(RDI), 3, RSI, 2)));
+/// asm.emit(Ret());
+///
+/// let mem = JitMemory::new(&asm.finish().unwrap()).unwrap();
+/// let f: extern "sysv64" fn(u64, u64) -> u64 = unsafe { mem.get() };
+/// assert_eq!(f(10, 5), 33);
+/// ```
+#[derive(Debug)]
+pub struct JitMemory {
+    ptr: *mut u8,
+    /// コードの長さ
+    len: usize,
+    /// mmap した長さ。空のコードでも 1 以上
+    map_len: usize,
+}
+
+impl JitMemory {
+    /// Maps `code` into fresh pages: written while RW, then switched to RX.
+    pub fn new(code: &[u8]) -> io::Result<Self> {
+        // 長さ 0 の mmap は失敗するので最低 1 byte 確保する
+        let map_len = code.len().max(1);
+        let ptr = unsafe {
+            mmap(
+                ptr::null_mut(),
+                map_len,
+                PROT_READ | PROT_WRITE,
+                MAP_PRIVATE | MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if ptr == MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        let mem = JitMemory {
+            ptr: ptr as *mut u8,
+            len: code.len(),
+            map_len,
+        };
+
+        unsafe {
+            ptr::copy_nonoverlapping(code.as_ptr(), mem.ptr, code.len());
+            if mprotect(ptr, map_len, PROT_READ | PROT_EXEC) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(mem)
+    }
+
+    pub fn as_ptr(&self) -> *const u8 {
+        self.ptr
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        unsafe { slice::from_raw_parts(self.ptr, self.len) }
+    }
+
+    /// Reinterprets the start of the code as a function pointer `F`, typically
+    /// an `extern "sysv64" fn(..) -> ..`.
+    ///
+    /// # Safety
+    ///
+    /// The code must be a function with exactly the signature and calling
+    /// convention of `F`, and the pointer must not be called after `self` is
+    /// dropped.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `F` is not pointer-sized.
+    pub unsafe fn get<F: Copy>(&self) -> F {
+        assert_eq!(mem::size_of::<F>(), mem::size_of::<*const u8>());
+        mem::transmute_copy(&self.ptr)
+    }
+}
+
+// 作成後は書き換えないので、スレッド間で共有してよい
+unsafe impl Send for JitMemory {}
+unsafe impl Sync for JitMemory {}
+
+impl Drop for JitMemory {
+    fn drop(&mut self) {
+        unsafe {
+            munmap(self.ptr as *mut c_void, self.map_len);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        asm::Assembler,
+        instruction::{Alu, AluOp, Mov, Ret},
+        Mem64,
+        Reg64::*,
+    };
+    use std::fs;
+
+    fn compile(build: impl FnOnce(&mut Assembler)) -> JitMemory {
+        let mut asm = Assembler::new();
+        build(&mut asm);
+        JitMemory::new(&asm.finish().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_call() {
+        let mem = compile(|asm| {
+            asm.emit(Mov(RAX, RDI));
+            asm.emit(Alu(AluOp::Sub, RAX, RSI));
+            asm.emit(Ret());
+        });
+        let sub: extern "sysv64" fn(i64, i64) -> i64 = unsafe { mem.get() };
+
+        let cases = [(5, 3, 2), (0, 1, -1), (i64::MIN, 1, i64::MAX)];
+        for (a, b, expected) in cases.iter() {
+            assert_eq!(sub(*a, *b), *expected);
+        }
+    }
+
+    #[test]
+    fn test_memory_operand() {
+        let mem = compile(|asm| {
+            asm.emit(Mov(RAX, Mem64::reg(RDI)));
+            asm.emit(Alu(AluOp::Add, RAX, Mem64::reg_offset(RDI, 8)));
+            asm.emit(Ret());
+        });
+        let sum: extern "sysv64" fn(*const u64) -> u64 = unsafe { mem.get() };
+        assert_eq!(sum([40, 2].as_ptr()), 42);
+    }
+
+    #[test]
+    fn test_protection() {
+        let mem = compile(|asm| asm.emit(Ret()));
+        assert_eq!(mem.as_bytes()[0], 0xC3);
+
+        // /proc/self/maps で r-x になっていることを確かめる
+        let addr = mem.as_ptr() as usize;
+        let maps = fs::read_to_string("/proc/self/maps").unwrap();
+        let perms = maps
+            .lines()
+            .find_map(|line| {
+                let mut fields = line.split_whitespace();
+                let range = fields.next()?;
+                let (start, end) = range.split_at(range.find('-')?);
+                let start = usize::from_str_radix(start, 16).ok()?;
+                let end = usize::from_str_radix(&end[1..], 16).ok()?;
+                if (start..end).contains(&addr) {
+                    fields.next()
+                } else {
+                    None
+                }
+            })
+            .unwrap();
+        assert_eq!(&perms[..3], "r-x");
+    }
+
+    #[test]
+    fn test_empty() {
+        let mem = JitMemory::new(&[]).unwrap();
+        assert!(mem.is_empty());
+        assert_eq!(mem.len(), 0);
+        assert_eq!(mem.as_bytes(), []);
+    }
+}
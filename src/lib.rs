@@ -1,10 +1,27 @@
+pub mod abi;
+pub mod asm;
 pub mod bytecode;
 mod bytes;
+pub mod coff;
+pub mod decode;
+pub mod elf;
+pub mod encode;
 pub mod instruction;
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+pub mod jit;
+pub mod linux;
+pub mod macho;
 pub mod mem;
+pub mod object;
+pub mod parse;
 mod reg;
+pub mod syntax;
 
 pub use bytecode::{ByteCode, ModRM, Rex, Sib};
 pub use bytes::BytesAtMost;
-pub use mem::Mem64;
-pub use reg::{Reg, Reg16, Reg32, Reg64, Reg8};
+pub use decode::DecodeError;
+pub use encode::EncodeError;
+pub use instruction::Instruction;
+pub use mem::{Mem64, Ptr};
+pub use parse::ParseError;
+pub use reg::{Reg, Reg16, Reg32, Reg64, Reg8, Register, Size};
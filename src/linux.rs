@@ -0,0 +1,416 @@
+//! Linux x86-64 のシステムコール呼び出し
+//!
+//! [`LinuxSyscall`] は引数を RDI, RSI, RDX, R10, R8, R9 に、番号を RAX に入れてから
+//! `syscall` を出力する。転送元のレジスタを先に上書きしないよう `mov` の順序を決める。
+
+use crate::{
+    asm::{Assembler, Emit},
+    instruction::{Alu, AluOp, Mov, Pop, Push, Syscall},
+    EncodeError, Mem64,
+    Reg64::{self, *},
+};
+
+/// Registers carrying the syscall arguments, in order.
+pub const ARG_REGS: [Reg64; 6] = [RDI, RSI, RDX, R10, R8, R9];
+
+/// An argument of [`LinuxSyscall`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arg {
+    Imm(u64),
+    Reg(Reg64),
+    /// A qword loaded from memory. RIP-relative operands are relative to the
+    /// emitted load, not to the `syscall`.
+    Mem(Mem64),
+}
+
+impl Arg {
+    /// この引数を読むのに `reg` の値が要るか
+    fn reads(&self, reg: Reg64) -> bool {
+        match *self {
+            Arg::Imm(_) => false,
+            Arg::Reg(src) => src == reg,
+            Arg::Mem(Mem64::RegOffset(base, _)) => base == reg,
+            Arg::Mem(Mem64::RipOffset(_)) => false,
+            // index の RSP は「index 無し」
+            Arg::Mem(Mem64::Sib { base, index, .. }) => {
+                base == Some(reg) || (index == reg && index != RSP)
+            }
+        }
+    }
+}
+
+impl From<u64> for Arg {
+    fn from(imm: u64) -> Self {
+        Arg::Imm(imm)
+    }
+}
+
+impl From<i64> for Arg {
+    fn from(imm: i64) -> Self {
+        Arg::Imm(imm as u64)
+    }
+}
+
+impl From<u32> for Arg {
+    fn from(imm: u32) -> Self {
+        Arg::Imm(imm as u64)
+    }
+}
+
+/// 負数は 64bit に符号拡張する
+impl From<i32> for Arg {
+    fn from(imm: i32) -> Self {
+        Arg::Imm(imm as i64 as u64)
+    }
+}
+
+impl From<Reg64> for Arg {
+    fn from(reg: Reg64) -> Self {
+        Arg::Reg(reg)
+    }
+}
+
+impl From<Mem64> for Arg {
+    fn from(mem: Mem64) -> Self {
+        Arg::Mem(mem)
+    }
+}
+
+/// A Linux system call: syscall number and up to six arguments.
+///
+/// The arguments are read as they were before the call sequence, whatever
+/// order the moves end up in. Arguments that form a cycle (such as swapping
+/// RDI and RSI) go through the stack with `push`/`pop`, so RSP must point to
+/// writable memory. RCX and R11 are left alone until the `syscall`, which
+/// clobbers them.
+///
+/// ```
+/// use at_64::{asm::Assembler, linux::{nr, LinuxSyscall}, Reg64::*};
+///
+/// // write(1, rsi, rdx)
+/// let mut asm = Assembler::new();
+/// asm.emit(LinuxSyscall(nr::WRITE, &[1u64.into(), RSI.into(), RDX.into()]));
+///
+/// // mov edi, 1; mov eax, 1; syscall
+/// let code = asm.finish().unwrap();
+/// assert_eq!(code, [0xBF, 1, 0, 0, 0, 0xB8, 1, 0, 0, 0, 0x0F, 0x05]);
+/// ```
+///
+/// # Panics
+///
+/// Emitting panics if there are more than six arguments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinuxSyscall<'a>(pub u32, pub &'a [Arg]);
+
+impl Emit for LinuxSyscall<'_> {
+    fn try_emit(&self, asm: &mut Assembler) -> Result<(), EncodeError> {
+        let LinuxSyscall(nr, args) = *self;
+        assert!(args.len() <= ARG_REGS.len(), "too many syscall arguments");
+
+        let mut moves: Vec<(Reg64, Arg)> = ARG_REGS
+            .iter()
+            .copied()
+            .zip(args.iter().copied())
+            .filter(|(dst, src)| *src != Arg::Reg(*dst))
+            .collect();
+        moves.push((RAX, Arg::Imm(nr as u64)));
+
+        // 即値は何も読まないので最後に回す
+        let (imms, mut pending): (Vec<_>, Vec<_>) = moves
+            .into_iter()
+            .partition(|(_, src)| matches!(src, Arg::Imm(_)));
+
+        // 他の転送元に読まれていない行き先から書いていく
+        while let Some(i) = (0..pending.len()).find(|&i| {
+            let dst = pending[i].0;
+            pending
+                .iter()
+                .enumerate()
+                .all(|(j, (_, src))| i == j || !src.reads(dst))
+        }) {
+            let (dst, src) = pending.remove(i);
+            emit_mov(asm, dst, src)?;
+        }
+
+        // 循環が残ったら、全部スタックに積んでから取り出す
+        for (pushed, (_, src)) in pending.iter().enumerate() {
+            let adjust = 8 * pushed as i32;
+            match *src {
+                // push rsp は積んだ分だけずれた値を積む
+                Arg::Reg(RSP) => {
+                    asm.try_emit(Push(RSP))?;
+                    if adjust != 0 {
+                        asm.try_emit(Alu(AluOp::Add, Mem64::reg(RSP), adjust))?;
+                    }
+                }
+                Arg::Reg(reg) => asm.try_emit(Push(reg))?,
+                Arg::Mem(mem) => asm.try_emit(Push(rsp_adjusted(mem, adjust)))?,
+                Arg::Imm(_) => unreachable!(),
+            }
+        }
+        for (dst, _) in pending.iter().rev() {
+            asm.try_emit(Pop(*dst))?;
+        }
+
+        for (dst, src) in imms {
+            emit_mov(asm, dst, src)?;
+        }
+        asm.try_emit(Syscall())
+    }
+}
+
+fn emit_mov(asm: &mut Assembler, dst: Reg64, src: Arg) -> Result<(), EncodeError> {
+    match src {
+        Arg::Imm(imm) => asm.try_emit(Mov(dst, imm)),
+        Arg::Reg(reg) => asm.try_emit(Mov(dst, reg)),
+        Arg::Mem(mem) => asm.try_emit(Mov(dst, mem)),
+    }
+}
+
+/// RSP を基準にしたアドレスを、push で下がった分だけずらす
+fn rsp_adjusted(mem: Mem64, adjust: i32) -> Mem64 {
+    match mem {
+        Mem64::RegOffset(RSP, disp) => Mem64::RegOffset(RSP, disp + adjust),
+        Mem64::Sib {
+            base: Some(RSP),
+            disp,
+            index,
+            scale,
+        } => Mem64::Sib {
+            base: Some(RSP),
+            disp: disp + adjust,
+            index,
+            scale,
+        },
+        mem => mem,
+    }
+}
+
+/// x86-64 Linux syscall numbers (`asm/unistd_64.h`).
+pub mod nr {
+    pub const READ: u32 = 0;
+    pub const WRITE: u32 = 1;
+    pub const OPEN: u32 = 2;
+    pub const CLOSE: u32 = 3;
+    pub const STAT: u32 = 4;
+    pub const FSTAT: u32 = 5;
+    pub const LSTAT: u32 = 6;
+    pub const POLL: u32 = 7;
+    pub const LSEEK: u32 = 8;
+    pub const MMAP: u32 = 9;
+    pub const MPROTECT: u32 = 10;
+    pub const MUNMAP: u32 = 11;
+    pub const BRK: u32 = 12;
+    pub const RT_SIGACTION: u32 = 13;
+    pub const RT_SIGPROCMASK: u32 = 14;
+    pub const RT_SIGRETURN: u32 = 15;
+    pub const IOCTL: u32 = 16;
+    pub const PREAD64: u32 = 17;
+    pub const PWRITE64: u32 = 18;
+    pub const READV: u32 = 19;
+    pub const WRITEV: u32 = 20;
+    pub const ACCESS: u32 = 21;
+    pub const PIPE: u32 = 22;
+    pub const SELECT: u32 = 23;
+    pub const SCHED_YIELD: u32 = 24;
+    pub const MREMAP: u32 = 25;
+    pub const MADVISE: u32 = 28;
+    pub const DUP: u32 = 32;
+    pub const DUP2: u32 = 33;
+    pub const NANOSLEEP: u32 = 35;
+    pub const GETPID: u32 = 39;
+    pub const SOCKET: u32 = 41;
+    pub const CONNECT: u32 = 42;
+    pub const ACCEPT: u32 = 43;
+    pub const SENDTO: u32 = 44;
+    pub const RECVFROM: u32 = 45;
+    pub const BIND: u32 = 49;
+    pub const LISTEN: u32 = 50;
+    pub const CLONE: u32 = 56;
+    pub const FORK: u32 = 57;
+    pub const VFORK: u32 = 58;
+    pub const EXECVE: u32 = 59;
+    pub const EXIT: u32 = 60;
+    pub const WAIT4: u32 = 61;
+    pub const KILL: u32 = 62;
+    pub const UNAME: u32 = 63;
+    pub const FCNTL: u32 = 72;
+    pub const FSYNC: u32 = 74;
+    pub const FTRUNCATE: u32 = 77;
+    pub const GETCWD: u32 = 79;
+    pub const CHDIR: u32 = 80;
+    pub const RENAME: u32 = 82;
+    pub const MKDIR: u32 = 83;
+    pub const RMDIR: u32 = 84;
+    pub const UNLINK: u32 = 87;
+    pub const READLINK: u32 = 89;
+    pub const CHMOD: u32 = 90;
+    pub const GETUID: u32 = 102;
+    pub const GETGID: u32 = 104;
+    pub const GETEUID: u32 = 107;
+    pub const GETEGID: u32 = 108;
+    pub const GETPPID: u32 = 110;
+    pub const ARCH_PRCTL: u32 = 158;
+    pub const GETTID: u32 = 186;
+    pub const FUTEX: u32 = 202;
+    pub const CLOCK_GETTIME: u32 = 228;
+    pub const EXIT_GROUP: u32 = 231;
+    pub const OPENAT: u32 = 257;
+    pub const MKDIRAT: u32 = 258;
+    pub const UNLINKAT: u32 = 263;
+    pub const PIPE2: u32 = 293;
+    pub const GETRANDOM: u32 = 318;
+    pub const MEMFD_CREATE: u32 = 319;
+    pub const EXECVEAT: u32 = 322;
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn code(args: &[Arg]) -> Vec<u8> {
+        let mut asm = Assembler::new();
+        asm.emit(LinuxSyscall(nr::EXIT, args));
+        asm.finish().unwrap()
+    }
+
+    #[test]
+    fn test_code() {
+        let cases: [(&[Arg], &[u8]); 5] = [
+            // mov eax, 60
+            (&[], &[0xB8, 0x3C, 0, 0, 0]),
+            // mov rdi, -1; mov eax, 60
+            (
+                &[Arg::Imm(!0)],
+                &[
+                    0x48, 0xC7, 0xC7, 0xFF, 0xFF, 0xFF, 0xFF, 0xB8, 0x3C, 0, 0, 0,
+                ],
+            ),
+            // mov rsi, rdi; mov rdi, rax; mov eax, 60
+            (
+                &[Arg::Reg(RAX), Arg::Reg(RDI)],
+                &[0x48, 0x89, 0xFE, 0x48, 0x89, 0xC7, 0xB8, 0x3C, 0, 0, 0],
+            ),
+            // push rsi; push rdi; pop rsi; pop rdi; mov eax, 60
+            (
+                &[Arg::Reg(RSI), Arg::Reg(RDI)],
+                &[0x56, 0x57, 0x5E, 0x5F, 0xB8, 0x3C, 0, 0, 0],
+            ),
+            // push rsi; push qword ptr [rsp + rdi + 16]; pop rsi; pop rdi; mov eax, 60
+            (
+                &[Arg::Reg(RSI), Arg::Mem(Mem64::sib(Some(RSP), 8, RDI, 0))],
+                &[
+                    0x56, 0xFF, 0x74, 0x3C, 0x10, 0x5E, 0x5F, 0xB8, 0x3C, 0, 0, 0,
+                ],
+            ),
+        ];
+        for (args, expected) in cases.iter() {
+            let code = code(args);
+            assert_eq!(&code[..code.len() - 2], *expected);
+            assert_eq!(&code[code.len() - 2..], [0x0F, 0x05]);
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn test_too_many_args() {
+        code(&[Arg::Imm(0); 7]);
+    }
+
+    /// `syscall` の直前のレジスタを調べる
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[test]
+    fn test_moves() {
+        use crate::jit::JitMemory;
+
+        // 初期値。RCX と RDX はメモリを指す
+        let memory: [u64; 4] = [100, 101, 102, 103];
+        let ptr = memory.as_ptr() as u64;
+        let init = [
+            (RAX, 7),
+            (RDI, 1),
+            (RSI, 0),
+            (RDX, ptr),
+            (R10, 4),
+            (R8, 5),
+            (R9, 6),
+            (RCX, ptr),
+        ];
+        let table = |index| Arg::Mem(Mem64::sib(Some(RCX), 0, index, 3));
+
+        let cases: Vec<Vec<Arg>> = vec![
+            vec![Arg::Imm(1), Arg::Imm(!0), Arg::Imm(0x1234_5678_9ABC)],
+            vec![Arg::Reg(RSI), Arg::Reg(RDI)],
+            vec![Arg::Reg(RSI), Arg::Reg(RDX), Arg::Reg(RDI)],
+            vec![Arg::Reg(RAX), Arg::Reg(RDI)],
+            vec![table(RDI), Arg::Reg(RDI)],
+            vec![table(RSI), Arg::Reg(RDI), Arg::Reg(RSI)],
+            vec![
+                Arg::Reg(RDI),
+                Arg::Mem(Mem64::reg_offset(RCX, 16)),
+                Arg::Reg(R10),
+                Arg::Reg(RSI),
+                Arg::Reg(R9),
+                Arg::Reg(R8),
+            ],
+            // RDI と RDX が循環し、RSI は RDI の転送元に読まれる
+            vec![
+                Arg::Mem(Mem64::sib(Some(RDX), 8, RSI, 3)),
+                Arg::Reg(RSP),
+                Arg::Reg(RDI),
+            ],
+        ];
+        for args in cases.iter() {
+            let mut prefix = Assembler::new();
+            for (reg, value) in init.iter() {
+                prefix.emit(Mov(*reg, *value));
+            }
+
+            let mut out = [0u64; 8];
+            let mut suffix = Assembler::new();
+            suffix.emit(Mov(R11, out.as_mut_ptr() as u64));
+            let regs = [RAX, RDI, RSI, RDX, R10, R8, R9, RSP];
+            for (i, reg) in regs.iter().enumerate() {
+                suffix.emit(Mov(Mem64::reg_offset(R11, 8 * i as i32), *reg));
+            }
+            suffix.emit(crate::instruction::Ret());
+
+            let body = code(args);
+            let mut bytes = prefix.finish().unwrap();
+            bytes.extend_from_slice(&body[..body.len() - 2]);
+            bytes.extend_from_slice(&suffix.finish().unwrap());
+            let mem = JitMemory::new(&bytes).unwrap();
+            let f: extern "sysv64" fn() = unsafe { mem.get() };
+            f();
+
+            // 呼び出し前の値から期待値を求める
+            let before = |reg: Reg64| -> u64 {
+                match reg {
+                    RSP => out[7],
+                    _ => init.iter().find(|(r, _)| *r == reg).unwrap().1,
+                }
+            };
+            let eval = |arg: &Arg| -> u64 {
+                let addr = match *arg {
+                    Arg::Imm(imm) => return imm,
+                    Arg::Reg(reg) => return before(reg),
+                    Arg::Mem(Mem64::RegOffset(base, disp)) => before(base) + disp as u64,
+                    Arg::Mem(Mem64::Sib {
+                        base: Some(base),
+                        disp,
+                        index,
+                        scale,
+                    }) => before(base) + disp as u64 + (before(index) << scale),
+                    Arg::Mem(_) => unreachable!(),
+                };
+                unsafe { *(addr as *const u64) }
+            };
+
+            assert_eq!(out[0], nr::EXIT as u64);
+            for (i, reg) in ARG_REGS.iter().enumerate() {
+                let expected = args.get(i).map(eval).unwrap_or_else(|| before(*reg));
+                assert_eq!(out[i + 1], expected, "{:?} {:?}", args, reg);
+            }
+        }
+    }
+}
@@ -0,0 +1,539 @@
+//! Mach-O 64-bit (x86_64) のオブジェクトファイルの入出力
+//!
+//! シンボル名はそのまま書き出す。C から参照する名前には `_main` のように
+//! 先頭の `_` を付けておく必要がある。
+
+use crate::object::{Object, Reloc, RelocKind, Section, SectionKind, Symbol};
+use byteorder::{ByteOrder as _, WriteBytesExt as _, LE};
+use std::collections::HashMap;
+
+const MH_MAGIC_64: u32 = 0xFEED_FACF;
+const CPU_TYPE_X86_64: u32 = 0x0100_0007;
+const CPU_SUBTYPE_X86_64_ALL: u32 = 3;
+const MH_OBJECT: u32 = 1;
+
+const LC_SYMTAB: u32 = 0x2;
+const LC_DYSYMTAB: u32 = 0xB;
+const LC_SEGMENT_64: u32 = 0x19;
+
+const HEADER_SIZE: u32 = 32;
+const SEGMENT_SIZE: u32 = 72;
+const SECTION_SIZE: u32 = 80;
+const SYMTAB_SIZE: u32 = 24;
+const DYSYMTAB_SIZE: u32 = 80;
+const NLIST_SIZE: usize = 16;
+const RELOC_SIZE: usize = 8;
+
+const S_ZEROFILL: u32 = 0x1;
+const S_ATTR_PURE_INSTRUCTIONS: u32 = 0x8000_0000;
+const S_ATTR_SOME_INSTRUCTIONS: u32 = 0x0000_0400;
+
+const N_EXT: u8 = 0x1;
+const N_SECT: u8 = 0xE;
+const N_TYPE: u8 = 0xE;
+
+const X86_64_RELOC_UNSIGNED: u32 = 0;
+const X86_64_RELOC_SIGNED: u32 = 1;
+const X86_64_RELOC_BRANCH: u32 = 2;
+const X86_64_RELOC_GOT_LOAD: u32 = 3;
+const R_SCATTERED: u32 = 0x8000_0000;
+
+/// Writes `obj` as a Mach-O relocatable object (`MH_OBJECT`).
+///
+/// All sections go into one unnamed segment, in the order of `obj.sections`.
+/// Relocation addends are stored in the relocated fields, as Mach-O expects.
+pub fn write_object(obj: &Object) -> Vec<u8> {
+    let nsects = obj.sections.len() as u32;
+    let sizeofcmds = SEGMENT_SIZE + SECTION_SIZE * nsects + SYMTAB_SIZE + DYSYMTAB_SIZE;
+    let data_start = (HEADER_SIZE + sizeofcmds) as u64;
+
+    // セクションのアドレス。ファイル上の位置は data_start + アドレス
+    let mut addrs = Vec::new();
+    let mut vmsize = 0;
+    let mut filesize = 0;
+    for section in obj.sections.iter() {
+        let addr = align(vmsize, section.align);
+        addrs.push(addr);
+        vmsize = addr + section.data.len() as u64;
+        if section.kind != SectionKind::Bss {
+            filesize = vmsize;
+        }
+    }
+    let section_index = |kind: SectionKind| {
+        obj.sections
+            .iter()
+            .position(|section| section.kind == kind)
+            .unwrap()
+    };
+
+    // ローカル、定義済みの外部シンボル、未定義シンボルの順に並べる
+    let symbols = obj.all_symbols();
+    let locals: Vec<&Symbol> = symbols.iter().filter(|sym| !sym.global).collect();
+    let extdefs: Vec<&Symbol> = symbols
+        .iter()
+        .filter(|sym| sym.global && sym.def.is_some())
+        .collect();
+    let undefs: Vec<&Symbol> = symbols
+        .iter()
+        .filter(|sym| sym.global && sym.def.is_none())
+        .collect();
+    let ordered: Vec<&Symbol> = locals
+        .iter()
+        .chain(extdefs.iter())
+        .chain(undefs.iter())
+        .copied()
+        .collect();
+    let mut symbol_index = HashMap::new();
+    for (i, sym) in ordered.iter().enumerate() {
+        symbol_index.entry(sym.name.as_str()).or_insert(i as u32);
+    }
+
+    let mut buf = vec![0; data_start as usize];
+
+    for (section, addr) in obj.sections.iter().zip(addrs.iter()) {
+        if section.kind == SectionKind::Bss {
+            continue;
+        }
+        let start = (data_start + addr) as usize;
+        pad(&mut buf, start as u64);
+        buf.extend_from_slice(&section.data);
+
+        for reloc in section.relocs.iter() {
+            let field = start + reloc.offset as usize;
+            match reloc.kind {
+                RelocKind::Abs64 => LE::write_i64(&mut buf[field..field + 8], reloc.addend),
+                // フィールドの末尾からの相対になる
+                _ => LE::write_i32(&mut buf[field..field + 4], reloc.addend as i32 + 4),
+            }
+        }
+    }
+    pad(&mut buf, data_start + filesize);
+
+    let mut reloc_offsets = Vec::new();
+    for section in obj.sections.iter() {
+        align_buf(&mut buf, 8);
+        reloc_offsets.push(buf.len() as u32);
+        // アセンブラの慣習に合わせて逆順に並べる
+        for reloc in section.relocs.iter().rev() {
+            let (ty, pcrel, length) = reloc_type(reloc.kind);
+            let sym = symbol_index[reloc.symbol.as_str()];
+            let info = sym | (pcrel as u32) << 24 | length << 25 | 1 << 27 | ty << 28;
+            buf.write_i32::<LE>(reloc.offset as i32).unwrap();
+            buf.write_u32::<LE>(info).unwrap();
+        }
+    }
+
+    let mut strtab = vec![0];
+    align_buf(&mut buf, 8);
+    let symoff = buf.len() as u32;
+    for sym in ordered.iter() {
+        let strx = strtab.len() as u32;
+        strtab.extend_from_slice(sym.name.as_bytes());
+        strtab.push(0);
+
+        let (ty, sect, value) = match sym.def {
+            Some((kind, offset)) => {
+                let i = section_index(kind);
+                (N_SECT, i as u8 + 1, addrs[i] + offset)
+            }
+            None => (0, 0, 0),
+        };
+        buf.write_u32::<LE>(strx).unwrap();
+        buf.write_u8(ty | if sym.global { N_EXT } else { 0 })
+            .unwrap();
+        buf.write_u8(sect).unwrap();
+        buf.write_u16::<LE>(0).unwrap(); // n_desc
+        buf.write_u64::<LE>(value).unwrap();
+    }
+    let stroff = buf.len() as u32;
+    strtab.resize(align(strtab.len() as u64, 8) as usize, 0);
+    buf.extend_from_slice(&strtab);
+
+    // ヘッダとロードコマンド
+    let mut cmds = Vec::new();
+    cmds.write_u32::<LE>(MH_MAGIC_64).unwrap();
+    cmds.write_u32::<LE>(CPU_TYPE_X86_64).unwrap();
+    cmds.write_u32::<LE>(CPU_SUBTYPE_X86_64_ALL).unwrap();
+    cmds.write_u32::<LE>(MH_OBJECT).unwrap();
+    cmds.write_u32::<LE>(3).unwrap(); // ncmds
+    cmds.write_u32::<LE>(sizeofcmds).unwrap();
+    cmds.write_u32::<LE>(0).unwrap(); // flags
+    cmds.write_u32::<LE>(0).unwrap(); // reserved
+
+    cmds.write_u32::<LE>(LC_SEGMENT_64).unwrap();
+    cmds.write_u32::<LE>(SEGMENT_SIZE + SECTION_SIZE * nsects)
+        .unwrap();
+    cmds.extend_from_slice(&[0; 16]); // segname
+    cmds.write_u64::<LE>(0).unwrap(); // vmaddr
+    cmds.write_u64::<LE>(vmsize).unwrap();
+    cmds.write_u64::<LE>(data_start).unwrap();
+    cmds.write_u64::<LE>(filesize).unwrap();
+    cmds.write_u32::<LE>(7).unwrap(); // maxprot: rwx
+    cmds.write_u32::<LE>(7).unwrap(); // initprot
+    cmds.write_u32::<LE>(nsects).unwrap();
+    cmds.write_u32::<LE>(0).unwrap(); // flags
+
+    for (i, section) in obj.sections.iter().enumerate() {
+        let (segname, sectname, flags) = section_names(section.kind);
+        cmds.extend_from_slice(&name16(sectname));
+        cmds.extend_from_slice(&name16(segname));
+        cmds.write_u64::<LE>(addrs[i]).unwrap();
+        cmds.write_u64::<LE>(section.data.len() as u64).unwrap();
+        let offset = match section.kind {
+            SectionKind::Bss => 0,
+            _ => (data_start + addrs[i]) as u32,
+        };
+        cmds.write_u32::<LE>(offset).unwrap();
+        cmds.write_u32::<LE>(section.align.max(1).trailing_zeros())
+            .unwrap();
+        let (reloff, nreloc) = match section.relocs.len() {
+            0 => (0, 0),
+            n => (reloc_offsets[i], n as u32),
+        };
+        cmds.write_u32::<LE>(reloff).unwrap();
+        cmds.write_u32::<LE>(nreloc).unwrap();
+        cmds.write_u32::<LE>(flags).unwrap();
+        cmds.extend_from_slice(&[0; 12]); // reserved1 ~ reserved3
+    }
+
+    cmds.write_u32::<LE>(LC_SYMTAB).unwrap();
+    cmds.write_u32::<LE>(SYMTAB_SIZE).unwrap();
+    cmds.write_u32::<LE>(symoff).unwrap();
+    cmds.write_u32::<LE>(ordered.len() as u32).unwrap();
+    cmds.write_u32::<LE>(stroff).unwrap();
+    cmds.write_u32::<LE>(strtab.len() as u32).unwrap();
+
+    cmds.write_u32::<LE>(LC_DYSYMTAB).unwrap();
+    cmds.write_u32::<LE>(DYSYMTAB_SIZE).unwrap();
+    let (nlocal, nextdef, nundef) = (locals.len(), extdefs.len(), undefs.len());
+    for n in [0, nlocal, nlocal, nextdef, nlocal + nextdef, nundef].iter() {
+        cmds.write_u32::<LE>(*n as u32).unwrap();
+    }
+    cmds.extend_from_slice(&[0; 4 * 12]); // tocoff ~ nlocrel
+
+    buf[..cmds.len()].copy_from_slice(&cmds);
+    buf
+}
+
+/// Reads back an object written by [`write_object`].
+///
+/// The implicit addends are moved into [`Reloc::addend`] and the fields are
+/// zeroed, so the result compares equal to the written [`Object`]. Returns
+/// `None` if `data` is malformed or uses sections or relocation types that
+/// [`Object`] cannot express.
+pub fn read_object(data: &[u8]) -> Option<Object> {
+    let u32_at = |offset: usize| data.get(offset..offset + 4).map(LE::read_u32);
+    if u32_at(0)? != MH_MAGIC_64 || u32_at(4)? != CPU_TYPE_X86_64 || u32_at(12)? != MH_OBJECT {
+        return None;
+    }
+
+    let mut obj = Object::new();
+    let mut addrs = Vec::new();
+    let mut reloc_ranges = Vec::new();
+    let mut symtab = None;
+
+    // 各コマンドは cmd と cmdsize の 8 バイト以上なので、sizeofcmds に収まる数まで
+    let (ncmds, sizeofcmds) = (u32_at(16)? as usize, u32_at(20)? as usize);
+    if ncmds > sizeofcmds / 8 {
+        return None;
+    }
+    let end = HEADER_SIZE as usize + sizeofcmds;
+    let mut cmd = HEADER_SIZE as usize;
+    for _ in 0..ncmds {
+        let (kind, size) = (u32_at(cmd)?, u32_at(cmd + 4)? as usize);
+        if size < 8 || size > end - cmd {
+            return None;
+        }
+        match kind {
+            LC_SEGMENT_64 => {
+                let nsects = u32_at(cmd + 64)? as usize;
+                if nsects > size.checked_sub(SEGMENT_SIZE as usize)? / SECTION_SIZE as usize {
+                    return None;
+                }
+                for i in 0..nsects {
+                    let sect = data.get(cmd + 72 + i * 80..cmd + 72 + (i + 1) * 80)?;
+                    let kind = section_kind(&sect[16..32], &sect[..16])?;
+                    let addr = LE::read_u64(&sect[32..]);
+                    let size = LE::read_u64(&sect[40..]) as usize;
+                    let offset = LE::read_u32(&sect[48..]) as usize;
+                    let align = LE::read_u32(&sect[52..]);
+                    let reloff = LE::read_u32(&sect[56..]) as usize;
+                    let nreloc = LE::read_u32(&sect[60..]) as usize;
+
+                    let bytes = match kind {
+                        // 壊れたサイズで巨大な領域を確保しない (COFF と同じく 32bit まで)
+                        SectionKind::Bss if size > u32::MAX as usize => return None,
+                        SectionKind::Bss => vec![0; size],
+                        _ => data.get(offset..offset.checked_add(size)?)?.to_vec(),
+                    };
+                    let mut section = Section::new(kind, bytes);
+                    section.align = 1u64.checked_shl(align)?;
+                    obj.sections.push(section);
+                    addrs.push(addr);
+                    reloc_ranges.push((reloff, nreloc));
+                }
+            }
+            LC_SYMTAB if size < SYMTAB_SIZE as usize => return None,
+            LC_SYMTAB => {
+                let cmd = data.get(cmd..cmd + SYMTAB_SIZE as usize)?;
+                symtab = Some((
+                    LE::read_u32(&cmd[8..]) as usize,
+                    LE::read_u32(&cmd[12..]) as usize,
+                    LE::read_u32(&cmd[16..]) as usize,
+                    LE::read_u32(&cmd[20..]) as usize,
+                ));
+            }
+            _ => {}
+        }
+        cmd += size;
+    }
+
+    let (symoff, nsyms, stroff, strsize) = symtab?;
+    let strtab = data.get(stroff..stroff.checked_add(strsize)?)?;
+    let mut names = Vec::new();
+    for i in 0..nsyms {
+        let nlist = data.get(symoff + i * NLIST_SIZE..symoff + (i + 1) * NLIST_SIZE)?;
+        let name = strtab.get(LE::read_u32(nlist) as usize..)?;
+        let name = String::from_utf8(name[..name.iter().position(|b| *b == 0)?].to_vec()).ok()?;
+        names.push(name.clone());
+
+        let (ty, sect) = (nlist[4], nlist[5] as usize);
+        let def = match ty & N_TYPE {
+            N_SECT => {
+                let i = sect.checked_sub(1)?;
+                let value = LE::read_u64(&nlist[8..]);
+                Some((obj.sections.get(i)?.kind, value.checked_sub(addrs[i])?))
+            }
+            0 => None,
+            _ => return None,
+        };
+        obj.symbols.push(Symbol {
+            name,
+            def,
+            global: ty & N_EXT != 0,
+        });
+    }
+
+    for (section, (reloff, nreloc)) in obj.sections.iter_mut().zip(reloc_ranges) {
+        let relocs = data.get(reloff..reloff + nreloc * RELOC_SIZE)?;
+        for reloc in relocs.chunks_exact(RELOC_SIZE).rev() {
+            // 最上位ビットが立つのは scattered relocation (非対応)
+            let offset = LE::read_u32(reloc);
+            if offset & R_SCATTERED != 0 {
+                return None;
+            }
+            let info = LE::read_u32(&reloc[4..]);
+            let kind = reloc_kind(info >> 28, info >> 25 & 0b11)?;
+            if info >> 27 & 1 == 0 {
+                return None;
+            }
+
+            let start = offset as usize;
+            let field = section
+                .data
+                .get_mut(start..start.checked_add(kind.size())?)?;
+            let addend = match kind {
+                RelocKind::Abs64 => LE::read_i64(field),
+                _ => LE::read_i32(field) as i64 - 4,
+            };
+            field.iter_mut().for_each(|b| *b = 0);
+
+            section.relocs.push(Reloc {
+                offset: offset as u64,
+                symbol: names.get((info & 0xFF_FFFF) as usize)?.clone(),
+                kind,
+                addend,
+            });
+        }
+    }
+
+    Some(obj)
+}
+
+/// `(segname, sectname, flags)`
+fn section_names(kind: SectionKind) -> (&'static str, &'static str, u32) {
+    match kind {
+        SectionKind::Text => (
+            "__TEXT",
+            "__text",
+            S_ATTR_PURE_INSTRUCTIONS | S_ATTR_SOME_INSTRUCTIONS,
+        ),
+        SectionKind::Rodata => ("__TEXT", "__const", 0),
+        SectionKind::Data => ("__DATA", "__data", 0),
+        SectionKind::Bss => ("__DATA", "__bss", S_ZEROFILL),
+    }
+}
+
+fn section_kind(segname: &[u8], sectname: &[u8]) -> Option<SectionKind> {
+    [
+        SectionKind::Text,
+        SectionKind::Data,
+        SectionKind::Rodata,
+        SectionKind::Bss,
+    ]
+    .iter()
+    .copied()
+    .find(|kind| {
+        let (seg, sect, _) = section_names(*kind);
+        name16(seg) == segname && name16(sect) == sectname
+    })
+}
+
+/// `(r_type, r_pcrel, r_length)`
+fn reloc_type(kind: RelocKind) -> (u32, bool, u32) {
+    match kind {
+        RelocKind::Pc32 => (X86_64_RELOC_SIGNED, true, 2),
+        RelocKind::Plt32 => (X86_64_RELOC_BRANCH, true, 2),
+        RelocKind::Abs64 => (X86_64_RELOC_UNSIGNED, false, 3),
+        RelocKind::GotPcRel => (X86_64_RELOC_GOT_LOAD, true, 2),
+    }
+}
+
+fn reloc_kind(ty: u32, length: u32) -> Option<RelocKind> {
+    match (ty, length) {
+        (X86_64_RELOC_SIGNED, 2) => Some(RelocKind::Pc32),
+        (X86_64_RELOC_BRANCH, 2) => Some(RelocKind::Plt32),
+        (X86_64_RELOC_UNSIGNED, 3) => Some(RelocKind::Abs64),
+        (X86_64_RELOC_GOT_LOAD, 2) => Some(RelocKind::GotPcRel),
+        _ => None,
+    }
+}
+
+/// 16 byte に 0 で詰めた名前
+fn name16(name: &str) -> [u8; 16] {
+    let mut buf = [0; 16];
+    buf[..name.len()].copy_from_slice(name.as_bytes());
+    buf
+}
+
+fn align(offset: u64, align: u64) -> u64 {
+    let align = align.max(1);
+    offset.div_ceil(align) * align
+}
+
+fn pad(buf: &mut Vec<u8>, len: u64) {
+    buf.resize(len as usize, 0);
+}
+
+fn align_buf(buf: &mut Vec<u8>, n: u64) {
+    let len = align(buf.len() as u64, n);
+    pad(buf, len);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        asm::{Assembler, Got, Sym},
+        instruction::{Call, Jmp, Lea, Mov, Ret},
+        Reg64::*,
+    };
+
+    /// `testdata/macho.o` と同じ内容
+    fn sample() -> Object {
+        let mut asm = Assembler::new();
+        asm.emit(Lea(RDI, Sym("msg")));
+        asm.emit(Call(Sym("_puts")));
+        asm.emit(Mov(RAX, Got("_counter")));
+        asm.emit(Mov(RCX, Sym("_table")));
+        asm.emit(Ret());
+        asm.emit(Jmp(Sym("_exit")));
+        let code = asm.assemble().unwrap();
+
+        let mut text = Section::new(SectionKind::Text, code.code);
+        text.relocs = code.relocs;
+        let mut data = Section::new(SectionKind::Data, vec![0; 8]);
+        data.relocs.push(Reloc {
+            offset: 0,
+            symbol: "msg".to_string(),
+            kind: RelocKind::Abs64,
+            addend: 2,
+        });
+
+        Object {
+            sections: vec![
+                text,
+                Section::new(SectionKind::Rodata, b"hello\0".to_vec()),
+                data,
+                Section::new(SectionKind::Bss, vec![0; 16]),
+            ],
+            symbols: vec![
+                Symbol::local("msg", SectionKind::Rodata, 0),
+                Symbol::global("_main", SectionKind::Text, 0),
+                Symbol::global("_table", SectionKind::Data, 0),
+                Symbol::global("_counter", SectionKind::Bss, 8),
+                Symbol::undefined("_puts"),
+                Symbol::undefined("_exit"),
+            ],
+        }
+    }
+
+    #[test]
+    fn test_golden() {
+        let golden = include_bytes!("testdata/macho.o");
+        assert_eq!(write_object(&sample()), &golden[..]);
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let obj = sample();
+        assert_eq!(read_object(&write_object(&obj)), Some(obj));
+    }
+
+    #[test]
+    fn test_implicit_addend() {
+        let bytes = write_object(&sample());
+
+        // __data の先頭に msg + 2 の addend が書き込まれる
+        let data_offset = LE::read_u32(&bytes[32 + 72 + 2 * 80 + 48..]) as usize;
+        assert_eq!(bytes[data_offset..data_offset + 8], 2u64.to_le_bytes());
+
+        // lea rdi, [rip + msg] は addend -4 なのでフィールドは 0
+        let text_offset = LE::read_u32(&bytes[32 + 72 + 48..]) as usize;
+        assert_eq!(bytes[text_offset + 3..text_offset + 7], [0; 4]);
+    }
+
+    #[test]
+    fn test_malformed() {
+        let bytes = write_object(&sample());
+        assert_eq!(read_object(&bytes[..100]), None);
+        assert_eq!(read_object(&bytes[4..]), None);
+        assert_eq!(read_object(b"\x7FELF"), None);
+
+        // section_64 の align (2 の指数) が 64 以上
+        let mut bytes = bytes;
+        let align = (HEADER_SIZE + SEGMENT_SIZE) as usize + 52;
+        bytes[align..align + 4].copy_from_slice(&64u32.to_le_bytes());
+        assert_eq!(read_object(&bytes), None);
+
+        // __bss の size が 32bit を超える
+        let mut bytes = write_object(&sample());
+        let size = (HEADER_SIZE + SEGMENT_SIZE + SECTION_SIZE * 3) as usize + 40;
+        assert_eq!(&bytes[size - 40..size - 34], b"__bss\0");
+        bytes[size..size + 8].copy_from_slice(&(1u64 << 40).to_le_bytes());
+        assert_eq!(read_object(&bytes), None);
+
+        // ncmds が sizeofcmds に収まらない、または cmdsize が 0 で同じコマンドを読み続ける
+        let mut bytes = write_object(&sample());
+        bytes[16..20].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(read_object(&bytes), None);
+        let mut bytes = write_object(&sample());
+        let cmdsize = HEADER_SIZE as usize + 4;
+        bytes[cmdsize..cmdsize + 4].copy_from_slice(&0u32.to_le_bytes());
+        assert_eq!(read_object(&bytes), None);
+
+        // コマンドが sizeofcmds をはみ出す
+        let mut bytes = write_object(&sample());
+        let sizeofcmds = LE::read_u32(&bytes[20..]) - 1;
+        bytes[20..24].copy_from_slice(&sizeofcmds.to_le_bytes());
+        assert_eq!(read_object(&bytes), None);
+
+        // 最初の relocation の r_address が負
+        let mut bytes = write_object(&sample());
+        let sect = (HEADER_SIZE + SEGMENT_SIZE) as usize;
+        let reloff = LE::read_u32(&bytes[sect + 56..]) as usize;
+        assert_ne!(LE::read_u32(&bytes[sect + 60..]), 0);
+        bytes[reloff..reloff + 4].copy_from_slice(&(-1i32).to_le_bytes());
+        assert_eq!(read_object(&bytes), None);
+    }
+}
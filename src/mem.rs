@@ -1,25 +1,32 @@
-use crate::{bytecode::Sib, reg::Reg64, BytesAtMost};
+use crate::{
+    bytecode::{ModRM, Rex, Sib},
+    reg::{Reg64, Size},
+    syntax::{Intel, Syntax},
+    BytesAtMost, EncodeError,
+};
+use std::fmt::{Display, Error as FmtError, Formatter};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Mem64 {
-    /// [reg + u32]
+    /// [reg + i32]
     ///
     /// ## NOTE
     /// - [RBP],[R13]は[RBP+0],[R13+0] として扱う
     /// - [RSP+d],[R12+d]は[SIB+d]として扱い、
     ///   SIB.base = RSP/R12, SIB.index = RSP
     ///   を設定する
-    RegOffset(Reg64, u32),
-    /// [RIP + u32]
-    RipOffset(u32),
+    RegOffset(Reg64, i32),
+    /// [RIP + i32]
+    RipOffset(i32),
     /// [base + disp + index * scale]
     ///
     /// ## NOTE
     /// indexフィールドにRSPを指定した場合、
     /// 「index無し」として扱われる。
+    /// base無し・scale 0 の場合のみ有効で、[disp32] (絶対アドレス) になる。
     Sib {
         base: Option<Reg64>,
-        disp: u32,
+        disp: i32,
         index: Reg64,
         scale: u8, // 0 ~ 3,
     },
@@ -30,38 +37,105 @@ impl Mem64 {
         Mem64::RegOffset(reg, 0)
     }
 
-    pub fn reg_offset(reg: Reg64, offset: u32) -> Self {
+    pub fn reg_offset(reg: Reg64, offset: i32) -> Self {
         Mem64::RegOffset(reg, offset)
     }
 
-    pub fn rip_offset(offset: u32) -> Self {
+    pub fn rip_offset(offset: i32) -> Self {
         Mem64::RipOffset(offset)
     }
 
-    pub fn sib(base: Option<Reg64>, disp: u32, index: Reg64, scale: u8) -> Self {
-        assert!(scale <= 3);
-        Mem64::Sib {
+    pub fn sib(base: Option<Reg64>, disp: i32, index: Reg64, scale: u8) -> Self {
+        Mem64::try_sib(base, disp, index, scale).unwrap()
+    }
+
+    pub fn try_sib(
+        base: Option<Reg64>,
+        disp: i32,
+        index: Reg64,
+        scale: u8,
+    ) -> Result<Self, EncodeError> {
+        let mem = Mem64::Sib {
             base,
             disp,
             index,
             scale,
+        };
+        mem.validate()?;
+        Ok(mem)
+    }
+
+    /// エンコードできない Sib (scale が 4 以上、index が RSP) を弾く
+    pub fn validate(&self) -> Result<(), EncodeError> {
+        match self {
+            Mem64::Sib { scale, .. } if *scale > 3 => Err(EncodeError::InvalidScale(*scale)),
+            Mem64::Sib {
+                base: None,
+                index: Reg64::RSP,
+                scale: 0,
+                ..
+            } => Ok(()),
+            Mem64::Sib {
+                index: Reg64::RSP, ..
+            } => Err(EncodeError::RspAsIndex),
+            _ => Ok(()),
+        }
+    }
+
+    /// ModR/M, SIB, disp から Mem64 を復元する
+    ///
+    /// ModR/M の mode は 0b11 (レジスタ直接) 以外であること
+    pub fn from_mod_rm(mod_rm: ModRM, sib: Option<Sib>, disp: i32, rex: Option<Rex>) -> Self {
+        let rex_x = rex.map(|rex| rex.x()).unwrap_or(false);
+        let rex_b = rex.map(|rex| rex.b()).unwrap_or(false);
+
+        match (mod_rm.mode(), mod_rm.rm(), sib) {
+            (_, 0b100, Some(sib)) => {
+                let base = match (mod_rm.mode(), sib.base()) {
+                    (0b00, 0b101) => None,
+                    (_, base) => Some(Reg64::from_bits(base, rex_b)),
+                };
+                let index = Reg64::from_bits(sib.index(), rex_x);
+
+                match (base, index) {
+                    // index無し
+                    (Some(base), Reg64::RSP) => Mem64::RegOffset(base, disp),
+                    (None, Reg64::RSP) => Mem64::Sib {
+                        base,
+                        disp,
+                        index,
+                        scale: 0,
+                    },
+                    (base, index) => Mem64::Sib {
+                        base,
+                        disp,
+                        index,
+                        scale: sib.scale(),
+                    },
+                }
+            }
+            (0b00, 0b101, _) => Mem64::RipOffset(disp),
+            (_, rm, _) => Mem64::RegOffset(Reg64::from_bits(rm, rex_b), disp),
         }
     }
 
     /// ModR/M operand の mode フィールドの値
+    ///
+    /// dispが -128..=127 に収まる場合は disp8 (0b01)、
+    /// それ以外は disp32 (0b10) を選択する
     pub fn mode_bits(&self) -> u8 {
         use {Mem64::*, Reg64::*};
 
         match self {
             RegOffset(RBP | R13, 0) => 0b01,
             RegOffset(_, 0) => 0b00,
-            RegOffset(_, 1..=256) => 0b01,
+            RegOffset(_, -128..=127) => 0b01,
             RegOffset(_, _) => 0b10,
             RipOffset(_) => 0b00,
             Sib { base: None, .. } => 0b00,
             Sib {
                 base: Some(RBP | R13),
-                disp: 0..=256,
+                disp: -128..=127,
                 ..
             } => 0b01,
             Sib {
@@ -69,7 +143,9 @@ impl Mem64 {
                 ..
             } => 0b10,
             Sib { disp: 0, .. } => 0b00,
-            Sib { disp: 1..=256, .. } => 0b01,
+            Sib {
+                disp: -128..=127, ..
+            } => 0b01,
             Sib { .. } => 0b10,
         }
     }
@@ -109,9 +185,9 @@ impl Mem64 {
         use {Mem64::*, Reg64::*};
 
         match self {
-            RegOffset(RBP | R13, 0) => BytesAtMost::from(0 as u8),
+            RegOffset(RBP | R13, 0) => BytesAtMost::from(0i8),
             RegOffset(_, 0) => BytesAtMost::new(0),
-            RegOffset(_, disp @ 1..=256) => BytesAtMost::from(*disp as u8),
+            RegOffset(_, disp @ -128..=127) => BytesAtMost::from(*disp as i8),
             RegOffset(_, disp) => BytesAtMost::from(*disp),
             RipOffset(disp) => BytesAtMost::from(*disp),
             Sib {
@@ -121,12 +197,12 @@ impl Mem64 {
                 base: Some(RBP | R13),
                 disp: 0,
                 ..
-            } => BytesAtMost::from(0u8),
+            } => BytesAtMost::from(0i8),
             Sib { disp: 0, .. } => BytesAtMost::new(0),
             Sib {
-                disp: disp @ 1..=256,
+                disp: disp @ -128..=127,
                 ..
-            } => BytesAtMost::from(*disp as u8),
+            } => BytesAtMost::from(*disp as i8),
             Sib { disp, .. } => BytesAtMost::from(*disp),
         }
     }
@@ -134,13 +210,13 @@ impl Mem64 {
     pub fn rex_x_bit(&self) -> bool {
         use Reg64::*;
 
-        match self {
+        matches!(
+            self,
             Mem64::Sib {
                 index: R8 | R9 | R10 | R11 | R12 | R13 | R14 | R15,
                 ..
-            } => true,
-            _ => false,
-        }
+            }
+        )
     }
 
     pub fn rex_b_bit(&self) -> bool {
@@ -154,3 +230,285 @@ impl Mem64 {
         }
     }
 }
+
+/// オペランドサイズを明示したメモリオペランド (`dword ptr [..]` など)
+///
+/// レジスタオペランドからサイズが決まらない命令 (mem, imm など) に使う
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ptr(pub Size, pub Mem64);
+
+impl Ptr {
+    pub fn byte(mem: Mem64) -> Self {
+        Ptr(Size::Byte, mem)
+    }
+
+    pub fn word(mem: Mem64) -> Self {
+        Ptr(Size::Word, mem)
+    }
+
+    pub fn dword(mem: Mem64) -> Self {
+        Ptr(Size::Dword, mem)
+    }
+
+    pub fn qword(mem: Mem64) -> Self {
+        Ptr(Size::Qword, mem)
+    }
+}
+
+/// Intel 記法 ([`Intel`] と同じ)
+impl Display for Mem64 {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        Intel.fmt_mem(self, f)
+    }
+}
+
+impl Display for Ptr {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        write!(f, "{} ptr {}", self.0, self.1)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_from_mod_rm() {
+        use Reg64::*;
+
+        let mems = [
+            Mem64::reg(RAX),
+            Mem64::reg(RBP),
+            Mem64::reg(R13),
+            Mem64::reg(RSP),
+            Mem64::reg(R12),
+            Mem64::reg_offset(RDI, -8),
+            Mem64::reg_offset(R15, 200),
+            Mem64::rip_offset(-42),
+            Mem64::sib(Some(RBP), 42, RAX, 3),
+            Mem64::sib(Some(RAX), 0, R12, 2),
+            Mem64::sib(Some(R13), -300, R9, 1),
+            Mem64::sib(None, 0, RDI, 1),
+            Mem64::sib(None, 8, RSP, 0),
+        ];
+
+        for mem in mems {
+            let mut mod_rm = ModRM::new();
+            mod_rm.set_mode(mem.mode_bits());
+            mod_rm.set_rm(mem.rm_bits());
+
+            let mut rex = Rex::new();
+            rex.set_x(mem.rex_x_bit());
+            rex.set_b(mem.rex_b_bit());
+
+            let disp = match mem.disp_bytes().bytes() {
+                [] => 0,
+                [disp] => *disp as i8 as i32,
+                disp => i32::from_le_bytes([disp[0], disp[1], disp[2], disp[3]]),
+            };
+
+            let decoded = Mem64::from_mod_rm(mod_rm, mem.sib_byte(), disp, Some(rex));
+            assert_eq!(decoded, mem);
+        }
+    }
+
+    #[test]
+    fn test_reg_offset_disp() {
+        use Reg64::*;
+
+        let cases = [
+            (Mem64::reg_offset(RAX, 0), 0b00, vec![]),
+            (Mem64::reg_offset(RAX, 1), 0b01, vec![0x01]),
+            (Mem64::reg_offset(RAX, -1), 0b01, vec![0xFF]),
+            (Mem64::reg_offset(RAX, 127), 0b01, vec![0x7F]),
+            (Mem64::reg_offset(RAX, -128), 0b01, vec![0x80]),
+            (
+                Mem64::reg_offset(RAX, 128),
+                0b10,
+                vec![0x80, 0x00, 0x00, 0x00],
+            ),
+            (
+                Mem64::reg_offset(RAX, -129),
+                0b10,
+                vec![0x7F, 0xFF, 0xFF, 0xFF],
+            ),
+            (
+                Mem64::reg_offset(RAX, 200),
+                0b10,
+                vec![0xC8, 0x00, 0x00, 0x00],
+            ),
+            (
+                Mem64::reg_offset(RAX, 256),
+                0b10,
+                vec![0x00, 0x01, 0x00, 0x00],
+            ),
+            (
+                Mem64::reg_offset(RAX, i32::MAX),
+                0b10,
+                vec![0xFF, 0xFF, 0xFF, 0x7F],
+            ),
+            (
+                Mem64::reg_offset(RAX, i32::MIN),
+                0b10,
+                vec![0x00, 0x00, 0x00, 0x80],
+            ),
+            // [RBP],[R13] は disp8 = 0 を必要とする
+            (Mem64::reg_offset(RBP, 0), 0b01, vec![0x00]),
+            (Mem64::reg_offset(R13, 0), 0b01, vec![0x00]),
+            (Mem64::reg_offset(RBP, -8), 0b01, vec![0xF8]),
+            (Mem64::reg_offset(RBP, 127), 0b01, vec![0x7F]),
+            (Mem64::reg_offset(RBP, -128), 0b01, vec![0x80]),
+            (
+                Mem64::reg_offset(RBP, 128),
+                0b10,
+                vec![0x80, 0x00, 0x00, 0x00],
+            ),
+            (
+                Mem64::reg_offset(R13, -129),
+                0b10,
+                vec![0x7F, 0xFF, 0xFF, 0xFF],
+            ),
+            // [RSP],[R12] は SIB を使うが disp の選択は同じ
+            (Mem64::reg_offset(RSP, 0), 0b00, vec![]),
+            (Mem64::reg_offset(RSP, -128), 0b01, vec![0x80]),
+            (
+                Mem64::reg_offset(R12, 128),
+                0b10,
+                vec![0x80, 0x00, 0x00, 0x00],
+            ),
+        ];
+
+        for (mem, mode, disp) in cases {
+            assert_eq!(mem.mode_bits(), mode, "{:?}", mem);
+            assert_eq!(mem.disp_bytes().bytes(), disp, "{:?}", mem);
+        }
+    }
+
+    #[test]
+    fn test_rip_offset_disp() {
+        let cases = [
+            (Mem64::rip_offset(0), vec![0x00, 0x00, 0x00, 0x00]),
+            (Mem64::rip_offset(1), vec![0x01, 0x00, 0x00, 0x00]),
+            (Mem64::rip_offset(-1), vec![0xFF, 0xFF, 0xFF, 0xFF]),
+            (Mem64::rip_offset(127), vec![0x7F, 0x00, 0x00, 0x00]),
+            (Mem64::rip_offset(-128), vec![0x80, 0xFF, 0xFF, 0xFF]),
+            (Mem64::rip_offset(i32::MAX), vec![0xFF, 0xFF, 0xFF, 0x7F]),
+            (Mem64::rip_offset(i32::MIN), vec![0x00, 0x00, 0x00, 0x80]),
+        ];
+
+        for (mem, disp) in cases {
+            // RIP相対は常に mode=0b00 + disp32
+            assert_eq!(mem.mode_bits(), 0b00, "{:?}", mem);
+            assert_eq!(mem.disp_bytes().bytes(), disp, "{:?}", mem);
+        }
+    }
+
+    #[test]
+    fn test_sib_disp() {
+        use Reg64::*;
+
+        let cases = [
+            (Mem64::sib(Some(RAX), 0, RDI, 0), 0b00, vec![]),
+            (Mem64::sib(Some(RAX), 1, RDI, 0), 0b01, vec![0x01]),
+            (Mem64::sib(Some(RAX), -1, RDI, 0), 0b01, vec![0xFF]),
+            (Mem64::sib(Some(RAX), 127, RDI, 0), 0b01, vec![0x7F]),
+            (Mem64::sib(Some(RAX), -128, RDI, 0), 0b01, vec![0x80]),
+            (
+                Mem64::sib(Some(RAX), 128, RDI, 0),
+                0b10,
+                vec![0x80, 0x00, 0x00, 0x00],
+            ),
+            (
+                Mem64::sib(Some(RAX), -129, RDI, 0),
+                0b10,
+                vec![0x7F, 0xFF, 0xFF, 0xFF],
+            ),
+            (
+                Mem64::sib(Some(RAX), i32::MIN, RDI, 0),
+                0b10,
+                vec![0x00, 0x00, 0x00, 0x80],
+            ),
+            // base が RBP/R13 の場合は disp8 = 0 を必要とする
+            (Mem64::sib(Some(RBP), 0, RAX, 3), 0b01, vec![0x00]),
+            (Mem64::sib(Some(R13), 0, RAX, 3), 0b01, vec![0x00]),
+            (Mem64::sib(Some(RBP), -128, RAX, 3), 0b01, vec![0x80]),
+            (Mem64::sib(Some(RBP), 127, RAX, 3), 0b01, vec![0x7F]),
+            (
+                Mem64::sib(Some(RBP), 128, RAX, 3),
+                0b10,
+                vec![0x80, 0x00, 0x00, 0x00],
+            ),
+            (
+                Mem64::sib(Some(R13), -129, RAX, 3),
+                0b10,
+                vec![0x7F, 0xFF, 0xFF, 0xFF],
+            ),
+            // base が無い場合は常に disp32
+            (
+                Mem64::sib(None, 0, RAX, 3),
+                0b00,
+                vec![0x00, 0x00, 0x00, 0x00],
+            ),
+            (
+                Mem64::sib(None, -8, RAX, 3),
+                0b00,
+                vec![0xF8, 0xFF, 0xFF, 0xFF],
+            ),
+            (
+                Mem64::sib(None, 127, RAX, 3),
+                0b00,
+                vec![0x7F, 0x00, 0x00, 0x00],
+            ),
+        ];
+
+        for (mem, mode, disp) in cases {
+            assert_eq!(mem.mode_bits(), mode, "{:?}", mem);
+            assert_eq!(mem.disp_bytes().bytes(), disp, "{:?}", mem);
+        }
+    }
+
+    #[test]
+    fn test_try_sib() {
+        use Reg64::*;
+
+        assert!(Mem64::try_sib(Some(RAX), 0, RDI, 3).is_ok());
+        assert!(Mem64::try_sib(None, 8, RSP, 0).is_ok());
+        assert_eq!(
+            Mem64::try_sib(Some(RAX), 0, RDI, 4),
+            Err(EncodeError::InvalidScale(4))
+        );
+        assert_eq!(
+            Mem64::try_sib(Some(RAX), 0, RSP, 0),
+            Err(EncodeError::RspAsIndex)
+        );
+        assert_eq!(
+            Mem64::try_sib(None, 0, RSP, 3),
+            Err(EncodeError::RspAsIndex)
+        );
+        // R12 は REX.X で区別されるため index に使える
+        assert!(Mem64::try_sib(Some(RAX), 0, R12, 1).is_ok());
+    }
+
+    #[test]
+    fn test_display() {
+        use Reg64::*;
+
+        let cases = [
+            (Mem64::reg(RDI), "[rdi]"),
+            (Mem64::reg_offset(RBP, -8), "[rbp - 0x8]"),
+            (Mem64::reg_offset(R12, 200), "[r12 + 0xc8]"),
+            (Mem64::rip_offset(42), "[rip + 0x2a]"),
+            (Mem64::sib(Some(RBP), 42, RAX, 3), "[rbp + rax*8 + 0x2a]"),
+            (Mem64::sib(Some(RAX), 0, R9, 0), "[rax + r9*1]"),
+            (Mem64::sib(None, -16, RDI, 2), "[rdi*4 - 0x10]"),
+            (Mem64::sib(None, 0x1000, RSP, 0), "[0x1000]"),
+            (Mem64::reg_offset(RAX, i32::MIN), "[rax - 0x80000000]"),
+        ];
+
+        for (mem, expected) in cases {
+            assert_eq!(mem.to_string(), expected);
+        }
+
+        assert_eq!(Ptr::dword(Mem64::reg(RSP)).to_string(), "dword ptr [rsp]");
+    }
+}
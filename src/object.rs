@@ -0,0 +1,144 @@
+//! オブジェクトファイル形式に依存しないセクション・シンボル・リロケーションの表現
+//!
+//! [`elf`](crate::elf) などの各形式の writer はこれをファイルに書き出す。
+
+/// The sections an [`Object`] can have; each format maps them to its own names.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SectionKind {
+    Text,
+    Data,
+    Rodata,
+    /// Zero-initialized data that takes no space in the file.
+    Bss,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub kind: SectionKind,
+    /// `Bss` では長さだけが使われる (中身は 0 として扱う)
+    pub data: Vec<u8>,
+    pub align: u64,
+    pub relocs: Vec<Reloc>,
+}
+
+impl Section {
+    /// `Text` は 16 byte、それ以外は 8 byte でアラインする
+    pub fn new(kind: SectionKind, data: Vec<u8>) -> Self {
+        let align = match kind {
+            SectionKind::Text => 16,
+            _ => 8,
+        };
+        Section {
+            kind,
+            data,
+            align,
+            relocs: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub name: String,
+    /// 定義されているセクションとその先頭からのオフセット。`None` は外部シンボル
+    pub def: Option<(SectionKind, u64)>,
+    pub global: bool,
+}
+
+impl Symbol {
+    pub fn local(name: &str, section: SectionKind, offset: u64) -> Self {
+        Symbol {
+            name: name.to_string(),
+            def: Some((section, offset)),
+            global: false,
+        }
+    }
+
+    pub fn global(name: &str, section: SectionKind, offset: u64) -> Self {
+        Symbol {
+            name: name.to_string(),
+            def: Some((section, offset)),
+            global: true,
+        }
+    }
+
+    /// A symbol defined in another object.
+    pub fn undefined(name: &str) -> Self {
+        Symbol {
+            name: name.to_string(),
+            def: None,
+            global: true,
+        }
+    }
+}
+
+/// How the linker fills in a relocated field.
+///
+/// The names follow ELF; other formats map them to their closest equivalent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RelocKind {
+    /// 32-bit `S + A - P` (`R_X86_64_PC32`).
+    Pc32,
+    /// 32-bit `L + A - P` through the PLT, for `call`/`jmp` (`R_X86_64_PLT32`).
+    Plt32,
+    /// 64-bit `S + A` (`R_X86_64_64`).
+    Abs64,
+    /// 32-bit `G + GOT + A - P` to the symbol's GOT entry (`R_X86_64_GOTPCREL`).
+    GotPcRel,
+}
+
+impl RelocKind {
+    pub fn is_pc_relative(&self) -> bool {
+        !matches!(self, RelocKind::Abs64)
+    }
+
+    /// 書き換えるフィールドのバイト数
+    pub fn size(&self) -> usize {
+        match self {
+            RelocKind::Abs64 => 8,
+            _ => 4,
+        }
+    }
+}
+
+/// A field the linker patches with the address of `symbol`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reloc {
+    /// Offset of the field from the start of the section.
+    pub offset: u64,
+    pub symbol: String,
+    pub kind: RelocKind,
+    /// ELF の RELA と同じく明示的な addend。PC 相対なら通常はフィールドから命令末尾までの距離の負数
+    pub addend: i64,
+}
+
+/// A relocatable object.
+///
+/// Each [`SectionKind`] appears at most once. Symbols referenced by a
+/// relocation but missing from `symbols` are written as undefined globals.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Object {
+    pub sections: Vec<Section>,
+    pub symbols: Vec<Symbol>,
+}
+
+impl Object {
+    pub fn new() -> Self {
+        Object::default()
+    }
+
+    pub fn section(&self, kind: SectionKind) -> Option<&Section> {
+        self.sections.iter().find(|section| section.kind == kind)
+    }
+
+    /// `symbols` に無いリロケーション先を外部シンボルとして加えた一覧
+    pub fn all_symbols(&self) -> Vec<Symbol> {
+        let mut symbols = self.symbols.clone();
+        for reloc in self.sections.iter().flat_map(|section| &section.relocs) {
+            if !symbols.iter().any(|sym| sym.name == reloc.symbol) {
+                symbols.push(Symbol::undefined(&reloc.symbol));
+            }
+        }
+        symbols
+    }
+}
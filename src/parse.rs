@@ -0,0 +1,1188 @@
+//! Intel 記法のアセンブリテキストのパーサ
+//!
+//! 1行に1命令で、`name:` でラベルを定義する。`;` と `#` から行末まではコメント。
+//! レジスタ名は `Display` の表示 (`rax`, `r8d`, `sil`, `ah` ...) と同じ。
+
+use crate::{
+    asm::{Assembler, Label},
+    instruction::{Alu, AluOp, Call, Cond, Jcc, Jmp, Lahf, Lea, Mov, Pop, Push, Ret, RetImm},
+    instruction::{Cdq, Cdqe, Cl, Cqo, Cwd, DoubleShift, DoubleShiftOp, Imul, ImulImm, MulDiv},
+    instruction::{MulOp, Sahf, Shift, ShiftOp, Syscall},
+    EncodeError, Instruction, Mem64, Ptr, Reg, Reg64, Reg8, Register, Size,
+};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    fmt::{Display, Error as FmtError, Formatter},
+};
+
+/// A parse error at a 1-based line and column.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub kind: ParseErrorKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A character or token that cannot appear here.
+    UnexpectedToken(String),
+    /// The line ended in the middle of an operand.
+    UnexpectedEnd,
+    UnknownMnemonic(String),
+    /// No form of the instruction takes these operands.
+    InvalidOperands,
+    /// A memory operand needs `byte ptr`, `word ptr`, ... to fix its size.
+    MissingSize,
+    /// The operands have different sizes.
+    OperandSizeMismatch,
+    /// The memory operand cannot be encoded (two bases, `rip` with an index, ...).
+    InvalidMem,
+    /// The scale is not 1, 2, 4 or 8.
+    InvalidScale(i128),
+    /// The immediate or displacement does not fit in its field.
+    OutOfRange(i128),
+    UndefinedLabel(String),
+    DuplicateLabel(String),
+    Encode(EncodeError),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        write!(f, "{}:{}: {}", self.line, self.column, self.kind)
+    }
+}
+
+impl Display for ParseErrorKind {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            ParseErrorKind::UnexpectedToken(token) => write!(f, "unexpected `{}`", token),
+            ParseErrorKind::UnexpectedEnd => write!(f, "unexpected end of line"),
+            ParseErrorKind::UnknownMnemonic(name) => write!(f, "unknown mnemonic `{}`", name),
+            ParseErrorKind::InvalidOperands => write!(f, "invalid operands"),
+            ParseErrorKind::MissingSize => write!(f, "operand size is not specified"),
+            ParseErrorKind::OperandSizeMismatch => write!(f, "operand size mismatch"),
+            ParseErrorKind::InvalidMem => write!(f, "invalid memory operand"),
+            ParseErrorKind::InvalidScale(scale) => write!(f, "invalid scale {}", scale),
+            ParseErrorKind::OutOfRange(value) => write!(f, "{:#x} is out of range", value),
+            ParseErrorKind::UndefinedLabel(name) => write!(f, "undefined label `{}`", name),
+            ParseErrorKind::DuplicateLabel(name) => write!(f, "label `{}` is defined twice", name),
+            ParseErrorKind::Encode(err) => err.fmt(f),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// パース結果の1文
+///
+/// ラベルを参照する命令はラベル名のまま保持し、[`emit`] で [`Assembler`] のラベルに変換する
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Stmt {
+    /// `name:`
+    Label(String),
+    Inst(Instruction),
+    /// `lea reg, [rip + name]`
+    Lea(Reg64, String),
+    /// `jmp name`
+    Jmp(String),
+    /// `jcc name`
+    Jcc(Cond, String),
+    /// `call name`
+    Call(String),
+    /// `.globl name`: オブジェクトファイルでラベルを外部に公開する
+    Global(String),
+}
+
+/// Parses a whole source text.
+///
+/// Every referenced label must be defined somewhere in `src`.
+///
+/// ```
+/// use at_64::{instruction::Mov, parse::{parse, Stmt}, Instruction, Mem64, Reg64::*};
+///
+/// let stmts = parse("mov rax, [rdi+42]").unwrap();
+/// assert_eq!(
+///     stmts,
+///     [Stmt::Inst(Instruction::from(Mov(RAX, Mem64::reg_offset(RDI, 42))))]
+/// );
+/// ```
+pub fn parse(src: &str) -> Result<Vec<Stmt>, ParseError> {
+    let mut stmts = Vec::new();
+    let mut defined = HashMap::new();
+    let mut refs = Vec::new();
+
+    for (i, text) in src.lines().enumerate() {
+        let line_no = i + 1;
+        let tokens = tokenize(text, line_no)?;
+        let mut line = Line::new(&tokens, line_no, text);
+
+        // ラベル定義
+        if let [Token {
+            tok: Tok::Ident(name),
+            col,
+        }, Token {
+            tok: Tok::Colon, ..
+        }, ..] = &tokens[..]
+        {
+            if defined.insert(name.clone(), *col).is_some() {
+                return Err(line.error(*col, ParseErrorKind::DuplicateLabel(name.clone())));
+            }
+            stmts.push(Stmt::Label(name.clone()));
+            line.pos = 2;
+        }
+
+        if line.is_end() {
+            continue;
+        }
+
+        let (stmt, label_ref) = line.stmt()?;
+        if let Some((name, col)) = label_ref {
+            refs.push((name, line_no, col));
+        }
+        stmts.push(stmt);
+    }
+
+    for (name, line, column) in refs {
+        if !defined.contains_key(&name) {
+            return Err(ParseError {
+                line,
+                column,
+                kind: ParseErrorKind::UndefinedLabel(name),
+            });
+        }
+    }
+
+    Ok(stmts)
+}
+
+/// Parses a single instruction that does not reference labels.
+pub fn parse_instruction(text: &str) -> Result<Instruction, ParseError> {
+    let tokens = tokenize(text, 1)?;
+    let mut line = Line::new(&tokens, 1, text);
+
+    match line.stmt()? {
+        (Stmt::Inst(inst), _) => Ok(inst),
+        (_, Some((name, col))) => Err(line.error(col, ParseErrorKind::UndefinedLabel(name))),
+        _ => Err(line.error(1, ParseErrorKind::InvalidOperands)),
+    }
+}
+
+/// 文を順に `asm` へ出力する
+///
+/// ラベル名と [`Label`] の対応は `labels` に記録され、複数回の呼び出しで共有できる
+pub fn emit(stmts: &[Stmt], asm: &mut Assembler, labels: &mut HashMap<String, Label>) {
+    for stmt in stmts {
+        match stmt {
+            Stmt::Label(name) => {
+                let label = label(labels, asm, name);
+                asm.bind(label);
+            }
+            Stmt::Inst(inst) => asm.emit(*inst),
+            Stmt::Lea(dst, name) => {
+                let label = label(labels, asm, name);
+                asm.emit(Lea(*dst, label));
+            }
+            Stmt::Jmp(name) => {
+                let label = label(labels, asm, name);
+                asm.emit(Jmp(label));
+            }
+            Stmt::Jcc(cond, name) => {
+                let label = label(labels, asm, name);
+                asm.emit(Jcc(*cond, label));
+            }
+            Stmt::Call(name) => {
+                let label = label(labels, asm, name);
+                asm.emit(Call(label));
+            }
+            Stmt::Global(_) => {}
+        }
+    }
+}
+
+impl Display for Stmt {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            Stmt::Label(name) => write!(f, "{}:", name),
+            Stmt::Inst(inst) => inst.fmt(f),
+            Stmt::Lea(dst, name) => write!(f, "lea {}, [rip + {}]", dst, name),
+            Stmt::Jmp(name) => write!(f, "jmp {}", name),
+            Stmt::Jcc(cond, name) => write!(f, "j{} {}", cond, name),
+            Stmt::Call(name) => write!(f, "call {}", name),
+            Stmt::Global(name) => write!(f, ".globl {}", name),
+        }
+    }
+}
+
+fn label(labels: &mut HashMap<String, Label>, asm: &mut Assembler, name: &str) -> Label {
+    *labels
+        .entry(name.to_string())
+        .or_insert_with(|| asm.new_label())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Tok {
+    Ident(String),
+    Num(i128),
+    Comma,
+    LBracket,
+    RBracket,
+    Plus,
+    Minus,
+    Star,
+    Colon,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Token {
+    tok: Tok,
+    /// 1-based
+    col: usize,
+}
+
+fn tokenize(text: &str, line: usize) -> Result<Vec<Token>, ParseError> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        let col = i + 1;
+
+        let tok = match c {
+            ';' | '#' => break,
+            _ if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ',' => Tok::Comma,
+            '[' => Tok::LBracket,
+            ']' => Tok::RBracket,
+            '+' => Tok::Plus,
+            '-' => Tok::Minus,
+            '*' => Tok::Star,
+            ':' => Tok::Colon,
+            _ if c.is_ascii_alphanumeric() || c == '_' || c == '.' => {
+                let start = i;
+                while i < chars.len() && is_word_char(chars[i]) {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+
+                let tok = if c.is_ascii_digit() {
+                    Tok::Num(parse_num(&word).ok_or_else(|| ParseError {
+                        line,
+                        column: col,
+                        kind: ParseErrorKind::UnexpectedToken(word.clone()),
+                    })?)
+                } else {
+                    Tok::Ident(word)
+                };
+                tokens.push(Token { tok, col });
+                continue;
+            }
+            _ => {
+                return Err(ParseError {
+                    line,
+                    column: col,
+                    kind: ParseErrorKind::UnexpectedToken(c.to_string()),
+                })
+            }
+        };
+
+        tokens.push(Token { tok, col });
+        i += 1;
+    }
+
+    Ok(tokens)
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '.'
+}
+
+/// 10進 または `0x` 付きの16進。u64 に収まる値のみ
+fn parse_num(word: &str) -> Option<i128> {
+    let n = match word.strip_prefix("0x").or_else(|| word.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16).ok()?,
+        None => word.parse::<u64>().ok()?,
+    };
+    Some(n as i128)
+}
+
+/// 名前が `Display` の表示と一致するレジスタ
+fn register(name: &str) -> Option<Reg> {
+    let name = name.to_ascii_lowercase();
+
+    [Size::Qword, Size::Dword, Size::Word, Size::Byte]
+        .iter()
+        .flat_map(|size| {
+            (0..16).flat_map(move |n| {
+                [false, true]
+                    .iter()
+                    .map(move |has_rex| Reg::from_bits(*size, n & 0b111, n >= 8, *has_rex))
+            })
+        })
+        .find(|reg| reg.to_string() == name)
+}
+
+fn cond(name: &str) -> Option<Cond> {
+    use Cond::*;
+
+    let cond = match name {
+        "o" => O,
+        "no" => NO,
+        "b" | "c" | "nae" => B,
+        "ae" | "nc" | "nb" => AE,
+        "e" | "z" => E,
+        "ne" | "nz" => NE,
+        "be" | "na" => BE,
+        "a" | "nbe" => A,
+        "s" => S,
+        "ns" => NS,
+        "p" | "pe" => P,
+        "np" | "po" => NP,
+        "l" | "nge" => L,
+        "ge" | "nl" => GE,
+        "le" | "ng" => LE,
+        "g" | "nle" => G,
+        _ => return None,
+    };
+    Some(cond)
+}
+
+fn alu_op(name: &str) -> Option<AluOp> {
+    AluOp::ALL.iter().copied().find(|op| op.to_string() == name)
+}
+
+fn mul_op(name: &str) -> Option<MulOp> {
+    MulOp::ALL.iter().copied().find(|op| op.to_string() == name)
+}
+
+/// `sal` は `shl` の別名
+fn shift_op(name: &str) -> Option<ShiftOp> {
+    match name {
+        "sal" => Some(ShiftOp::Shl),
+        _ => ShiftOp::ALL
+            .iter()
+            .copied()
+            .find(|op| op.to_string() == name),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Operand {
+    Reg(Reg),
+    Imm(i128),
+    Mem(Option<Size>, Mem64),
+    /// `[rip + name]`
+    RipLabel(String),
+    Label(String),
+}
+
+/// 1行分のトークン列
+struct Line<'a> {
+    tokens: &'a [Token],
+    pos: usize,
+    line: usize,
+    /// 行末のエラー位置
+    end_col: usize,
+}
+
+impl<'a> Line<'a> {
+    fn new(tokens: &'a [Token], line: usize, text: &str) -> Self {
+        Line {
+            tokens,
+            pos: 0,
+            line,
+            end_col: text.chars().count() + 1,
+        }
+    }
+
+    fn error(&self, column: usize, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            line: self.line,
+            column,
+            kind,
+        }
+    }
+
+    fn is_end(&self) -> bool {
+        self.pos >= self.tokens.len()
+    }
+
+    fn peek(&self) -> Option<&'a Tok> {
+        self.tokens.get(self.pos).map(|token| &token.tok)
+    }
+
+    fn col(&self) -> usize {
+        self.tokens
+            .get(self.pos)
+            .map(|token| token.col)
+            .unwrap_or(self.end_col)
+    }
+
+    fn next(&mut self) -> Result<(&'a Tok, usize), ParseError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| self.error(self.end_col, ParseErrorKind::UnexpectedEnd))?;
+        self.pos += 1;
+        Ok((&token.tok, token.col))
+    }
+
+    fn unexpected(&self, tok: &Tok, col: usize) -> ParseError {
+        let text = match tok {
+            Tok::Ident(name) => name.clone(),
+            Tok::Num(n) => n.to_string(),
+            Tok::Comma => ",".to_string(),
+            Tok::LBracket => "[".to_string(),
+            Tok::RBracket => "]".to_string(),
+            Tok::Plus => "+".to_string(),
+            Tok::Minus => "-".to_string(),
+            Tok::Star => "*".to_string(),
+            Tok::Colon => ":".to_string(),
+        };
+        self.error(col, ParseErrorKind::UnexpectedToken(text))
+    }
+
+    fn expect(&mut self, expected: Tok) -> Result<(), ParseError> {
+        let (tok, col) = self.next()?;
+        if *tok == expected {
+            Ok(())
+        } else {
+            Err(self.unexpected(tok, col))
+        }
+    }
+
+    /// 命令1つ。ラベルを参照する場合はその名前と位置も返す
+    fn stmt(&mut self) -> Result<(Stmt, Option<(String, usize)>), ParseError> {
+        let (mnemonic, mnemonic_col) = match self.next()? {
+            (Tok::Ident(name), col) => (name.to_ascii_lowercase(), col),
+            (tok, col) => return Err(self.unexpected(tok, col)),
+        };
+
+        let mut operands = Vec::new();
+        while !self.is_end() {
+            if !operands.is_empty() {
+                self.expect(Tok::Comma)?;
+            }
+            let col = self.col();
+            operands.push((self.operand()?, col));
+        }
+
+        let label_ref = operands.iter().find_map(|(operand, col)| match operand {
+            Operand::Label(name) | Operand::RipLabel(name) => Some((name.clone(), *col)),
+            _ => None,
+        });
+
+        // エラー位置はオペランドがあれば最初のオペランド
+        let col = operands
+            .first()
+            .map(|(_, col)| *col)
+            .unwrap_or(mnemonic_col);
+        let operands: Vec<Operand> = operands.into_iter().map(|(operand, _)| operand).collect();
+
+        let stmt = build(&mnemonic, operands).map_err(|kind| match kind {
+            ParseErrorKind::UnknownMnemonic(_) => self.error(mnemonic_col, kind),
+            _ => self.error(col, kind),
+        })?;
+
+        Ok((stmt, label_ref))
+    }
+
+    fn operand(&mut self) -> Result<Operand, ParseError> {
+        let (tok, col) = self.next()?;
+
+        match tok {
+            Tok::LBracket => self.mem(None),
+            Tok::Num(n) => Ok(Operand::Imm(*n)),
+            Tok::Minus => match self.next()? {
+                (Tok::Num(n), _) => Ok(Operand::Imm(-*n)),
+                (tok, col) => Err(self.unexpected(tok, col)),
+            },
+            Tok::Ident(name) => {
+                if let Some(size) = size_keyword(name) {
+                    match self.next()? {
+                        (Tok::Ident(ptr), _) if ptr.eq_ignore_ascii_case("ptr") => {}
+                        (tok, col) => return Err(self.unexpected(tok, col)),
+                    }
+                    self.expect(Tok::LBracket)?;
+                    return self.mem(Some(size));
+                }
+
+                match register(name) {
+                    Some(reg) => Ok(Operand::Reg(reg)),
+                    None => Ok(Operand::Label(name.clone())),
+                }
+            }
+            _ => Err(self.unexpected(tok, col)),
+        }
+    }
+
+    /// `[` の後から `]` まで
+    fn mem(&mut self, size: Option<Size>) -> Result<Operand, ParseError> {
+        let start_col = self.col();
+        let mut base = None;
+        let mut index: Option<(Reg64, i128)> = None;
+        let mut disp: i128 = 0;
+        let mut rip = false;
+        let mut label = None;
+
+        let mut negative = match self.peek() {
+            Some(Tok::Minus) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        };
+
+        loop {
+            let (tok, col) = self.next()?;
+            match tok {
+                Tok::Num(n) if self.peek() == Some(&Tok::Star) => {
+                    self.pos += 1;
+                    let reg = self.mem_reg()?;
+                    if negative || index.replace((reg, *n)).is_some() {
+                        return Err(self.error(col, ParseErrorKind::InvalidMem));
+                    }
+                }
+                Tok::Num(n) => disp += if negative { -*n } else { *n },
+                Tok::Ident(name) if name.eq_ignore_ascii_case("rip") => {
+                    if negative || rip {
+                        return Err(self.error(col, ParseErrorKind::InvalidMem));
+                    }
+                    rip = true;
+                }
+                Tok::Ident(name) if register(name).is_some() => {
+                    self.pos -= 1;
+                    let reg = self.mem_reg()?;
+                    if negative {
+                        return Err(self.error(col, ParseErrorKind::InvalidMem));
+                    }
+                    if self.peek() == Some(&Tok::Star) {
+                        self.pos += 1;
+                        let scale = match self.next()? {
+                            (Tok::Num(n), _) => *n,
+                            (tok, col) => return Err(self.unexpected(tok, col)),
+                        };
+                        if index.replace((reg, scale)).is_some() {
+                            return Err(self.error(col, ParseErrorKind::InvalidMem));
+                        }
+                    } else if base.is_none() {
+                        base = Some(reg);
+                    } else if index.is_none() {
+                        index = Some((reg, 1));
+                    } else {
+                        return Err(self.error(col, ParseErrorKind::InvalidMem));
+                    }
+                }
+                Tok::Ident(name) => {
+                    if negative || label.replace(name.clone()).is_some() {
+                        return Err(self.error(col, ParseErrorKind::InvalidMem));
+                    }
+                }
+                _ => return Err(self.unexpected(tok, col)),
+            }
+
+            match self.next()? {
+                (Tok::Plus, _) => negative = false,
+                (Tok::Minus, _) => negative = true,
+                (Tok::RBracket, _) => break,
+                (tok, col) => return Err(self.unexpected(tok, col)),
+            }
+        }
+
+        let invalid = |kind| Err(self.error(start_col, kind));
+
+        if rip {
+            return match (base, index, label) {
+                (None, None, None) => match i32::try_from(disp) {
+                    Ok(disp) => Ok(Operand::Mem(size, Mem64::rip_offset(disp))),
+                    Err(_) => invalid(ParseErrorKind::OutOfRange(disp)),
+                },
+                (None, None, Some(label)) if disp == 0 => Ok(Operand::RipLabel(label)),
+                _ => invalid(ParseErrorKind::InvalidMem),
+            };
+        }
+        if label.is_some() {
+            return invalid(ParseErrorKind::InvalidMem);
+        }
+
+        let disp = match i32::try_from(disp) {
+            Ok(disp) => disp,
+            Err(_) => return invalid(ParseErrorKind::OutOfRange(disp)),
+        };
+
+        let mem = match (base, index) {
+            (Some(base), None) => Mem64::reg_offset(base, disp),
+            (base, Some((index, scale))) => {
+                let scale = match scale {
+                    1 => 0,
+                    2 => 1,
+                    4 => 2,
+                    8 => 3,
+                    _ => return invalid(ParseErrorKind::InvalidScale(scale)),
+                };
+                // index が RSP の SIB は「index 無し」と同じエンコーディングになる
+                if index == Reg64::RSP {
+                    return invalid(ParseErrorKind::Encode(EncodeError::RspAsIndex));
+                }
+                match Mem64::try_sib(base, disp, index, scale) {
+                    Ok(mem) => mem,
+                    Err(err) => return invalid(ParseErrorKind::Encode(err)),
+                }
+            }
+            // [disp32]
+            (None, None) => Mem64::sib(None, disp, Reg64::RSP, 0),
+        };
+
+        Ok(Operand::Mem(size, mem))
+    }
+
+    /// アドレス計算に使う64bitレジスタ
+    fn mem_reg(&mut self) -> Result<Reg64, ParseError> {
+        match self.next()? {
+            (Tok::Ident(name), col) => match register(name) {
+                Some(Reg::Reg64(reg)) => Ok(reg),
+                _ => Err(self.error(col, ParseErrorKind::InvalidMem)),
+            },
+            (tok, col) => Err(self.unexpected(tok, col)),
+        }
+    }
+}
+
+fn size_keyword(name: &str) -> Option<Size> {
+    match name.to_ascii_lowercase().as_str() {
+        "byte" => Some(Size::Byte),
+        "word" => Some(Size::Word),
+        "dword" => Some(Size::Dword),
+        "qword" => Some(Size::Qword),
+        _ => None,
+    }
+}
+
+/// ニーモニックとオペランドから命令を組み立てる
+fn build(mnemonic: &str, operands: Vec<Operand>) -> Result<Stmt, ParseErrorKind> {
+    use Operand::*;
+
+    let inst = match (mnemonic, &operands[..]) {
+        ("mov", [Reg(dst), Reg(src)]) => Instruction::from(Mov(*dst, same_size(*dst, *src)?)),
+        ("mov", [Mem(size, dst), Reg(src)]) => Instruction::from(Mov(*dst, mem_size(*size, *src)?)),
+        ("mov", [Reg(dst), Mem(size, src)]) => Instruction::from(Mov(mem_size(*size, *dst)?, *src)),
+        ("mov", [Reg(dst), Imm(imm)]) => Instruction::from(Mov(*dst, reg_imm(dst.size(), *imm)?)),
+        ("mov", [Mem(size, dst), Imm(imm)]) => {
+            let size = size.ok_or(ParseErrorKind::MissingSize)?;
+            Instruction::from(Mov(Ptr(size, *dst), sized_imm(size, *imm)?))
+        }
+        ("lea", [Reg(dst), Mem(None, src)]) if dst.size() != Size::Byte => {
+            Instruction::from(Lea(*dst, *src))
+        }
+        ("lea", [Reg(crate::Reg::Reg64(dst)), RipLabel(name)]) => {
+            return Ok(Stmt::Lea(*dst, name.clone()))
+        }
+        (".globl" | ".global", [Label(name)]) => return Ok(Stmt::Global(name.clone())),
+        ("syscall", []) => Instruction::from(Syscall()),
+        ("lahf", []) => Instruction::from(Lahf()),
+        ("sahf", []) => Instruction::from(Sahf()),
+        ("cwd", []) => Instruction::from(Cwd()),
+        ("cdq", []) => Instruction::from(Cdq()),
+        ("cqo", []) => Instruction::from(Cqo()),
+        ("cdqe", []) => Instruction::from(Cdqe()),
+        ("ret", []) => Instruction::from(Ret()),
+        ("ret", [Imm(imm)]) => match u16::try_from(*imm) {
+            Ok(imm) => Instruction::from(RetImm(imm)),
+            Err(_) => return Err(ParseErrorKind::OutOfRange(*imm)),
+        },
+        ("jmp", [Label(name)]) => return Ok(Stmt::Jmp(name.clone())),
+        ("jmp", [Reg(crate::Reg::Reg64(dst))]) => Instruction::from(Jmp(*dst)),
+        ("jmp", [Mem(None | Some(Size::Qword), dst)]) => Instruction::from(Jmp(*dst)),
+        ("jmp", [Imm(rel)]) => match (i8::try_from(*rel), i32::try_from(*rel)) {
+            (Ok(rel), _) => Instruction::from(Jmp(rel)),
+            (_, Ok(rel)) => Instruction::from(Jmp(rel)),
+            _ => return Err(ParseErrorKind::OutOfRange(*rel)),
+        },
+        ("call", [Label(name)]) => return Ok(Stmt::Call(name.clone())),
+        ("call", [Reg(crate::Reg::Reg64(dst))]) => Instruction::from(Call(*dst)),
+        ("call", [Mem(None | Some(Size::Qword), dst)]) => Instruction::from(Call(*dst)),
+        ("call", [Imm(rel)]) => match i32::try_from(*rel) {
+            Ok(rel) => Instruction::from(Call(rel)),
+            Err(_) => return Err(ParseErrorKind::OutOfRange(*rel)),
+        },
+        ("push", [Reg(crate::Reg::Reg64(src))]) => Instruction::from(Push(*src)),
+        ("push", [Reg(crate::Reg::Reg16(src))]) => Instruction::from(Push(*src)),
+        ("push", [Mem(None, src)]) => Instruction::from(Push(*src)),
+        ("push", [Mem(Some(size), src)]) => Instruction::from(Push(Ptr(*size, *src))),
+        ("push", [Imm(imm)]) => match (i8::try_from(*imm), i32::try_from(*imm)) {
+            (Ok(imm), _) => Instruction::from(Push(imm)),
+            (_, Ok(imm)) => Instruction::from(Push(imm)),
+            _ => return Err(ParseErrorKind::OutOfRange(*imm)),
+        },
+        ("pop", [Reg(crate::Reg::Reg64(dst))]) => Instruction::from(Pop(*dst)),
+        ("pop", [Reg(crate::Reg::Reg16(dst))]) => Instruction::from(Pop(*dst)),
+        ("pop", [Mem(None, dst)]) => Instruction::from(Pop(*dst)),
+        ("pop", [Mem(Some(size), dst)]) => Instruction::from(Pop(Ptr(*size, *dst))),
+        (name, operands) if name.starts_with('j') && name != "jmp" => {
+            let cond = cond(&name[1..]).ok_or_else(unknown(name))?;
+            match operands {
+                [Label(name)] => return Ok(Stmt::Jcc(cond, name.clone())),
+                [Imm(rel)] => match (i8::try_from(*rel), i32::try_from(*rel)) {
+                    (Ok(rel), _) => Instruction::from(Jcc(cond, rel)),
+                    (_, Ok(rel)) => Instruction::from(Jcc(cond, rel)),
+                    _ => return Err(ParseErrorKind::OutOfRange(*rel)),
+                },
+                _ => return Err(ParseErrorKind::InvalidOperands),
+            }
+        }
+        (name, operands) if alu_op(name).is_some() => {
+            let op = alu_op(name).unwrap();
+            match operands {
+                [Reg(dst), Reg(src)] => Instruction::from(Alu(op, *dst, same_size(*dst, *src)?)),
+                [Mem(size, dst), Reg(src)] => {
+                    Instruction::from(Alu(op, *dst, mem_size(*size, *src)?))
+                }
+                [Reg(dst), Mem(size, src)] => {
+                    Instruction::from(Alu(op, mem_size(*size, *dst)?, *src))
+                }
+                [Reg(dst), Imm(imm)] => {
+                    Instruction::from(Alu(op, *dst, sized_imm(dst.size(), *imm)?))
+                }
+                [Mem(size, dst), Imm(imm)] => {
+                    let size = size.ok_or(ParseErrorKind::MissingSize)?;
+                    Instruction::from(Alu(op, Ptr(size, *dst), sized_imm(size, *imm)?))
+                }
+                _ => return Err(ParseErrorKind::InvalidOperands),
+            }
+        }
+        ("imul", [Reg(dst), ..]) if dst.size() == Size::Byte && operands.len() > 1 => {
+            return Err(ParseErrorKind::InvalidOperands)
+        }
+        ("imul", [Reg(dst), Reg(src)]) => Instruction::from(Imul(*dst, same_size(*dst, *src)?)),
+        ("imul", [Reg(dst), Mem(size, src)]) => {
+            Instruction::from(Imul(mem_size(*size, *dst)?, *src))
+        }
+        ("imul", [Reg(dst), Reg(src), Imm(imm)]) => Instruction::from(ImulImm(
+            *dst,
+            same_size(*dst, *src)?,
+            sized_imm(dst.size(), *imm)?,
+        )),
+        ("imul", [Reg(dst), Mem(size, src), Imm(imm)]) => Instruction::from(ImulImm(
+            mem_size(*size, *dst)?,
+            *src,
+            sized_imm(dst.size(), *imm)?,
+        )),
+        (name, operands) if mul_op(name).is_some() => {
+            let op = mul_op(name).unwrap();
+            match operands {
+                [Reg(src)] => Instruction::from(MulDiv(op, *src)),
+                [Mem(size, src)] => {
+                    let size = size.ok_or(ParseErrorKind::MissingSize)?;
+                    Instruction::from(MulDiv(op, Ptr(size, *src)))
+                }
+                _ => return Err(ParseErrorKind::InvalidOperands),
+            }
+        }
+        (name, operands) if shift_op(name).is_some() => {
+            let op = shift_op(name).unwrap();
+            match operands {
+                [Reg(dst), Reg(crate::Reg::Reg8(Reg8::CL))] => {
+                    Instruction::from(Shift(op, *dst, Cl))
+                }
+                [Reg(dst), Imm(count)] => Instruction::from(Shift(op, *dst, count_imm(*count)?)),
+                [Mem(size, dst), Reg(crate::Reg::Reg8(Reg8::CL))] => {
+                    let size = size.ok_or(ParseErrorKind::MissingSize)?;
+                    Instruction::from(Shift(op, Ptr(size, *dst), Cl))
+                }
+                [Mem(size, dst), Imm(count)] => {
+                    let size = size.ok_or(ParseErrorKind::MissingSize)?;
+                    Instruction::from(Shift(op, Ptr(size, *dst), count_imm(*count)?))
+                }
+                _ => return Err(ParseErrorKind::InvalidOperands),
+            }
+        }
+        (name @ ("shld" | "shrd"), operands) => {
+            let op = match name {
+                "shld" => DoubleShiftOp::Shld,
+                _ => DoubleShiftOp::Shrd,
+            };
+            match operands {
+                [_, Reg(src), _] if src.size() == Size::Byte => {
+                    return Err(ParseErrorKind::InvalidOperands)
+                }
+                [Reg(dst), Reg(src), Reg(crate::Reg::Reg8(Reg8::CL))] => {
+                    Instruction::from(DoubleShift(op, *dst, same_size(*dst, *src)?, Cl))
+                }
+                [Reg(dst), Reg(src), Imm(count)] => Instruction::from(DoubleShift(
+                    op,
+                    *dst,
+                    same_size(*dst, *src)?,
+                    count_imm(*count)?,
+                )),
+                [Mem(size, dst), Reg(src), Reg(crate::Reg::Reg8(Reg8::CL))] => {
+                    Instruction::from(DoubleShift(op, *dst, mem_size(*size, *src)?, Cl))
+                }
+                [Mem(size, dst), Reg(src), Imm(count)] => Instruction::from(DoubleShift(
+                    op,
+                    *dst,
+                    mem_size(*size, *src)?,
+                    count_imm(*count)?,
+                )),
+                _ => return Err(ParseErrorKind::InvalidOperands),
+            }
+        }
+        (
+            ".globl" | ".global" | "mov" | "lea" | "syscall" | "lahf" | "sahf" | "ret" | "jmp"
+            | "call" | "push" | "pop" | "cwd" | "cdq" | "cqo" | "cdqe",
+            _,
+        ) => return Err(ParseErrorKind::InvalidOperands),
+        (name, _) => return Err(unknown(name)()),
+    };
+
+    // AH と REX prefix の組み合わせなど、組み立てられてもエンコードできないもの
+    inst.try_bytecode().map_err(ParseErrorKind::Encode)?;
+
+    Ok(Stmt::Inst(inst))
+}
+
+fn unknown(name: &str) -> impl FnOnce() -> ParseErrorKind + '_ {
+    move || ParseErrorKind::UnknownMnemonic(name.to_string())
+}
+
+fn same_size(dst: Reg, src: Reg) -> Result<Reg, ParseErrorKind> {
+    if dst.size() == src.size() {
+        Ok(src)
+    } else {
+        Err(ParseErrorKind::OperandSizeMismatch)
+    }
+}
+
+/// `qword ptr [..]` などの指定はレジスタのサイズと一致すること
+fn mem_size(size: Option<Size>, reg: Reg) -> Result<Reg, ParseErrorKind> {
+    match size {
+        Some(size) if size != reg.size() => Err(ParseErrorKind::OperandSizeMismatch),
+        _ => Ok(reg),
+    }
+}
+
+/// `mov reg, imm` の即値。64bit は符号付き・符号無しのどちらの表記も受け付ける
+fn reg_imm(size: Size, imm: i128) -> Result<u64, ParseErrorKind> {
+    let in_range = match size {
+        Size::Qword => (i64::MIN as i128..=u64::MAX as i128).contains(&imm),
+        _ => i64::try_from(imm)
+            .map(|imm| size.fits(imm))
+            .unwrap_or(false),
+    };
+    if !in_range {
+        return Err(ParseErrorKind::OutOfRange(imm));
+    }
+
+    let mask = match size {
+        Size::Byte => 0xFF,
+        Size::Word => 0xFFFF,
+        Size::Dword => 0xFFFF_FFFF,
+        Size::Qword => u64::MAX,
+    };
+    Ok(imm as u64 & mask)
+}
+
+/// ALU や `mov mem, imm` の即値。デコーダと同じくオペランドサイズから符号拡張した値にする
+fn sized_imm(size: Size, imm: i128) -> Result<i32, ParseErrorKind> {
+    let in_range = match size {
+        Size::Qword => i32::try_from(imm).is_ok(),
+        _ => i64::try_from(imm)
+            .map(|imm| size.fits(imm))
+            .unwrap_or(false),
+    };
+    if !in_range {
+        return Err(ParseErrorKind::OutOfRange(imm));
+    }
+
+    let imm = match size {
+        Size::Byte => imm as i8 as i32,
+        Size::Word => imm as i16 as i32,
+        Size::Dword | Size::Qword => imm as i32,
+    };
+    Ok(imm)
+}
+
+/// シフト量の即値
+fn count_imm(imm: i128) -> Result<u8, ParseErrorKind> {
+    u8::try_from(imm).map_err(|_| ParseErrorKind::OutOfRange(imm))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{instruction::Cond::*, Reg16::*, Reg32::*, Reg64::*, Reg8::*};
+
+    #[test]
+    fn test_parse_instruction() {
+        let cases: [(&str, Instruction); 31] = [
+            (
+                "mov rax, [rdi+42]",
+                Mov(RAX, Mem64::reg_offset(RDI, 42)).into(),
+            ),
+            ("MOV RAX, RBX", Mov(RAX, RBX).into()),
+            ("mov eax, -1", Mov(EAX, 0xFFFF_FFFF_u32).into()),
+            ("mov al, ah", Mov(AL, AH).into()),
+            ("mov word ptr [rsp], si", Mov(Mem64::reg(RSP), SI).into()),
+            (
+                "mov qword ptr [rbp + rax*8 - 0x10], 1",
+                Mov(Ptr(Size::Qword, Mem64::sib(Some(RBP), -16, RAX, 3)), 1).into(),
+            ),
+            (
+                "mov ecx, [4*rdi]",
+                Mov(ECX, Mem64::sib(None, 0, RDI, 2)).into(),
+            ),
+            ("lea r8, [rip - 8]", Lea(R8, Mem64::rip_offset(-8)).into()),
+            (
+                "lea edx, [rax + r9]",
+                Lea(EDX, Mem64::sib(Some(RAX), 0, R9, 0)).into(),
+            ),
+            ("add rsp, 8", Alu(AluOp::Add, RSP, 8).into()),
+            (
+                "cmp byte ptr [rdi], 0xff",
+                Alu(AluOp::Cmp, Ptr(Size::Byte, Mem64::reg(RDI)), -1).into(),
+            ),
+            (
+                "xor r10d, dword ptr [r12]",
+                Alu(AluOp::Xor, R10D, Mem64::reg(R12)).into(),
+            ),
+            ("jne 0x10", Jcc(NE, 16_i8).into()),
+            ("jz 0x1000", Jcc(E, 0x1000).into()),
+            ("push -1", Push(-1_i8).into()),
+            ("pop qword ptr [rax]", Pop(Mem64::reg(RAX)).into()),
+            ("push ax", Push(AX).into()),
+            (
+                "pop word ptr [rbp - 2]",
+                Pop(Ptr::word(Mem64::reg_offset(RBP, -2))).into(),
+            ),
+            ("ret 16 ; comment", RetImm(16).into()),
+            ("syscall", Syscall().into()),
+            ("shl rax, 1", Shift(ShiftOp::Shl, RAX, 1).into()),
+            ("sal r8d, cl", Shift(ShiftOp::Shl, R8D, Cl).into()),
+            (
+                "sar word ptr [rdi], 3",
+                Shift(ShiftOp::Sar, Ptr(Size::Word, Mem64::reg(RDI)), 3).into(),
+            ),
+            (
+                "shld rax, rdx, 0x10",
+                DoubleShift(DoubleShiftOp::Shld, RAX, RDX, 16).into(),
+            ),
+            (
+                "shrd [rsi], ecx, cl",
+                DoubleShift(DoubleShiftOp::Shrd, Mem64::reg(RSI), ECX, Cl).into(),
+            ),
+            ("div rcx", MulDiv(MulOp::Div, RCX).into()),
+            (
+                "imul byte ptr [rax]",
+                MulDiv(MulOp::Imul, Ptr(Size::Byte, Mem64::reg(RAX))).into(),
+            ),
+            ("imul r8d, ecx", Imul(R8D, ECX).into()),
+            (
+                "imul rax, [rdi + 8]",
+                Imul(RAX, Mem64::reg_offset(RDI, 8)).into(),
+            ),
+            ("imul si, di, 0xffff", ImulImm(SI, DI, -1).into()),
+            ("cqo", Cqo().into()),
+        ];
+
+        for (text, expected) in cases {
+            assert_eq!(parse_instruction(text), Ok(expected), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_display_round_trip() {
+        let insts: [Instruction; 20] = [
+            Mov(R13, Mem64::sib(Some(R12), -300, R9, 1)).into(),
+            Mov(Mem64::reg(RBP), BPL).into(),
+            Mov(DIL, 0x80_u8).into(),
+            Mov(Ptr(Size::Word, Mem64::rip_offset(4)), 0x1234).into(),
+            Mov(RAX, 0x1234_5678_9ABC_DEF0_u64).into(),
+            Lea(RSI, Mem64::sib(None, 0x1000, RSP, 0)).into(),
+            Mov(RAX, Mem64::sib(None, i32::MIN, RSP, 0)).into(),
+            Alu(AluOp::Sbb, Mem64::reg(RAX), R11).into(),
+            Alu(AluOp::And, Ptr(Size::Dword, Mem64::reg(RCX)), -2).into(),
+            Jmp(Mem64::reg_offset(RAX, 8)).into(),
+            Call(R11).into(),
+            Shift(ShiftOp::Rcr, AH, 1).into(),
+            Shift(
+                ShiftOp::Rol,
+                Ptr(Size::Qword, Mem64::reg_offset(RBP, -8)),
+                Cl,
+            )
+            .into(),
+            DoubleShift(DoubleShiftOp::Shrd, R9W, R10W, 15).into(),
+            DoubleShift(DoubleShiftOp::Shld, Mem64::reg(RAX), RBX, Cl).into(),
+            MulDiv(MulOp::Idiv, Ptr(Size::Word, Mem64::reg_offset(RBP, -2))).into(),
+            Imul(EDX, Mem64::sib(Some(RAX), 4, RCX, 2)).into(),
+            ImulImm(R12, Mem64::rip_offset(0), -0x1000).into(),
+            Cdqe().into(),
+            Cwd().into(),
+        ];
+
+        for inst in insts {
+            assert_eq!(parse_instruction(&inst.to_string()), Ok(inst), "{}", inst);
+        }
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let cases = [
+            ("mov rax, [rdi+42", 1, 17, ParseErrorKind::UnexpectedEnd),
+            (
+                "mvo rax, rbx",
+                1,
+                1,
+                ParseErrorKind::UnknownMnemonic("mvo".to_string()),
+            ),
+            ("mov rax, ebx", 1, 5, ParseErrorKind::OperandSizeMismatch),
+            ("mov [rax], 1", 1, 5, ParseErrorKind::MissingSize),
+            (
+                "mov rax, [rbx + rcx*3]",
+                1,
+                11,
+                ParseErrorKind::InvalidScale(3),
+            ),
+            (
+                "mov rax, [rbx + rsp*2]",
+                1,
+                11,
+                ParseErrorKind::Encode(EncodeError::RspAsIndex),
+            ),
+            ("mov rax, [eax]", 1, 11, ParseErrorKind::InvalidMem),
+            (
+                "mov rax, [rsp*1 + 8]",
+                1,
+                11,
+                ParseErrorKind::Encode(EncodeError::RspAsIndex),
+            ),
+            (
+                "mov rax, [rax + rsp]",
+                1,
+                11,
+                ParseErrorKind::Encode(EncodeError::RspAsIndex),
+            ),
+            (
+                "mov rax, [rsp]\nlea rdx, [rsp*1]",
+                2,
+                11,
+                ParseErrorKind::Encode(EncodeError::RspAsIndex),
+            ),
+            (
+                "mov ah, sil",
+                1,
+                5,
+                ParseErrorKind::Encode(EncodeError::HighByteWithRex),
+            ),
+            (
+                "add ah, byte ptr [r8]",
+                1,
+                5,
+                ParseErrorKind::Encode(EncodeError::HighByteWithRex),
+            ),
+            (
+                "  shr bh, 1\n  mov qword ptr [rax], r8\n  xor ch, r9b",
+                3,
+                7,
+                ParseErrorKind::Encode(EncodeError::HighByteWithRex),
+            ),
+            (
+                "mov dh, byte ptr [rax + r10]",
+                1,
+                5,
+                ParseErrorKind::Encode(EncodeError::HighByteWithRex),
+            ),
+            (
+                "push dword ptr [rax]",
+                1,
+                6,
+                ParseErrorKind::Encode(EncodeError::InvalidOperandSize(Size::Dword)),
+            ),
+            ("pop eax", 1, 5, ParseErrorKind::InvalidOperands),
+            ("add al, 0x100", 1, 5, ParseErrorKind::OutOfRange(0x100)),
+            ("shl rax, 256", 1, 5, ParseErrorKind::OutOfRange(256)),
+            ("shl rax, dl", 1, 5, ParseErrorKind::InvalidOperands),
+            ("shld al, bl, 1", 1, 6, ParseErrorKind::InvalidOperands),
+            ("mul [rax]", 1, 5, ParseErrorKind::MissingSize),
+            ("imul al, bl", 1, 6, ParseErrorKind::InvalidOperands),
+            (
+                "imul ax, cx, 0x10000",
+                1,
+                6,
+                ParseErrorKind::OutOfRange(0x10000),
+            ),
+            ("cqo rax", 1, 5, ParseErrorKind::InvalidOperands),
+            (
+                "ret\n  mov rax, $1",
+                2,
+                12,
+                ParseErrorKind::UnexpectedToken("$".to_string()),
+            ),
+            (
+                "start:\n  jmp end",
+                2,
+                7,
+                ParseErrorKind::UndefinedLabel("end".to_string()),
+            ),
+            (
+                "a:\na:",
+                2,
+                1,
+                ParseErrorKind::DuplicateLabel("a".to_string()),
+            ),
+        ];
+
+        for (text, line, column, kind) in cases {
+            let expected = ParseError { line, column, kind };
+            assert_eq!(parse(text), Err(expected), "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_emit() {
+        let src = "
+            .globl loop
+            ; rdi = str
+            lea rsi, [rip + msg]
+            xor eax, eax
+        loop:
+            cmp byte ptr [rdi + rax], 0
+            je done
+            add rax, 1
+            jmp loop
+        done:
+            call msg
+            ret
+        msg:
+            ret
+        ";
+
+        let stmts = parse(src).unwrap();
+        assert_eq!(stmts[0], Stmt::Global("loop".to_string()));
+        assert_eq!(stmts[5].to_string(), "je done");
+
+        let mut asm = Assembler::new();
+        let mut labels = HashMap::new();
+        emit(&stmts, &mut asm, &mut labels);
+
+        let assembled = asm.assemble().unwrap();
+        assert_eq!(assembled.labels.get(labels["loop"]), Some(9));
+        assert_eq!(
+            assembled.code,
+            [
+                0x48, 0x8D, 0x35, 0x14, 0x00, 0x00, 0x00, // lea rsi, [rip + 0x14]
+                0x31, 0xC0, // xor eax, eax
+                0x80, 0x3C, 0x07, 0x00, // cmp byte ptr [rdi + rax*1], 0
+                0x74, 0x06, // je done
+                0x48, 0x83, 0xC0, 0x01, // add rax, 1
+                0xEB, 0xF4, // jmp loop
+                0xE8, 0x01, 0x00, 0x00, 0x00, // call msg
+                0xC3, // ret
+                0xC3, // ret
+            ]
+        );
+    }
+}
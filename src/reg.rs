@@ -1,5 +1,89 @@
 use std::fmt::{Display, Error as FmtError, Formatter};
 
+/// オペランドサイズ
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Size {
+    Byte,
+    Word,
+    Dword,
+    Qword,
+}
+
+impl Size {
+    pub fn bytes(&self) -> usize {
+        match self {
+            Size::Byte => 1,
+            Size::Word => 2,
+            Size::Dword => 4,
+            Size::Qword => 8,
+        }
+    }
+
+    /// 16bit オペランドには operand-size prefix (0x66) が必要
+    pub fn prefix(&self) -> Option<u8> {
+        match self {
+            Size::Word => Some(0x66),
+            _ => None,
+        }
+    }
+
+    /// 64bit オペランドには REX.W が必要
+    pub fn rex_w(&self) -> bool {
+        *self == Size::Qword
+    }
+
+    /// 即値が符号付き・符号無しのいずれかとしてこのサイズに収まるか
+    pub fn fits(&self, imm: i64) -> bool {
+        match self {
+            Size::Byte => (-0x80..=0xFF).contains(&imm),
+            Size::Word => (-0x8000..=0xFFFF).contains(&imm),
+            Size::Dword => (-0x8000_0000..=0xFFFF_FFFF).contains(&imm),
+            Size::Qword => true,
+        }
+    }
+}
+
+/// `byte ptr [..]` などのサイズ指定子
+impl Display for Size {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self {
+            Size::Byte => write!(f, "byte"),
+            Size::Word => write!(f, "word"),
+            Size::Dword => write!(f, "dword"),
+            Size::Qword => write!(f, "qword"),
+        }
+    }
+}
+
+/// 汎用レジスタに共通の操作
+pub trait Register: Copy {
+    fn size(&self) -> Size;
+
+    fn rex_r_bit(&self) -> bool;
+
+    fn rex_b_bit(&self) -> bool;
+
+    fn mode_bits(&self) -> u8 {
+        0b11
+    }
+
+    fn reg_bits(&self) -> u8;
+
+    fn rm_bits(&self) -> u8 {
+        self.reg_bits()
+    }
+
+    /// SPL/BPL/SIL/DIL は (空であっても) REX prefix が無いと AH/CH/DH/BH になる
+    fn requires_rex(&self) -> bool {
+        false
+    }
+
+    /// AH/CH/DH/BH は REX prefix があるとエンコードできない
+    fn is_high_byte(&self) -> bool {
+        false
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Reg {
     Reg64(Reg64),
@@ -8,6 +92,94 @@ pub enum Reg {
     Reg8(Reg8),
 }
 
+impl Reg {
+    /// ModR/M, SIB の3bitフィールドと REX からレジスタを得る
+    pub fn from_bits(size: Size, bits: u8, rex_bit: bool, has_rex: bool) -> Self {
+        match size {
+            Size::Byte => Reg::Reg8(Reg8::from_bits(bits, rex_bit, has_rex)),
+            Size::Word => Reg::Reg16(Reg16::from_bits(bits, rex_bit)),
+            Size::Dword => Reg::Reg32(Reg32::from_bits(bits, rex_bit)),
+            Size::Qword => Reg::Reg64(Reg64::from_bits(bits, rex_bit)),
+        }
+    }
+}
+
+impl Register for Reg {
+    fn size(&self) -> Size {
+        match self {
+            Reg::Reg64(reg) => reg.size(),
+            Reg::Reg32(reg) => reg.size(),
+            Reg::Reg16(reg) => reg.size(),
+            Reg::Reg8(reg) => reg.size(),
+        }
+    }
+
+    fn rex_r_bit(&self) -> bool {
+        match self {
+            Reg::Reg64(reg) => reg.rex_r_bit(),
+            Reg::Reg32(reg) => reg.rex_r_bit(),
+            Reg::Reg16(reg) => reg.rex_r_bit(),
+            Reg::Reg8(reg) => reg.rex_r_bit(),
+        }
+    }
+
+    fn rex_b_bit(&self) -> bool {
+        match self {
+            Reg::Reg64(reg) => reg.rex_b_bit(),
+            Reg::Reg32(reg) => reg.rex_b_bit(),
+            Reg::Reg16(reg) => reg.rex_b_bit(),
+            Reg::Reg8(reg) => reg.rex_b_bit(),
+        }
+    }
+
+    fn reg_bits(&self) -> u8 {
+        match self {
+            Reg::Reg64(reg) => reg.reg_bits(),
+            Reg::Reg32(reg) => reg.reg_bits(),
+            Reg::Reg16(reg) => reg.reg_bits(),
+            Reg::Reg8(reg) => reg.reg_bits(),
+        }
+    }
+
+    fn requires_rex(&self) -> bool {
+        match self {
+            Reg::Reg8(reg) => reg.requires_rex(),
+            _ => false,
+        }
+    }
+
+    fn is_high_byte(&self) -> bool {
+        match self {
+            Reg::Reg8(reg) => reg.is_high_byte(),
+            _ => false,
+        }
+    }
+}
+
+impl From<Reg64> for Reg {
+    fn from(reg: Reg64) -> Self {
+        Reg::Reg64(reg)
+    }
+}
+
+impl From<Reg32> for Reg {
+    fn from(reg: Reg32) -> Self {
+        Reg::Reg32(reg)
+    }
+}
+
+impl From<Reg16> for Reg {
+    fn from(reg: Reg16) -> Self {
+        Reg::Reg16(reg)
+    }
+}
+
+impl From<Reg8> for Reg {
+    fn from(reg: Reg8) -> Self {
+        Reg::Reg8(reg)
+    }
+}
+
 impl Display for Reg {
     fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
         match self {
@@ -56,6 +228,30 @@ pub enum Reg64 {
 }
 
 impl Reg64 {
+    /// ModR/M, SIB の3bitフィールドと REX の拡張bitからレジスタを得る
+    pub fn from_bits(bits: u8, rex_bit: bool) -> Self {
+        use Reg64::*;
+
+        match (rex_bit, bits & 0b111) {
+            (false, 0b000) => RAX,
+            (false, 0b001) => RCX,
+            (false, 0b010) => RDX,
+            (false, 0b011) => RBX,
+            (false, 0b100) => RSP,
+            (false, 0b101) => RBP,
+            (false, 0b110) => RSI,
+            (false, _) => RDI,
+            (true, 0b000) => R8,
+            (true, 0b001) => R9,
+            (true, 0b010) => R10,
+            (true, 0b011) => R11,
+            (true, 0b100) => R12,
+            (true, 0b101) => R13,
+            (true, 0b110) => R14,
+            (true, _) => R15,
+        }
+    }
+
     pub fn rex_r_bit(&self) -> bool {
         use Reg64::*;
 
@@ -109,6 +305,24 @@ impl Reg64 {
     }
 }
 
+impl Register for Reg64 {
+    fn size(&self) -> Size {
+        Size::Qword
+    }
+
+    fn rex_r_bit(&self) -> bool {
+        Reg64::rex_r_bit(self)
+    }
+
+    fn rex_b_bit(&self) -> bool {
+        Reg64::rex_b_bit(self)
+    }
+
+    fn reg_bits(&self) -> u8 {
+        Reg64::reg_bits(self)
+    }
+}
+
 impl Display for Reg64 {
     fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
         match self {
@@ -168,6 +382,64 @@ pub enum Reg32 {
     R15D,
 }
 
+impl Reg32 {
+    pub fn from_bits(bits: u8, rex_bit: bool) -> Self {
+        use Reg32::*;
+
+        match (rex_bit, bits & 0b111) {
+            (false, 0b000) => EAX,
+            (false, 0b001) => ECX,
+            (false, 0b010) => EDX,
+            (false, 0b011) => EBX,
+            (false, 0b100) => ESP,
+            (false, 0b101) => EBP,
+            (false, 0b110) => ESI,
+            (false, _) => EDI,
+            (true, 0b000) => R8D,
+            (true, 0b001) => R9D,
+            (true, 0b010) => R10D,
+            (true, 0b011) => R11D,
+            (true, 0b100) => R12D,
+            (true, 0b101) => R13D,
+            (true, 0b110) => R14D,
+            (true, _) => R15D,
+        }
+    }
+}
+
+impl Register for Reg32 {
+    fn size(&self) -> Size {
+        Size::Dword
+    }
+
+    fn rex_r_bit(&self) -> bool {
+        use Reg32::*;
+
+        matches!(self, R8D | R9D | R10D | R11D | R12D | R13D | R14D | R15D)
+    }
+
+    fn rex_b_bit(&self) -> bool {
+        use Reg32::*;
+
+        matches!(self, R8D | R9D | R10D | R11D | R12D | R13D | R14D | R15D)
+    }
+
+    fn reg_bits(&self) -> u8 {
+        use Reg32::*;
+
+        match self {
+            EAX | R8D => 0b000,
+            ECX | R9D => 0b001,
+            EDX | R10D => 0b010,
+            EBX | R11D => 0b011,
+            ESP | R12D => 0b100,
+            EBP | R13D => 0b101,
+            ESI | R14D => 0b110,
+            EDI | R15D => 0b111,
+        }
+    }
+}
+
 impl Display for Reg32 {
     fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
         match self {
@@ -227,6 +499,64 @@ pub enum Reg16 {
     R15W,
 }
 
+impl Reg16 {
+    pub fn from_bits(bits: u8, rex_bit: bool) -> Self {
+        use Reg16::*;
+
+        match (rex_bit, bits & 0b111) {
+            (false, 0b000) => AX,
+            (false, 0b001) => CX,
+            (false, 0b010) => DX,
+            (false, 0b011) => BX,
+            (false, 0b100) => SP,
+            (false, 0b101) => BP,
+            (false, 0b110) => SI,
+            (false, _) => DI,
+            (true, 0b000) => R8W,
+            (true, 0b001) => R9W,
+            (true, 0b010) => R10W,
+            (true, 0b011) => R11W,
+            (true, 0b100) => R12W,
+            (true, 0b101) => R13W,
+            (true, 0b110) => R14W,
+            (true, _) => R15W,
+        }
+    }
+}
+
+impl Register for Reg16 {
+    fn size(&self) -> Size {
+        Size::Word
+    }
+
+    fn rex_r_bit(&self) -> bool {
+        use Reg16::*;
+
+        matches!(self, R8W | R9W | R10W | R11W | R12W | R13W | R14W | R15W)
+    }
+
+    fn rex_b_bit(&self) -> bool {
+        use Reg16::*;
+
+        matches!(self, R8W | R9W | R10W | R11W | R12W | R13W | R14W | R15W)
+    }
+
+    fn reg_bits(&self) -> u8 {
+        use Reg16::*;
+
+        match self {
+            AX | R8W => 0b000,
+            CX | R9W => 0b001,
+            DX | R10W => 0b010,
+            BX | R11W => 0b011,
+            SP | R12W => 0b100,
+            BP | R13W => 0b101,
+            SI | R14W => 0b110,
+            DI | R15W => 0b111,
+        }
+    }
+}
+
 impl Display for Reg16 {
     fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
         match self {
@@ -284,6 +614,89 @@ pub enum Reg8 {
     R14B,
     /// Lower 8-bits of R15 Register
     R15B,
+    /// Bits 8-15 of RAX Register
+    AH,
+    /// Bits 8-15 of RCX Register
+    CH,
+    /// Bits 8-15 of RDX Register
+    DH,
+    /// Bits 8-15 of RBX Register
+    BH,
+}
+
+impl Reg8 {
+    /// REX prefix が無い場合、0b100 ~ 0b111 は SPL/BPL/SIL/DIL ではなく AH/CH/DH/BH を指す
+    pub fn from_bits(bits: u8, rex_bit: bool, has_rex: bool) -> Self {
+        use Reg8::*;
+
+        match (rex_bit, bits & 0b111) {
+            (false, 0b000) => AL,
+            (false, 0b001) => CL,
+            (false, 0b010) => DL,
+            (false, 0b011) => BL,
+            (false, 0b100) if !has_rex => AH,
+            (false, 0b101) if !has_rex => CH,
+            (false, 0b110) if !has_rex => DH,
+            (false, 0b111) if !has_rex => BH,
+            (false, 0b100) => SPL,
+            (false, 0b101) => BPL,
+            (false, 0b110) => SIL,
+            (false, _) => DIL,
+            (true, 0b000) => R8B,
+            (true, 0b001) => R9B,
+            (true, 0b010) => R10B,
+            (true, 0b011) => R11B,
+            (true, 0b100) => R12B,
+            (true, 0b101) => R13B,
+            (true, 0b110) => R14B,
+            (true, _) => R15B,
+        }
+    }
+}
+
+impl Register for Reg8 {
+    fn size(&self) -> Size {
+        Size::Byte
+    }
+
+    fn rex_r_bit(&self) -> bool {
+        use Reg8::*;
+
+        matches!(self, R8B | R9B | R10B | R11B | R12B | R13B | R14B | R15B)
+    }
+
+    fn rex_b_bit(&self) -> bool {
+        use Reg8::*;
+
+        matches!(self, R8B | R9B | R10B | R11B | R12B | R13B | R14B | R15B)
+    }
+
+    fn reg_bits(&self) -> u8 {
+        use Reg8::*;
+
+        match self {
+            AL | R8B => 0b000,
+            CL | R9B => 0b001,
+            DL | R10B => 0b010,
+            BL | R11B => 0b011,
+            SPL | R12B | AH => 0b100,
+            BPL | R13B | CH => 0b101,
+            SIL | R14B | DH => 0b110,
+            DIL | R15B | BH => 0b111,
+        }
+    }
+
+    fn requires_rex(&self) -> bool {
+        use Reg8::*;
+
+        matches!(self, SPL | BPL | SIL | DIL)
+    }
+
+    fn is_high_byte(&self) -> bool {
+        use Reg8::*;
+
+        matches!(self, AH | CH | DH | BH)
+    }
 }
 
 impl Display for Reg8 {
@@ -305,6 +718,10 @@ impl Display for Reg8 {
             Reg8::R13B => write!(f, "r13b"),
             Reg8::R14B => write!(f, "r14b"),
             Reg8::R15B => write!(f, "r15b"),
+            Reg8::AH => write!(f, "ah"),
+            Reg8::CH => write!(f, "ch"),
+            Reg8::DH => write!(f, "dh"),
+            Reg8::BH => write!(f, "bh"),
         }
     }
 }
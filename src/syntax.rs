@@ -0,0 +1,695 @@
+//! Intel 記法と AT&T 記法のプリンタ
+//!
+//! [`Format::display`] に [`Syntax`] を渡すと、同じ命令・メモリオペランドを
+//! どちらの記法でも表示できる。`Display` は Intel 記法になる。
+
+use crate::{
+    instruction::{
+        Alu, Call, DoubleShift, Imul, ImulImm, Instruction, Jcc, Jmp, Lea, Mov, MulDiv, Pop, Push,
+        RetImm, Shift,
+    },
+    Mem64, Ptr, Reg64, Register, Size,
+};
+use std::fmt::{Display, Error as FmtError, Formatter};
+
+/// アセンブリ記法
+pub trait Syntax {
+    fn fmt_instruction(&self, inst: &Instruction, f: &mut Formatter) -> Result<(), FmtError>;
+
+    fn fmt_mem(&self, mem: &Mem64, f: &mut Formatter) -> Result<(), FmtError>;
+}
+
+/// 記法を選んで表示できる値
+pub trait Format {
+    fn fmt_with(&self, syntax: &dyn Syntax, f: &mut Formatter) -> Result<(), FmtError>;
+
+    /// ```
+    /// use at_64::{instruction::Mov, syntax::{Att, Format as _}, Mem64, Reg64::*};
+    ///
+    /// let mov = Mov(Mem64::sib(Some(RBP), 42, RAX, 3), R13);
+    /// assert_eq!(mov.to_string(), "mov qword ptr [rbp + rax*8 + 0x2a], r13");
+    /// assert_eq!(mov.display(&Att).to_string(), "movq %r13, 0x2a(%rbp,%rax,8)");
+    /// ```
+    fn display<'a>(&'a self, syntax: &'a dyn Syntax) -> Formatted<'a, Self> {
+        Formatted {
+            value: self,
+            syntax,
+        }
+    }
+}
+
+/// [`Format::display`] の戻り値
+pub struct Formatted<'a, T: ?Sized> {
+    value: &'a T,
+    syntax: &'a dyn Syntax,
+}
+
+impl<'a, T: Format + ?Sized> Display for Formatted<'a, T> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        self.value.fmt_with(self.syntax, f)
+    }
+}
+
+impl<T: Into<Instruction> + Copy> Format for T {
+    fn fmt_with(&self, syntax: &dyn Syntax, f: &mut Formatter) -> Result<(), FmtError> {
+        syntax.fmt_instruction(&(*self).into(), f)
+    }
+}
+
+impl Format for Mem64 {
+    fn fmt_with(&self, syntax: &dyn Syntax, f: &mut Formatter) -> Result<(), FmtError> {
+        syntax.fmt_mem(self, f)
+    }
+}
+
+/// Intel 記法 (`mov qword ptr [rbp + rax*8 + 0x2a], r13`)
+///
+/// 即値は16進で表示する。分岐の rel8 / rel32 は命令末尾からの相対距離をそのまま表示する
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Intel;
+
+impl Syntax for Intel {
+    fn fmt_instruction(&self, inst: &Instruction, f: &mut Formatter) -> Result<(), FmtError> {
+        match inst {
+            Instruction::MovRegReg(Mov(dst, src)) => write!(f, "mov {}, {}", dst, src),
+            Instruction::MovMemReg(Mov(dst, src)) => {
+                write!(f, "mov {}, {}", Ptr(src.size(), *dst), src)
+            }
+            Instruction::MovRegMem(Mov(dst, src)) => {
+                write!(f, "mov {}, {}", dst, Ptr(dst.size(), *src))
+            }
+            Instruction::MovRegImm(Mov(dst, src)) => write!(f, "mov {}, {:#x}", dst, src),
+            Instruction::MovMemImm(Mov(dst, src)) => write!(f, "mov {}, {}", dst, Hex(*src)),
+            Instruction::Lea(Lea(dst, src)) => write!(f, "lea {}, {}", dst, src),
+            Instruction::Syscall(_) => write!(f, "syscall"),
+            Instruction::JmpI8(Jmp(rel)) => write!(f, "jmp {}", Hex(*rel)),
+            Instruction::JmpI32(Jmp(rel)) => write!(f, "jmp {}", Hex(*rel)),
+            Instruction::JmpReg64(Jmp(dst)) => write!(f, "jmp {}", dst),
+            Instruction::JmpMem64(Jmp(dst)) => write!(f, "jmp {}", Ptr::qword(*dst)),
+            Instruction::JccI8(Jcc(cond, rel)) => write!(f, "j{} {}", cond, Hex(*rel)),
+            Instruction::JccI32(Jcc(cond, rel)) => write!(f, "j{} {}", cond, Hex(*rel)),
+            Instruction::CallI32(Call(rel)) => write!(f, "call {}", Hex(*rel)),
+            Instruction::CallReg64(Call(dst)) => write!(f, "call {}", dst),
+            Instruction::CallMem64(Call(dst)) => write!(f, "call {}", Ptr::qword(*dst)),
+            Instruction::Ret(_) => write!(f, "ret"),
+            Instruction::RetImm(RetImm(imm)) => write!(f, "ret {:#x}", imm),
+            Instruction::PushReg64(Push(src)) => write!(f, "push {}", src),
+            Instruction::PushReg16(Push(src)) => write!(f, "push {}", src),
+            Instruction::PushMem(Push(src)) => write!(f, "push {}", src),
+            Instruction::PushI8(Push(imm)) => write!(f, "push {}", Hex(*imm)),
+            Instruction::PushI32(Push(imm)) => write!(f, "push {}", Hex(*imm)),
+            Instruction::PopReg64(Pop(dst)) => write!(f, "pop {}", dst),
+            Instruction::PopReg16(Pop(dst)) => write!(f, "pop {}", dst),
+            Instruction::PopMem(Pop(dst)) => write!(f, "pop {}", dst),
+            Instruction::AluRegReg(Alu(op, dst, src)) => write!(f, "{} {}, {}", op, dst, src),
+            Instruction::AluMemReg(Alu(op, dst, src)) => {
+                write!(f, "{} {}, {}", op, Ptr(src.size(), *dst), src)
+            }
+            Instruction::AluRegMem(Alu(op, dst, src)) => {
+                write!(f, "{} {}, {}", op, dst, Ptr(dst.size(), *src))
+            }
+            Instruction::AluRegImm(Alu(op, dst, src)) => {
+                write!(f, "{} {}, {}", op, dst, Hex(*src))
+            }
+            Instruction::AluMemImm(Alu(op, dst, src)) => {
+                write!(f, "{} {}, {}", op, dst, Hex(*src))
+            }
+            Instruction::ShiftRegImm(Shift(op, dst, count)) => {
+                write!(f, "{} {}, {:#x}", op, dst, count)
+            }
+            Instruction::ShiftRegCl(Shift(op, dst, count)) => {
+                write!(f, "{} {}, {}", op, dst, count)
+            }
+            Instruction::ShiftMemImm(Shift(op, dst, count)) => {
+                write!(f, "{} {}, {:#x}", op, dst, count)
+            }
+            Instruction::ShiftMemCl(Shift(op, dst, count)) => {
+                write!(f, "{} {}, {}", op, dst, count)
+            }
+            Instruction::DoubleShiftRegImm(DoubleShift(op, dst, src, count)) => {
+                write!(f, "{} {}, {}, {:#x}", op, dst, src, count)
+            }
+            Instruction::DoubleShiftRegCl(DoubleShift(op, dst, src, count)) => {
+                write!(f, "{} {}, {}, {}", op, dst, src, count)
+            }
+            Instruction::DoubleShiftMemImm(DoubleShift(op, dst, src, count)) => {
+                write!(f, "{} {}, {}, {:#x}", op, Ptr(src.size(), *dst), src, count)
+            }
+            Instruction::DoubleShiftMemCl(DoubleShift(op, dst, src, count)) => {
+                write!(f, "{} {}, {}, {}", op, Ptr(src.size(), *dst), src, count)
+            }
+            Instruction::MulDivReg(MulDiv(op, src)) => write!(f, "{} {}", op, src),
+            Instruction::MulDivMem(MulDiv(op, src)) => write!(f, "{} {}", op, src),
+            Instruction::ImulRegReg(Imul(dst, src)) => write!(f, "imul {}, {}", dst, src),
+            Instruction::ImulRegMem(Imul(dst, src)) => {
+                write!(f, "imul {}, {}", dst, Ptr(dst.size(), *src))
+            }
+            Instruction::ImulRegRegImm(ImulImm(dst, src, imm)) => {
+                write!(f, "imul {}, {}, {}", dst, src, Hex(*imm))
+            }
+            Instruction::ImulRegMemImm(ImulImm(dst, src, imm)) => {
+                write!(f, "imul {}, {}, {}", dst, Ptr(dst.size(), *src), Hex(*imm))
+            }
+            Instruction::Cwd(_) => write!(f, "cwd"),
+            Instruction::Cdq(_) => write!(f, "cdq"),
+            Instruction::Cqo(_) => write!(f, "cqo"),
+            Instruction::Cdqe(_) => write!(f, "cdqe"),
+            Instruction::Lahf(_) => write!(f, "lahf"),
+            Instruction::Sahf(_) => write!(f, "sahf"),
+        }
+    }
+
+    fn fmt_mem(&self, mem: &Mem64, f: &mut Formatter) -> Result<(), FmtError> {
+        match mem {
+            Mem64::RegOffset(reg, disp) => {
+                write!(f, "[{}", reg)?;
+                fmt_disp(f, *disp)?;
+            }
+            Mem64::RipOffset(disp) => {
+                write!(f, "[rip")?;
+                fmt_disp(f, *disp)?;
+            }
+            // index無し (絶対アドレス)
+            Mem64::Sib {
+                base: None,
+                disp,
+                index: Reg64::RSP,
+                ..
+            } => write!(f, "[{}", Hex(*disp))?,
+            Mem64::Sib {
+                base: None,
+                disp,
+                index,
+                scale,
+            } => {
+                write!(f, "[{}*{}", index, 1 << scale)?;
+                fmt_disp(f, *disp)?;
+            }
+            Mem64::Sib {
+                base: Some(base),
+                disp,
+                index,
+                scale,
+            } => {
+                write!(f, "[{} + {}*{}", base, index, 1 << scale)?;
+                fmt_disp(f, *disp)?;
+            }
+        }
+        write!(f, "]")
+    }
+}
+
+fn fmt_disp(f: &mut Formatter, disp: i32) -> Result<(), FmtError> {
+    match disp {
+        0 => Ok(()),
+        1.. => write!(f, " + {:#x}", disp),
+        _ => write!(f, " - {:#x}", (disp as i64).abs()),
+    }
+}
+
+/// AT&T 記法 (`movq %r13, 0x2a(%rbp,%rax,8)`)
+///
+/// オペランドは src, dst の順で、ニーモニックにはオペランドサイズの接尾辞 (b/w/l/q) を付ける
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Att;
+
+impl Syntax for Att {
+    fn fmt_instruction(&self, inst: &Instruction, f: &mut Formatter) -> Result<(), FmtError> {
+        match inst {
+            Instruction::MovRegReg(Mov(dst, src)) => {
+                write!(
+                    f,
+                    "mov{} {}, {}",
+                    suffix(src.size()),
+                    AttReg(*src),
+                    AttReg(*dst)
+                )
+            }
+            Instruction::MovMemReg(Mov(dst, src)) => {
+                write!(
+                    f,
+                    "mov{} {}, {}",
+                    suffix(src.size()),
+                    AttReg(*src),
+                    AttMem(*dst)
+                )
+            }
+            Instruction::MovRegMem(Mov(dst, src)) => {
+                write!(
+                    f,
+                    "mov{} {}, {}",
+                    suffix(dst.size()),
+                    AttMem(*src),
+                    AttReg(*dst)
+                )
+            }
+            Instruction::MovRegImm(Mov(dst, src)) => {
+                write!(f, "mov{} ${:#x}, {}", suffix(dst.size()), src, AttReg(*dst))
+            }
+            Instruction::MovMemImm(Mov(Ptr(size, dst), src)) => {
+                write!(f, "mov{} ${}, {}", suffix(*size), Hex(*src), AttMem(*dst))
+            }
+            Instruction::Lea(Lea(dst, src)) => {
+                write!(
+                    f,
+                    "lea{} {}, {}",
+                    suffix(dst.size()),
+                    AttMem(*src),
+                    AttReg(*dst)
+                )
+            }
+            Instruction::Syscall(_) => write!(f, "syscall"),
+            Instruction::JmpI8(Jmp(rel)) => write!(f, "jmp {}", Hex(*rel)),
+            Instruction::JmpI32(Jmp(rel)) => write!(f, "jmp {}", Hex(*rel)),
+            Instruction::JmpReg64(Jmp(dst)) => write!(f, "jmp *{}", AttReg(*dst)),
+            Instruction::JmpMem64(Jmp(dst)) => write!(f, "jmp *{}", AttMem(*dst)),
+            Instruction::JccI8(Jcc(cond, rel)) => write!(f, "j{} {}", cond, Hex(*rel)),
+            Instruction::JccI32(Jcc(cond, rel)) => write!(f, "j{} {}", cond, Hex(*rel)),
+            Instruction::CallI32(Call(rel)) => write!(f, "call {}", Hex(*rel)),
+            Instruction::CallReg64(Call(dst)) => write!(f, "call *{}", AttReg(*dst)),
+            Instruction::CallMem64(Call(dst)) => write!(f, "call *{}", AttMem(*dst)),
+            Instruction::Ret(_) => write!(f, "ret"),
+            Instruction::RetImm(RetImm(imm)) => write!(f, "ret ${:#x}", imm),
+            Instruction::PushReg64(Push(src)) => write!(f, "pushq {}", AttReg(*src)),
+            Instruction::PushReg16(Push(src)) => write!(f, "pushw {}", AttReg(*src)),
+            Instruction::PushMem(Push(Ptr(size, src))) => {
+                write!(f, "push{} {}", suffix(*size), AttMem(*src))
+            }
+            Instruction::PushI8(Push(imm)) => write!(f, "pushq ${}", Hex(*imm)),
+            Instruction::PushI32(Push(imm)) => write!(f, "pushq ${}", Hex(*imm)),
+            Instruction::PopReg64(Pop(dst)) => write!(f, "popq {}", AttReg(*dst)),
+            Instruction::PopReg16(Pop(dst)) => write!(f, "popw {}", AttReg(*dst)),
+            Instruction::PopMem(Pop(Ptr(size, dst))) => {
+                write!(f, "pop{} {}", suffix(*size), AttMem(*dst))
+            }
+            Instruction::AluRegReg(Alu(op, dst, src)) => {
+                write!(
+                    f,
+                    "{}{} {}, {}",
+                    op,
+                    suffix(src.size()),
+                    AttReg(*src),
+                    AttReg(*dst)
+                )
+            }
+            Instruction::AluMemReg(Alu(op, dst, src)) => {
+                write!(
+                    f,
+                    "{}{} {}, {}",
+                    op,
+                    suffix(src.size()),
+                    AttReg(*src),
+                    AttMem(*dst)
+                )
+            }
+            Instruction::AluRegMem(Alu(op, dst, src)) => {
+                write!(
+                    f,
+                    "{}{} {}, {}",
+                    op,
+                    suffix(dst.size()),
+                    AttMem(*src),
+                    AttReg(*dst)
+                )
+            }
+            Instruction::AluRegImm(Alu(op, dst, src)) => {
+                write!(
+                    f,
+                    "{}{} ${}, {}",
+                    op,
+                    suffix(dst.size()),
+                    Hex(*src),
+                    AttReg(*dst)
+                )
+            }
+            Instruction::AluMemImm(Alu(op, Ptr(size, dst), src)) => {
+                write!(
+                    f,
+                    "{}{} ${}, {}",
+                    op,
+                    suffix(*size),
+                    Hex(*src),
+                    AttMem(*dst)
+                )
+            }
+            Instruction::ShiftRegImm(Shift(op, dst, count)) => {
+                write!(
+                    f,
+                    "{}{} ${:#x}, {}",
+                    op,
+                    suffix(dst.size()),
+                    count,
+                    AttReg(*dst)
+                )
+            }
+            Instruction::ShiftRegCl(Shift(op, dst, count)) => {
+                write!(
+                    f,
+                    "{}{} {}, {}",
+                    op,
+                    suffix(dst.size()),
+                    AttReg(*count),
+                    AttReg(*dst)
+                )
+            }
+            Instruction::ShiftMemImm(Shift(op, Ptr(size, dst), count)) => {
+                write!(f, "{}{} ${:#x}, {}", op, suffix(*size), count, AttMem(*dst))
+            }
+            Instruction::ShiftMemCl(Shift(op, Ptr(size, dst), count)) => {
+                write!(
+                    f,
+                    "{}{} {}, {}",
+                    op,
+                    suffix(*size),
+                    AttReg(*count),
+                    AttMem(*dst)
+                )
+            }
+            Instruction::DoubleShiftRegImm(DoubleShift(op, dst, src, count)) => {
+                write!(
+                    f,
+                    "{}{} ${:#x}, {}, {}",
+                    op,
+                    suffix(src.size()),
+                    count,
+                    AttReg(*src),
+                    AttReg(*dst)
+                )
+            }
+            Instruction::DoubleShiftRegCl(DoubleShift(op, dst, src, count)) => {
+                write!(
+                    f,
+                    "{}{} {}, {}, {}",
+                    op,
+                    suffix(src.size()),
+                    AttReg(*count),
+                    AttReg(*src),
+                    AttReg(*dst)
+                )
+            }
+            Instruction::DoubleShiftMemImm(DoubleShift(op, dst, src, count)) => {
+                write!(
+                    f,
+                    "{}{} ${:#x}, {}, {}",
+                    op,
+                    suffix(src.size()),
+                    count,
+                    AttReg(*src),
+                    AttMem(*dst)
+                )
+            }
+            Instruction::DoubleShiftMemCl(DoubleShift(op, dst, src, count)) => {
+                write!(
+                    f,
+                    "{}{} {}, {}, {}",
+                    op,
+                    suffix(src.size()),
+                    AttReg(*count),
+                    AttReg(*src),
+                    AttMem(*dst)
+                )
+            }
+            Instruction::MulDivReg(MulDiv(op, src)) => {
+                write!(f, "{}{} {}", op, suffix(src.size()), AttReg(*src))
+            }
+            Instruction::MulDivMem(MulDiv(op, Ptr(size, src))) => {
+                write!(f, "{}{} {}", op, suffix(*size), AttMem(*src))
+            }
+            Instruction::ImulRegReg(Imul(dst, src)) => {
+                write!(
+                    f,
+                    "imul{} {}, {}",
+                    suffix(dst.size()),
+                    AttReg(*src),
+                    AttReg(*dst)
+                )
+            }
+            Instruction::ImulRegMem(Imul(dst, src)) => {
+                write!(
+                    f,
+                    "imul{} {}, {}",
+                    suffix(dst.size()),
+                    AttMem(*src),
+                    AttReg(*dst)
+                )
+            }
+            Instruction::ImulRegRegImm(ImulImm(dst, src, imm)) => {
+                write!(
+                    f,
+                    "imul{} ${}, {}, {}",
+                    suffix(dst.size()),
+                    Hex(*imm),
+                    AttReg(*src),
+                    AttReg(*dst)
+                )
+            }
+            Instruction::ImulRegMemImm(ImulImm(dst, src, imm)) => {
+                write!(
+                    f,
+                    "imul{} ${}, {}, {}",
+                    suffix(dst.size()),
+                    Hex(*imm),
+                    AttMem(*src),
+                    AttReg(*dst)
+                )
+            }
+            Instruction::Cwd(_) => write!(f, "cwtd"),
+            Instruction::Cdq(_) => write!(f, "cltd"),
+            Instruction::Cqo(_) => write!(f, "cqto"),
+            Instruction::Cdqe(_) => write!(f, "cltq"),
+            Instruction::Lahf(_) => write!(f, "lahf"),
+            Instruction::Sahf(_) => write!(f, "sahf"),
+        }
+    }
+
+    /// `disp(base,index,scale)`
+    fn fmt_mem(&self, mem: &Mem64, f: &mut Formatter) -> Result<(), FmtError> {
+        match mem {
+            Mem64::RegOffset(reg, disp) => write!(f, "{}({})", AttDisp(*disp), AttReg(*reg)),
+            Mem64::RipOffset(disp) => write!(f, "{}(%rip)", AttDisp(*disp)),
+            // index無し (絶対アドレス)
+            Mem64::Sib {
+                base: None,
+                disp,
+                index: Reg64::RSP,
+                ..
+            } => write!(f, "{}", Hex(*disp)),
+            Mem64::Sib {
+                base: None,
+                disp,
+                index,
+                scale,
+            } => write!(f, "{}(,{},{})", AttDisp(*disp), AttReg(*index), 1 << scale),
+            Mem64::Sib {
+                base: Some(base),
+                disp,
+                index,
+                scale,
+            } => write!(
+                f,
+                "{}({},{},{})",
+                AttDisp(*disp),
+                AttReg(*base),
+                AttReg(*index),
+                1 << scale
+            ),
+        }
+    }
+}
+
+fn suffix(size: Size) -> char {
+    match size {
+        Size::Byte => 'b',
+        Size::Word => 'w',
+        Size::Dword => 'l',
+        Size::Qword => 'q',
+    }
+}
+
+struct AttReg<R>(R);
+
+impl<R: Display> Display for AttReg<R> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        write!(f, "%{}", self.0)
+    }
+}
+
+struct AttMem(Mem64);
+
+impl Display for AttMem {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        Att.fmt_mem(&self.0, f)
+    }
+}
+
+/// 0 は省略する
+struct AttDisp(i32);
+
+impl Display for AttDisp {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        match self.0 {
+            0 => Ok(()),
+            disp => Hex(disp).fmt(f),
+        }
+    }
+}
+
+/// 符号付きの16進 (`0x2a`, `-0x8`)
+struct Hex<T>(T);
+
+impl<T: Into<i64> + Copy> Display for Hex<T> {
+    fn fmt(&self, f: &mut Formatter) -> Result<(), FmtError> {
+        let imm: i64 = self.0.into();
+        if imm < 0 {
+            write!(f, "-{:#x}", (imm as i128).abs())
+        } else {
+            write!(f, "{:#x}", imm)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        instruction::{AluOp, Cdqe, Cl, Cond, Cwd, Ret, Syscall},
+        Reg16, Reg32, Reg8,
+    };
+
+    #[test]
+    fn test_att() {
+        use Reg64::*;
+
+        let cases = [
+            (
+                Instruction::from(Mov(Mem64::sib(Some(RBP), 42, RAX, 3), R13)),
+                "movq %r13, 0x2a(%rbp,%rax,8)",
+            ),
+            (
+                Instruction::from(Mov(Reg32::EAX, Mem64::reg_offset(RBP, -4))),
+                "movl -0x4(%rbp), %eax",
+            ),
+            (Instruction::from(Mov(Reg8::AH, Reg8::AL)), "movb %al, %ah"),
+            (
+                Instruction::from(Mov(RAX, 0x1_0000_0000)),
+                "movq $0x100000000, %rax",
+            ),
+            (
+                Instruction::from(Mov(Ptr::word(Mem64::reg(RDI)), -1)),
+                "movw $-0x1, (%rdi)",
+            ),
+            (
+                Instruction::from(Lea::new(RDI, Mem64::rip_offset(42))),
+                "leaq 0x2a(%rip), %rdi",
+            ),
+            (
+                Instruction::from(Lea(Reg32::ECX, Mem64::sib(None, 0, RDI, 2))),
+                "leal (,%rdi,4), %ecx",
+            ),
+            (Instruction::from(Syscall()), "syscall"),
+            (Instruction::from(Jmp(-2i8)), "jmp -0x2"),
+            (Instruction::from(Jmp(RAX)), "jmp *%rax"),
+            (Instruction::from(Jcc(Cond::NE, 0x100)), "jne 0x100"),
+            (
+                Instruction::from(Call(Mem64::reg_offset(RAX, 8))),
+                "call *0x8(%rax)",
+            ),
+            (Instruction::from(Ret()), "ret"),
+            (Instruction::from(RetImm(16)), "ret $0x10"),
+            (Instruction::from(Push(R12)), "pushq %r12"),
+            (Instruction::from(Push(-1i8)), "pushq $-0x1"),
+            (Instruction::from(Push(Reg16::R9W)), "pushw %r9w"),
+            (
+                Instruction::from(Pop(Ptr::word(Mem64::reg(RBP)))),
+                "popw (%rbp)",
+            ),
+            (Instruction::from(Pop(Mem64::reg(RSP))), "popq (%rsp)"),
+            (Instruction::from(Alu::sub(RSP, 8)), "subq $0x8, %rsp"),
+            (
+                Instruction::from(Alu(AluOp::Xor, Mem64::reg(RDI), Reg16::CX)),
+                "xorw %cx, (%rdi)",
+            ),
+            (
+                Instruction::from(Alu::add(Reg8::R8B, Mem64::sib(Some(R12), -8, R9, 1))),
+                "addb -0x8(%r12,%r9,2), %r8b",
+            ),
+            (
+                Instruction::from(Alu::cmp(Ptr::byte(Mem64::reg(RAX)), -128)),
+                "cmpb $-0x80, (%rax)",
+            ),
+            (
+                Instruction::from(Mov(RAX, Mem64::sib(None, 0x1000, RSP, 0))),
+                "movq 0x1000, %rax",
+            ),
+            (
+                Instruction::from(Mov(RAX, Mem64::sib(None, -8, RSP, 0))),
+                "movq -0x8, %rax",
+            ),
+            (Instruction::from(Shift::shl(RAX, 1)), "shlq $0x1, %rax"),
+            (Instruction::from(Shift::ror(Reg8::AL, Cl)), "rorb %cl, %al"),
+            (
+                Instruction::from(Shift::sar(Ptr::word(Mem64::reg(RDI)), 3)),
+                "sarw $0x3, (%rdi)",
+            ),
+            (
+                Instruction::from(DoubleShift::shld(Reg32::EAX, Reg32::EDX, 4)),
+                "shldl $0x4, %edx, %eax",
+            ),
+            (
+                Instruction::from(DoubleShift::shrd(Mem64::reg(RSI), R8, Cl)),
+                "shrdq %cl, %r8, (%rsi)",
+            ),
+            (Instruction::from(MulDiv::div(R9)), "divq %r9"),
+            (
+                Instruction::from(MulDiv::imul(Ptr::word(Mem64::reg(RAX)))),
+                "imulw (%rax)",
+            ),
+            (
+                Instruction::from(Imul(Reg32::EDX, Mem64::reg_offset(RSP, 8))),
+                "imull 0x8(%rsp), %edx",
+            ),
+            (
+                Instruction::from(ImulImm(RAX, RCX, -16)),
+                "imulq $-0x10, %rcx, %rax",
+            ),
+            (Instruction::from(Cwd()), "cwtd"),
+            (Instruction::from(Cdqe()), "cltq"),
+        ];
+
+        for (inst, expected) in cases {
+            assert_eq!(inst.display(&Att).to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn test_switch_syntax() {
+        let mem = Mem64::reg_offset(Reg64::RBP, -8);
+        let inst = Instruction::from(Mov(Reg64::RAX, mem));
+
+        let syntaxes: [&dyn Syntax; 2] = [&Intel, &Att];
+        let printed: Vec<_> = syntaxes
+            .iter()
+            .map(|syntax| {
+                (
+                    inst.display(*syntax).to_string(),
+                    mem.display(*syntax).to_string(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            printed,
+            [
+                (
+                    "mov rax, qword ptr [rbp - 0x8]".to_string(),
+                    "[rbp - 0x8]".to_string()
+                ),
+                (
+                    "movq -0x8(%rbp), %rax".to_string(),
+                    "-0x8(%rbp)".to_string()
+                ),
+            ]
+        );
+        assert_eq!(inst.display(&Intel).to_string(), inst.to_string());
+    }
+}
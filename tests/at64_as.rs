@@ -0,0 +1,148 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+const SRC: &str = "\
+.global _start
+_start:
+    mov eax, 60
+    mov edi, 42
+    syscall
+";
+
+const CODE: [u8; 12] = [
+    0xb8, 0x3c, 0x00, 0x00, 0x00, // mov eax, 60
+    0xbf, 0x2a, 0x00, 0x00, 0x00, // mov edi, 42
+    0x0f, 0x05, // syscall
+];
+
+/// テストごとに衝突しない一時ファイルのパス
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("at64-as-{}-{}", std::process::id(), name))
+}
+
+fn at64_as(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_at64-as"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// `src` を `-f format` でアセンブルし、出力ファイルの中身を返す
+fn assemble(name: &str, src: &str, format: &str) -> Vec<u8> {
+    let input = temp_path(&format!("{}.s", name));
+    let output = temp_path(&format!("{}.{}", name, format));
+    fs::write(&input, src).unwrap();
+
+    let out = at64_as(&[
+        "-f",
+        format,
+        "-o",
+        output.to_str().unwrap(),
+        input.to_str().unwrap(),
+    ]);
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+
+    let bytes = fs::read(&output).unwrap();
+    fs::remove_file(&input).unwrap();
+    fs::remove_file(&output).unwrap();
+    bytes
+}
+
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack.windows(needle.len()).any(|w| w == needle)
+}
+
+#[test]
+fn test_bin() {
+    assert_eq!(assemble("bin", SRC, "bin"), CODE);
+}
+
+#[test]
+fn test_hex() {
+    let out = String::from_utf8(assemble("hex", SRC, "hex")).unwrap();
+    let lines: Vec<&str> = out.lines().map(str::trim_end).collect();
+    assert_eq!(
+        lines,
+        [
+            "00000000                                  .globl _start",
+            "00000000                                  _start:",
+            "00000000  b8 3c 00 00 00                  mov eax, 0x3c",
+            "00000005  bf 2a 00 00 00                  mov edi, 0x2a",
+            "0000000a  0f 05                           syscall",
+        ]
+    );
+}
+
+#[test]
+fn test_elf() {
+    let obj = assemble("elf", SRC, "elf");
+    assert_eq!(obj[..4], *b"\x7fELF");
+    // e_type = ET_REL
+    assert_eq!(obj[16..18], [1, 0]);
+    assert!(contains(&obj, &CODE));
+    assert!(contains(&obj, b"_start\0"));
+}
+
+#[test]
+fn test_exe() {
+    let exe = assemble("exe", SRC, "exe");
+    assert_eq!(exe[..4], *b"\x7fELF");
+    // e_type = ET_EXEC
+    assert_eq!(exe[16..18], [2, 0]);
+    assert!(contains(&exe, &CODE));
+
+    // 実際に動かして終了コードを確かめる
+    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let path = temp_path("run.exe");
+        fs::write(&path, &exe).unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        let status = Command::new(&path).status().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(status.code(), Some(42));
+    }
+}
+
+#[test]
+fn test_error() {
+    let cases = [
+        ("    ret\n    mov ah, sil\n", ":2:9: "),
+        ("_start:\n    mov rax, [rsp*1 + 8]\n", ":2:15: "),
+        ("    jmp missing\n", "missing"),
+    ];
+
+    for (i, (src, msg)) in cases.iter().enumerate() {
+        let input = temp_path(&format!("error{}.s", i));
+        let output = temp_path(&format!("error{}.bin", i));
+        fs::write(&input, src).unwrap();
+
+        let out = at64_as(&["-o", output.to_str().unwrap(), input.to_str().unwrap()]);
+        fs::remove_file(&input).unwrap();
+
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert_eq!(out.status.code(), Some(1), "{:?}: {}", src, stderr);
+        assert!(stderr.starts_with("at64-as: "), "{:?}: {}", src, stderr);
+        assert!(stderr.contains(msg), "{:?}: {}", src, stderr);
+        assert!(!stderr.contains("panicked"), "{:?}: {}", src, stderr);
+        assert!(out.stdout.is_empty());
+        assert!(!output.exists());
+    }
+}
+
+#[test]
+fn test_usage() {
+    for args in [&["-f", "coff", "x.s"][..], &[]].iter() {
+        let out = at64_as(args);
+        assert_eq!(out.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&out.stderr).contains("usage: at64-as"));
+    }
+}
@@ -0,0 +1,229 @@
+use at_64::{
+    elf,
+    object::{Object, Section, SectionKind, Symbol},
+};
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+const CODE: [u8; 10] = [
+    0xb8, 0x3c, 0x00, 0x00, 0x00, // mov eax, 60
+    0x31, 0xff, // xor edi, edi
+    0x0f, 0x05, // syscall
+    0xc3, // ret
+];
+
+/// テストごとに衝突しない一時ファイルのパス
+fn temp_path(name: &str) -> PathBuf {
+    env::temp_dir().join(format!("at64-objdump-{}-{}", std::process::id(), name))
+}
+
+fn at64_objdump(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_at64-objdump"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// `data` をファイルに書いて逆アセンブルし、各行の末尾の空白を除いて返す
+fn disassemble(name: &str, data: &[u8], args: &[&str]) -> Vec<String> {
+    let input = temp_path(name);
+    fs::write(&input, data).unwrap();
+
+    let mut args = args.to_vec();
+    args.push(input.to_str().unwrap());
+    let out = at64_objdump(&args);
+    fs::remove_file(&input).unwrap();
+
+    let stderr = String::from_utf8_lossy(&out.stderr);
+    assert!(out.status.success(), "{}", stderr);
+    assert!(stderr.is_empty(), "{}", stderr);
+    String::from_utf8(out.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| line.trim_end().to_string())
+        .collect()
+}
+
+fn object() -> Vec<u8> {
+    let obj = Object {
+        sections: vec![Section::new(SectionKind::Text, CODE.to_vec())],
+        symbols: vec![Symbol {
+            name: "_start".to_string(),
+            def: Some((SectionKind::Text, 0)),
+            global: true,
+        }],
+    };
+    elf::write_object(&obj)
+}
+
+#[test]
+fn test_raw() {
+    let cases: [(&[&str], &[&str]); 2] = [
+        (
+            &["--raw"],
+            &[
+                "       0:  b8 3c 00 00 00                  mov eax, 0x3c",
+                "       5:  31 ff                           xor edi, edi",
+                "       7:  0f 05                           syscall",
+                "       9:  c3                              ret",
+            ],
+        ),
+        (
+            &["-M", "att", "--raw", "--base", "0x1000"],
+            &[
+                "    1000:  b8 3c 00 00 00                  movl $0x3c, %eax",
+                "    1005:  31 ff                           xorl %edi, %edi",
+                "    1007:  0f 05                           syscall",
+                "    1009:  c3                              ret",
+            ],
+        ),
+    ];
+
+    for (i, (args, expected)) in cases.iter().enumerate() {
+        let lines = disassemble(&format!("raw{}.bin", i), &CODE, args);
+        assert_eq!(lines, *expected, "{:?}", args);
+    }
+}
+
+#[test]
+fn test_elf() {
+    let obj = object();
+    let cases: [(&[&str], &[&str]); 2] = [
+        (
+            &[],
+            &[
+                "       0:  b8 3c 00 00 00                  mov eax, 0x3c",
+                "       5:  31 ff                           xor edi, edi",
+                "       7:  0f 05                           syscall",
+                "       9:  c3                              ret",
+            ],
+        ),
+        (
+            &["-M", "att"],
+            &[
+                "       0:  b8 3c 00 00 00                  movl $0x3c, %eax",
+                "       5:  31 ff                           xorl %edi, %edi",
+                "       7:  0f 05                           syscall",
+                "       9:  c3                              ret",
+            ],
+        ),
+    ];
+
+    for (i, (args, expected)) in cases.iter().enumerate() {
+        let lines = disassemble(&format!("elf{}.o", i), &obj, args);
+        assert_eq!(lines, *expected, "{:?}", args);
+    }
+
+    // --raw ならヘッダごとファイル全体を逆アセンブルする
+    let lines = disassemble("elf-raw.o", &obj, &["--raw"]);
+    assert_eq!(
+        lines[0],
+        "       0:  7f 45                           jg 0x45  # 0x47"
+    );
+    assert!(lines.len() > CODE.len());
+}
+
+#[test]
+fn test_bad() {
+    // 0f 0b (ud2) は未対応なので 0f だけを (bad) として読み飛ばし、次のバイトから読み直す
+    let data = [0x31, 0xff, 0x0f, 0x0b, 0x0f];
+    let cases: [(&[&str], &[&str]); 2] = [
+        (
+            &["--raw"],
+            &[
+                "       0:  31 ff                           xor edi, edi",
+                "       2:  0f                              (bad)",
+                "       3:  0b 0f                           or ecx, dword ptr [rdi]",
+            ],
+        ),
+        (
+            &["-M", "att", "--raw"],
+            &[
+                "       0:  31 ff                           xorl %edi, %edi",
+                "       2:  0f                              (bad)",
+                "       3:  0b 0f                           orl (%rdi), %ecx",
+            ],
+        ),
+    ];
+
+    for (i, (args, expected)) in cases.iter().enumerate() {
+        let lines = disassemble(&format!("bad{}.bin", i), &data, args);
+        assert_eq!(lines, *expected, "{:?}", args);
+    }
+
+    // 末尾で途切れた命令も (bad)
+    let lines = disassemble("truncated.bin", &[0xc3, 0xb8, 0x01], &["--raw"]);
+    assert_eq!(
+        lines,
+        [
+            "       0:  c3                              ret",
+            "       1:  b8                              (bad)",
+            "       2:  01                              (bad)",
+        ]
+    );
+}
+
+#[test]
+fn test_error() {
+    let missing = temp_path("missing.bin");
+    let cases = [
+        vec!["-M", "gas", "x.bin"],
+        vec!["--base", "zz", "x.bin"],
+        vec![],
+        vec![missing.to_str().unwrap()],
+    ];
+
+    for args in cases.iter() {
+        let out = at64_objdump(args);
+        let stderr = String::from_utf8_lossy(&out.stderr);
+        assert_eq!(out.status.code(), Some(1), "{:?}: {}", args, stderr);
+        assert!(
+            stderr.starts_with("at64-objdump: "),
+            "{:?}: {}",
+            args,
+            stderr
+        );
+        assert!(!stderr.contains("panicked"), "{:?}: {}", args, stderr);
+        assert!(out.stdout.is_empty());
+    }
+}
+
+#[test]
+fn test_broken_pipe() {
+    use std::{
+        io::{BufRead as _, BufReader},
+        process::Stdio,
+    };
+
+    // パイプの容量を大きく超える出力にする
+    let input = temp_path("pipe.bin");
+    fs::write(&input, vec![0xc3; 1 << 20]).unwrap();
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_at64-objdump"))
+        .args(["--raw", input.to_str().unwrap()])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // `| head -1` と同じく、1行読んだら読み手を閉じる
+    let mut line = String::new();
+    BufReader::new(child.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    assert_eq!(
+        line.trim_end(),
+        "       0:  c3                              ret"
+    );
+
+    let out = child.wait_with_output().unwrap();
+    fs::remove_file(&input).unwrap();
+    assert!(
+        out.status.success(),
+        "{}",
+        String::from_utf8_lossy(&out.stderr)
+    );
+    assert!(out.stderr.is_empty());
+}